@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+
+ALTER TABLE "media_payment" DROP COLUMN "settled_at";
+ALTER TABLE "api_payment" DROP COLUMN "settled_at";
@@ -0,0 +1,4 @@
+-- Your SQL goes here
+
+ALTER TABLE "media_payment" ADD COLUMN "settled_at" TIMESTAMP WITH TIME ZONE DEFAULT NULL;
+ALTER TABLE "api_payment" ADD COLUMN "settled_at" TIMESTAMP WITH TIME ZONE DEFAULT NULL;
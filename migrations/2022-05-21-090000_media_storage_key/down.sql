@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+
+ALTER TABLE "media" RENAME COLUMN "storage_key" TO "absolute_path";
@@ -0,0 +1,5 @@
+-- Your SQL goes here
+
+-- Files are addressed through the storage backend.
+-- Existing absolute paths remain valid keys for the local storage.
+ALTER TABLE "media" RENAME COLUMN "absolute_path" TO "storage_key";
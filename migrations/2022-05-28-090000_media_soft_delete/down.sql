@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+
+ALTER TABLE "media" DROP COLUMN "purged_at";
+ALTER TABLE "media" DROP COLUMN "deleted_at";
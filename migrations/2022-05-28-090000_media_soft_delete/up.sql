@@ -0,0 +1,6 @@
+-- Your SQL goes here
+
+-- Deleted media are kept as archives so their payments history remains,
+-- their file is purged from the storage once the grace period is over.
+ALTER TABLE "media" ADD COLUMN "deleted_at" TIMESTAMP WITH TIME ZONE DEFAULT NULL;
+ALTER TABLE "media" ADD COLUMN "purged_at" TIMESTAMP WITH TIME ZONE DEFAULT NULL;
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+
+DROP INDEX "media_checksum_idx";
+
+ALTER TABLE "media" DROP COLUMN "checksum";
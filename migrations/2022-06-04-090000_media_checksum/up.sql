@@ -0,0 +1,6 @@
+-- Your SQL goes here
+
+-- Files are stored by their content hash, which is recorded on the media.
+ALTER TABLE "media" ADD COLUMN "checksum" TEXT DEFAULT NULL;
+
+CREATE INDEX "media_checksum_idx" ON "media" ("checksum");
@@ -0,0 +1,13 @@
+-- Your SQL goes here
+
+-- Resumable uploads in progress. The media is created once the upload is complete.
+CREATE TABLE IF NOT EXISTS "upload" (
+    "uuid" uuid UNIQUE PRIMARY KEY NOT NULL,
+    "user_uuid" uuid references "user"(uuid) NOT NULL,
+    "upload_length" BIGINT NOT NULL,
+    "upload_offset" BIGINT NOT NULL DEFAULT 0,
+    "metadata" TEXT NOT NULL,
+    "media_uuid" uuid references media(uuid) DEFAULT NULL,
+    "created_at" TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    "expires_at" TIMESTAMP WITH TIME ZONE NOT NULL
+);
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+
+DROP TABLE IF EXISTS "bundle_payment";
+DROP TABLE IF EXISTS "bundle_media";
+DROP TABLE IF EXISTS "bundle";
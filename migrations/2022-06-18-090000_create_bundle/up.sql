@@ -0,0 +1,34 @@
+-- Your SQL goes here
+
+-- A priced collection of media unlocked by a single payment
+CREATE TABLE IF NOT EXISTS "bundle" (
+    "uuid" uuid UNIQUE PRIMARY KEY NOT NULL,
+    "title" TEXT NOT NULL,
+    "description" TEXT,
+    "price" INTEGER NOT NULL DEFAULT 0,
+    "payment_duration" INTEGER DEFAULT NULL,
+    "published" BOOLEAN NOT NULL DEFAULT false,
+    "created_at" TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    "updated_at" TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS "bundle_media" (
+    "bundle_uuid" uuid references bundle(uuid) ON DELETE CASCADE NOT NULL,
+    "media_uuid" uuid references media(uuid) NOT NULL,
+    "position" INTEGER NOT NULL,
+    PRIMARY KEY( bundle_uuid, media_uuid )
+);
+
+CREATE INDEX IF NOT EXISTS "bundle_media_media_uuid" ON "bundle_media" ("media_uuid");
+
+CREATE TABLE IF NOT EXISTS "bundle_payment" (
+    "uuid" uuid UNIQUE NOT NULL,
+    "request" TEXT UNIQUE NOT NULL,
+    "state" TEXT,
+    "hash" TEXT UNIQUE NOT NULL,
+    "bundle_uuid" uuid references bundle(uuid) NOT NULL,
+    "expires_at" TIMESTAMP WITH TIME ZONE NOT NULL,
+    "valid_until" TIMESTAMP WITH TIME ZONE DEFAULT NULL,
+    "settled_at" TIMESTAMP WITH TIME ZONE DEFAULT NULL,
+    PRIMARY KEY( uuid )
+);
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+
+ALTER TABLE "media" DROP COLUMN "thumbnail_key";
+ALTER TABLE "media" DROP COLUMN "preview_filename";
+ALTER TABLE "media" DROP COLUMN "preview_key";
@@ -0,0 +1,7 @@
+-- Your SQL goes here
+
+-- Free assets a prospective buyer can access before paying
+-- for a media, stored along with the media file.
+ALTER TABLE "media" ADD COLUMN "preview_key" TEXT DEFAULT NULL;
+ALTER TABLE "media" ADD COLUMN "preview_filename" TEXT DEFAULT NULL;
+ALTER TABLE "media" ADD COLUMN "thumbnail_key" TEXT DEFAULT NULL;
@@ -0,0 +1,8 @@
+-- This file should undo anything in `up.sql`
+
+ALTER TABLE "media" DROP COLUMN "page_count";
+ALTER TABLE "media" DROP COLUMN "duration";
+ALTER TABLE "media" DROP COLUMN "height";
+ALTER TABLE "media" DROP COLUMN "width";
+ALTER TABLE "media" DROP COLUMN "size_bytes";
+ALTER TABLE "media" DROP COLUMN "mime_type";
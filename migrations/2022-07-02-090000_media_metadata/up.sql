@@ -0,0 +1,9 @@
+-- Your SQL goes here
+
+-- Metadata extracted from the file of a media when it is uploaded
+ALTER TABLE "media" ADD COLUMN "mime_type" TEXT DEFAULT NULL;
+ALTER TABLE "media" ADD COLUMN "size_bytes" BIGINT DEFAULT NULL;
+ALTER TABLE "media" ADD COLUMN "width" INTEGER DEFAULT NULL;
+ALTER TABLE "media" ADD COLUMN "height" INTEGER DEFAULT NULL;
+ALTER TABLE "media" ADD COLUMN "duration" DOUBLE PRECISION DEFAULT NULL;
+ALTER TABLE "media" ADD COLUMN "page_count" INTEGER DEFAULT NULL;
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+
+ALTER TABLE "media" DROP COLUMN "download_filename";
+ALTER TABLE "media" DROP COLUMN "original_filename";
@@ -0,0 +1,12 @@
+-- Your SQL goes here
+
+-- Files are stored by their content hash, so the name
+-- of the uploaded file is recorded on the media.
+ALTER TABLE "media" ADD COLUMN "original_filename" TEXT NOT NULL DEFAULT '';
+
+UPDATE "media" SET "original_filename" = regexp_replace("storage_key", '^.*/', '')
+WHERE "storage_key" NOT LIKE 'sha256/%';
+
+-- The name a media file is downloaded as, when the publisher
+-- overrides the name of the uploaded file.
+ALTER TABLE "media" ADD COLUMN "download_filename" TEXT DEFAULT NULL;
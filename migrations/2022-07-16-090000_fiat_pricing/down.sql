@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+
+ALTER TABLE "media_payment" DROP COLUMN "exchange_rate";
+ALTER TABLE "media_payment" DROP COLUMN "price_currency";
+ALTER TABLE "media" DROP COLUMN "price_amount";
+ALTER TABLE "media" DROP COLUMN "price_currency";
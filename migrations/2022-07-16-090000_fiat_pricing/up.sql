@@ -0,0 +1,10 @@
+-- Your SQL goes here
+
+-- Media priced in a fiat currency, in the ISO 4217 minor unit of the currency.
+-- The amount of satoshis is converted at the current rate for each invoice.
+ALTER TABLE "media" ADD COLUMN "price_currency" TEXT DEFAULT NULL;
+ALTER TABLE "media" ADD COLUMN "price_amount" INTEGER DEFAULT NULL;
+
+-- The rate a fiat price was converted with, for auditing
+ALTER TABLE "media_payment" ADD COLUMN "price_currency" TEXT DEFAULT NULL;
+ALTER TABLE "media_payment" ADD COLUMN "exchange_rate" DOUBLE PRECISION DEFAULT NULL;
@@ -0,0 +1,8 @@
+-- This file should undo anything in `up.sql`
+
+-- Sub-satoshi prices are rounded up
+ALTER TABLE "bundle" RENAME COLUMN "price_msat" TO "price";
+ALTER TABLE "bundle" ALTER COLUMN "price" TYPE INTEGER USING CEIL("price" / 1000.0)::INTEGER;
+
+ALTER TABLE "media" RENAME COLUMN "price_msat" TO "price";
+ALTER TABLE "media" ALTER COLUMN "price" TYPE INTEGER USING CEIL("price" / 1000.0)::INTEGER;
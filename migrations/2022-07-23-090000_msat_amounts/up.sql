@@ -0,0 +1,8 @@
+-- Your SQL goes here
+
+-- Prices are recorded in millisatoshis, as 64 bits integers
+ALTER TABLE "media" ALTER COLUMN "price" TYPE BIGINT USING "price"::BIGINT * 1000;
+ALTER TABLE "media" RENAME COLUMN "price" TO "price_msat";
+
+ALTER TABLE "bundle" ALTER COLUMN "price" TYPE BIGINT USING "price"::BIGINT * 1000;
+ALTER TABLE "bundle" RENAME COLUMN "price" TO "price_msat";
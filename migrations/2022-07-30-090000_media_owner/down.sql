@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+
+DROP INDEX "media_owner_uuid_idx";
+ALTER TABLE "media" DROP COLUMN "owner_uuid";
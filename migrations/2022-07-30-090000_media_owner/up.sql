@@ -0,0 +1,5 @@
+-- Your SQL goes here
+
+-- The user who uploaded the media. Media uploaded before owners were recorded have none.
+ALTER TABLE "media" ADD COLUMN "owner_uuid" uuid references "user"(uuid) ON DELETE SET NULL DEFAULT NULL;
+CREATE INDEX "media_owner_uuid_idx" ON "media" ("owner_uuid");
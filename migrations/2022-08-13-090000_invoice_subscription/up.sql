@@ -0,0 +1,10 @@
+-- Your SQL goes here
+
+-- The settle index of the last invoice recorded by the invoices subscription,
+-- so that the settlements happening while the server is down are replayed on restart.
+CREATE TABLE "invoice_subscription" (
+    "id" SMALLINT PRIMARY KEY DEFAULT 1 CHECK ("id" = 1),
+    "settle_index" BIGINT NOT NULL DEFAULT 0
+);
+
+INSERT INTO "invoice_subscription" ("id", "settle_index") VALUES (1, 0);
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+
+ALTER TABLE "bundle_payment" DROP COLUMN "client_secret_hash";
+ALTER TABLE "media_payment" DROP COLUMN "client_secret_hash";
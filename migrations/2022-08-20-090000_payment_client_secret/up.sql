@@ -0,0 +1,6 @@
+-- Your SQL goes here
+
+-- Hash of the secret returned to the client that requested the invoice.
+-- Only this client can get a download token without providing the invoice preimage.
+ALTER TABLE "media_payment" ADD COLUMN "client_secret_hash" TEXT DEFAULT NULL;
+ALTER TABLE "bundle_payment" ADD COLUMN "client_secret_hash" TEXT DEFAULT NULL;
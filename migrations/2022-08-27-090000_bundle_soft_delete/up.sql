@@ -0,0 +1,4 @@
+-- Your SQL goes here
+
+-- Deleted bundles are kept as archives so their payments history remains.
+ALTER TABLE "bundle" ADD COLUMN "deleted_at" TIMESTAMP WITH TIME ZONE DEFAULT NULL;
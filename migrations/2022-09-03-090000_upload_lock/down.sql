@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+
+ALTER TABLE "upload" DROP COLUMN "locked_until";
+ALTER TABLE "upload" DROP COLUMN "locked_by";
@@ -0,0 +1,6 @@
+-- Your SQL goes here
+
+-- The request or task writing to an upload holds a lease on it until it is released or expires,
+-- so a single server instance writes to an upload at a time.
+ALTER TABLE "upload" ADD COLUMN "locked_by" UUID DEFAULT NULL;
+ALTER TABLE "upload" ADD COLUMN "locked_until" TIMESTAMP WITH TIME ZONE DEFAULT NULL;
@@ -1,19 +1,21 @@
 ///
 /// Todo : Split routes in different files
 ///
-use crate::{
-    graphql::{context::GQLContext, mutation::Mutation, query::Query},
-    guards::userguard::UserGuard,
-    lnd::client::LndClient,
+use crate::graphql::{
+    context::GQLContext, mutation::Mutation, query::Query, subscription::Subscription,
 };
 use juniper_rocket_multipart_handler::graphql_upload_wrapper::GraphQLUploadWrapper;
 use rocket::State;
-pub type Schema = RootNode<'static, Query, Mutation, EmptySubscription<GQLContext>>;
-use crate::db::PostgresConn;
+pub type Schema = RootNode<'static, Query, Mutation, Subscription>;
 use crate::guards::paymentrequestheader::PaymentRequestHeader;
-use juniper::{EmptySubscription, RootNode};
+use juniper::RootNode;
 use juniper_rocket::GraphQLResponse;
 
+/// Builds the GraphQL schema served over HTTP and WebSocket
+pub fn build_schema() -> Schema {
+    Schema::new(Query, Mutation, Subscription)
+}
+
 /*
     This is a void handler that will return a 200 empty response
     for browsers that intends to check pre-flight for CORS rules.
@@ -31,23 +33,14 @@ pub async fn auth_options_handler() {}
 pub async fn post_graphql_handler(
     request: GraphQLUploadWrapper,
     schema: &State<Schema>,
-    db: PostgresConn,
-    user_guard: UserGuard,
-    lnd: LndClient,
+    context: GQLContext,
 ) -> GraphQLResponse {
-    request
-        .operations
-        .execute(
-            &*schema,
-            &GQLContext {
-                pool: db,
-                lnd: lnd,
-                files: request.files,
-                user: user_guard.0,
-                server_config: None,
-            },
-        )
-        .await
+    let context = GQLContext {
+        files: request.files,
+        ..context
+    };
+
+    request.operations.execute(&*schema, &context).await
 }
 
 /// Calls the API through an API-scoped paywall
@@ -55,46 +48,22 @@ pub async fn post_graphql_handler(
 pub async fn payable_post_graphql_handler(
     request: juniper_rocket::GraphQLRequest,
     schema: &State<Schema>,
-    db: PostgresConn,
-    lnd: LndClient,
     _payment_request: PaymentRequestHeader,
-    user_guard: UserGuard,
+    context: GQLContext,
 ) -> GraphQLResponse {
-    request
-        .execute(
-            &*schema,
-            &GQLContext {
-                pool: db,
-                lnd: lnd,
-                files: None,
-                user: user_guard.0,
-                server_config: None,
-            },
-        )
-        .await
+    request.execute(&*schema, &context).await
 }
 
 #[rocket::post("/upload", data = "<request>")]
 pub async fn upload<'r>(
     request: GraphQLUploadWrapper,
     schema: &State<Schema>,
-    db: PostgresConn,
-    user_guard: UserGuard,
-    lnd: LndClient,
+    context: GQLContext,
 ) -> GraphQLResponse {
-    let result = request
-        .operations
-        .execute(
-            &*schema,
-            &GQLContext {
-                pool: db,
-                lnd,
-                files: request.files,
-                user: user_guard.0,
-                server_config: None,
-            },
-        )
-        .await;
+    let context = GQLContext {
+        files: request.files,
+        ..context
+    };
 
-    result
+    request.operations.execute(&*schema, &context).await
 }
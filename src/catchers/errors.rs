@@ -0,0 +1,41 @@
+use crate::responders::error::{ErrorCode, ErrorResponder};
+use rocket::{catch, Request};
+
+/*
+   Catchers replying to the errors raised by Rocket, e.g: unmatched routes or
+   failing guards, with the same JSON body as the routes.
+*/
+
+#[catch(400)]
+pub fn bad_request(_: &Request) -> ErrorResponder {
+    ErrorResponder::new(ErrorCode::BadRequest, "The request is malformed")
+}
+
+#[catch(401)]
+pub fn unauthorized(_: &Request) -> ErrorResponder {
+    ErrorResponder::new(
+        ErrorCode::Unauthenticated,
+        "You need to be authenticated to perform this request",
+    )
+}
+
+#[catch(403)]
+pub fn forbidden(_: &Request) -> ErrorResponder {
+    ErrorResponder::new(
+        ErrorCode::Forbidden,
+        "You do not have the required permission to perform this request",
+    )
+}
+
+#[catch(404)]
+pub fn not_found(request: &Request) -> ErrorResponder {
+    ErrorResponder::new(
+        ErrorCode::NotFound,
+        format!("No resource found at {}", request.uri().path()),
+    )
+}
+
+#[catch(500)]
+pub fn internal_error(_: &Request) -> ErrorResponder {
+    ErrorResponder::new(ErrorCode::InternalError, "An internal error happened")
+}
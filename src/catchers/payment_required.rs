@@ -1,8 +1,10 @@
 use crate::db::models::api_payment::ApiPayment;
 use crate::db::PostgresConn;
+use crate::errors::payment::PaymentError;
+use crate::l402::{self, L402Service};
 use crate::lnd::client::LndClient;
-use rocket::response::content::RawJson;
-use rocket::response::status;
+use crate::lnd::invoice::InvoiceDetails;
+use crate::responders::error::{ErrorCode, ErrorResponder};
 use rocket::{catch, http::Status, Request};
 
 /**
@@ -10,36 +12,56 @@ use rocket::{catch, http::Status, Request};
    It allows us to populate the response body with custom data
 */
 #[catch(402)]
-pub async fn payment_required<'r>(
-    _: Status,
-    request: &'r Request<'_>,
-) -> Result<status::Custom<RawJson<String>>, Status> {
+pub async fn payment_required<'r>(_: Status, request: &'r Request<'_>) -> ErrorResponder {
     let pool = request.guard::<PostgresConn>().await.succeeded();
     let lnd_client_result = request.guard::<LndClient>().await.succeeded();
 
-    match pool {
-        Some(db) => {
-            match lnd_client_result {
-                Some(lnd_client) => {
-                    //    see https://api.rocket.rs/v0.5-rc/rocket/request/macro.local_cache.html
-                    let payment_request_result = request
-                        .local_cache_async(async {
-                            ApiPayment::create_from_client(lnd_client, db, None).await
-                        })
-                        .await;
-                    match payment_request_result {
-                        Ok(payment_request) => {
-                            let json_state =
-                                format!(r#"{{"payment": {:?}}}"#, payment_request.request.as_str());
-                            Ok(status::Custom(Status::PaymentRequired, RawJson(json_state)))
-                        }
-                        Err(_) => Err(Status::InternalServerError),
-                    }
-                    // todo: use serde json and handle response with structs
-                }
-                None => Err(Status::InternalServerError),
+    let (db, lnd_client) = match (pool, lnd_client_result) {
+        (Some(db), Some(lnd_client)) => (db, lnd_client),
+        _ => {
+            return ErrorResponder::new(
+                ErrorCode::InternalError,
+                "Unable to generate the payment request",
+            )
+        }
+    };
+
+    //    see https://api.rocket.rs/v0.5-rc/rocket/request/macro.local_cache.html
+    let payment_request_result = request
+        .local_cache_async(async { ApiPayment::create_from_client(lnd_client, db, None).await })
+        .await;
+
+    match payment_request_result {
+        Ok(payment_request) => {
+            let mut response = ErrorResponder::payment_required(InvoiceDetails::new(
+                &payment_request.request,
+                &payment_request.hash,
+                payment_request.expires_at,
+            ));
+
+            // Provides the L402 challenge for standard lightning HTTP clients
+            match l402::challenge(
+                &L402Service::Api,
+                &payment_request.hash,
+                &payment_request.request,
+                None,
+            ) {
+                Ok(challenge) => response = response.with_header(challenge),
+                Err(e) => warn!("Unable to build the L402 challenge: {}", e),
             }
+
+            response
+        }
+        Err(PaymentError::LightningError(e)) => {
+            error!("Unable to generate the payment request of the API: {}", e);
+            ErrorResponder::new(
+                ErrorCode::LnUnavailable,
+                "Error while requesting lightning network registry",
+            )
+        }
+        Err(PaymentError::DbError(e)) => {
+            error!("Unable to register the payment request of the API: {}", e);
+            ErrorResponder::new(ErrorCode::InternalError, "Error while requesting database")
         }
-        None => Err(Status::InternalServerError),
     }
 }
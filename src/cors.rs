@@ -51,16 +51,28 @@ impl Fairing for Cors {
             "Access-Control-Allow-Credentials",
             config.allow_credentials,
         ));
+        // Allows browser clients to read the L402 challenge and the state of resumable uploads
+        response.set_header(Header::new(
+            "Access-Control-Expose-Headers",
+            "WWW-Authenticate, Location, Upload-Offset, Upload-Length, Upload-Expires, \
+             Tus-Resumable, Tus-Version, Tus-Extension, Tus-Max-Size, Media-Uuid",
+        ));
     }
 }
 
 impl Cors {
+    /// Checks the provided origin is allowed by the `CORS_ORIGIN_POLICY`
+    pub fn allows_origin(origin: &str) -> bool {
+        let allowed = Cors::get_config().allow_origin;
+        allowed == "*" || allowed == origin
+    }
+
     // Creates a config with environment variables if set and default values for
     // values not set in env variables
     fn get_config() -> CorsConfig {
         let origin_policy = env::var("CORS_ORIGIN_POLICY").unwrap_or("*".to_string());
-        let allow_methods =
-            env::var("CORS_METHOD_POLICY").unwrap_or("POST, GET, PATCH, OPTIONS".to_string());
+        let allow_methods = env::var("CORS_METHOD_POLICY")
+            .unwrap_or("POST, GET, PATCH, HEAD, DELETE, OPTIONS".to_string());
         let allow_headers = env::var("CORS_HEADERS_POLICY").unwrap_or("*".to_string());
         let allow_credentials = env::var("CORS_CREDENTIALS_POLICY").unwrap_or("false".to_string());
 
@@ -1,6 +1,6 @@
 use std::env;
 
-use super::PostgresConn;
+use super::{pool::DbPool, PostgresConn};
 use crate::db::models::user::NewUser;
 use crate::db::models::user::UserRoleEnum;
 use bcrypt::hash;
@@ -36,6 +36,17 @@ pub async fn run_db_migrations(rocket: Rocket<Build>) -> Result<Rocket<Build>, R
     .await
 }
 
+// Creates the database pool of the tasks running outside of requests on ignite of the server
+pub async fn setup_db_pool(rocket: Rocket<Build>) -> Result<Rocket<Build>, Rocket<Build>> {
+    match DbPool::from(&rocket) {
+        Ok(pool) => Ok(rocket.manage(pool)),
+        Err(e) => {
+            error!("Unable to set up the database pool: {}", e);
+            Err(rocket)
+        }
+    }
+}
+
 // populates the database on ignite of the server
 // if `DATABASE_SEED_ON_IGNITE` env var is set to true
 pub async fn seed_db(rocket: Rocket<Build>) -> Result<Rocket<Build>, Rocket<Build>> {
@@ -1,9 +1,13 @@
-use rocket_sync_db_pools::{database, diesel};
+use rocket_sync_db_pools::{
+    database,
+    diesel::{self, PgConnection, QueryResult},
+};
 
-use self::models::api_payment::ApiPayment;
+use self::{models::api_payment::ApiPayment, pool::DbPool};
 
 pub mod igniter;
 pub mod models;
+pub mod pool;
 pub mod schema;
 
 #[database("main_db")]
@@ -15,3 +19,26 @@ impl PostgresConn {
             .await
     }
 }
+
+/// The database connection of a GraphQL context.
+///
+/// HTTP requests hold the connection of their `PostgresConn` guard while WebSocket
+/// connections take one from the pool for each call, so that pending subscriptions
+/// don't hold any.
+pub enum DbConnection {
+    Request(PostgresConn),
+    Pool(DbPool),
+}
+
+impl DbConnection {
+    pub async fn run<F, T>(&self, f: F) -> QueryResult<T>
+    where
+        F: FnOnce(&PgConnection) -> QueryResult<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        match self {
+            DbConnection::Request(connection) => connection.run(move |c| f(c)).await,
+            DbConnection::Pool(pool) => pool.run(f).await,
+        }
+    }
+}
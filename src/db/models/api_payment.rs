@@ -1,11 +1,11 @@
 pub use crate::db::schema::api_payment;
 use crate::db::PostgresConn;
+use crate::errors::payment::PaymentError;
 use crate::lnd::client::LndClient;
 use crate::lnd::invoice::{InvoiceParams, InvoiceUtils, LndInvoice};
 use chrono::NaiveDateTime;
 use diesel;
 use diesel::prelude::*;
-use diesel::result::Error;
 use uuid::Uuid;
 
 #[derive(Queryable, PartialEq, Associations)]
@@ -16,6 +16,7 @@ pub struct ApiPayment {
     pub state: Option<String>,
     pub hash: String,
     pub expires_at: NaiveDateTime,
+    pub settled_at: Option<NaiveDateTime>,
 }
 
 #[derive(Debug, Insertable)]
@@ -64,16 +65,33 @@ impl ApiPayment {
             .unwrap()
     }
 
+    /// Records the final state of the payment matching the provided hash
+    pub fn update_state_by_hash(
+        payment_hash: String,
+        payment_state: String,
+        settlement_date: Option<NaiveDateTime>,
+        connection: &PgConnection,
+    ) -> QueryResult<usize> {
+        use crate::db::schema::api_payment::dsl::*;
+
+        diesel::update(api_payment.filter(hash.eq(payment_hash)))
+            .set((state.eq(payment_state), settled_at.eq(settlement_date)))
+            .execute(connection)
+    }
+
     pub async fn create_from_client(
         lnd_client: LndClient,
         db: PostgresConn,
         _invoice_params: Option<InvoiceParams>,
-    ) -> Result<ApiPayment, Error> {
-        let client = lnd_client.0;
+    ) -> Result<ApiPayment, PaymentError> {
         let invoice =
-            InvoiceUtils::generate_invoice(client, InvoiceParams::new(None, None, None)).await;
+            InvoiceUtils::generate_invoice(&lnd_client, InvoiceParams::new(None, None, None))
+                .await?;
+
+        let api_payment = db
+            .run(move |c| Self::create(NewApiPayment::from(invoice), c))
+            .await?;
 
-        db.run(move |c| Self::create(NewApiPayment::from(invoice), c))
-            .await
+        Ok(api_payment)
     }
 }
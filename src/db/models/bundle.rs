@@ -0,0 +1,191 @@
+pub use crate::db::schema::bundle;
+pub use crate::db::schema::bundle_media;
+
+use crate::graphql::types::input::bundle::NewBundleInput;
+use chrono::{NaiveDateTime, Utc};
+use diesel;
+use diesel::prelude::*;
+use diesel::PgConnection;
+use uuid::Uuid;
+
+use super::media::Media;
+
+/// A priced collection of media unlocked by a single payment
+#[derive(Identifiable, Queryable, PartialEq, Debug, Clone)]
+#[primary_key(uuid)]
+#[table_name = "bundle"]
+pub struct Bundle {
+    pub uuid: Uuid,
+    pub title: String,
+    pub description: Option<String>,
+    pub price_msat: i64,
+    pub payment_duration: Option<i32>,
+    pub published: bool,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    pub deleted_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "bundle"]
+pub struct NewBundle {
+    pub uuid: Uuid,
+    pub title: String,
+    pub description: Option<String>,
+    pub price_msat: i64,
+    pub payment_duration: Option<i32>,
+    pub published: bool,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "bundle_media"]
+struct NewBundleMedia {
+    bundle_uuid: Uuid,
+    media_uuid: Uuid,
+    position: i32,
+}
+
+impl From<NewBundleInput> for NewBundle {
+    fn from(bundle_input: NewBundleInput) -> Self {
+        Self {
+            uuid: Uuid::new_v4(),
+            title: bundle_input.title,
+            description: bundle_input.description,
+            price_msat: bundle_input.price_msat.0,
+            payment_duration: bundle_input.payment_duration,
+            published: bundle_input.published,
+        }
+    }
+}
+
+impl Bundle {
+    /// Creates a bundle of the provided media, kept in the provided order.
+    /// Fails with `NotFound` if one of the media is deleted, its row is locked
+    /// until the bundle is created so it can't be deleted meanwhile.
+    pub fn create(
+        new_bundle: NewBundle,
+        media_uuids: Vec<Uuid>,
+        connection: &PgConnection,
+    ) -> QueryResult<Bundle> {
+        use crate::db::schema::media;
+
+        connection.transaction(|| {
+            let available = media::table
+                .filter(media::uuid.eq_any(&media_uuids))
+                .filter(media::deleted_at.is_null())
+                .select(media::uuid)
+                .for_update()
+                .load::<Uuid>(connection)?;
+            if available.len() != media_uuids.len() {
+                return Err(diesel::result::Error::NotFound);
+            }
+
+            let created = diesel::insert_into(bundle::table)
+                .values(&new_bundle)
+                .get_result::<Bundle>(connection)?;
+
+            let contents = media_uuids
+                .into_iter()
+                .enumerate()
+                .map(|(position, media_uuid)| NewBundleMedia {
+                    bundle_uuid: created.uuid,
+                    media_uuid,
+                    position: position as i32,
+                })
+                .collect::<Vec<NewBundleMedia>>();
+
+            diesel::insert_into(bundle_media::table)
+                .values(&contents)
+                .execute(connection)?;
+
+            Ok(created)
+        })
+    }
+
+    pub fn find_all(connection: &PgConnection) -> QueryResult<Vec<Bundle>> {
+        use crate::db::schema::bundle::dsl::*;
+
+        bundle
+            .filter(deleted_at.is_null())
+            .order(created_at.desc())
+            .load(connection)
+    }
+
+    pub fn find_all_published(connection: &PgConnection) -> QueryResult<Vec<Bundle>> {
+        use crate::db::schema::bundle::dsl::*;
+
+        bundle
+            .filter(published.eq(true))
+            .filter(deleted_at.is_null())
+            .order(created_at.desc())
+            .load(connection)
+    }
+
+    pub fn find_one_by_uuid(
+        bundle_uuid: Uuid,
+        connection: &PgConnection,
+    ) -> QueryResult<Option<Bundle>> {
+        use crate::db::schema::bundle::dsl::*;
+
+        bundle
+            .filter(uuid.eq(bundle_uuid))
+            .filter(deleted_at.is_null())
+            .first::<Bundle>(connection)
+            .optional()
+    }
+
+    /// Marks a bundle as deleted.
+    /// The bundle is kept along with its payments and its media.
+    pub fn delete(bundle_uuid: Uuid, connection: &PgConnection) -> QueryResult<usize> {
+        use crate::db::schema::bundle::dsl::*;
+
+        diesel::update(
+            bundle
+                .filter(uuid.eq(bundle_uuid))
+                .filter(deleted_at.is_null()),
+        )
+        .set(deleted_at.eq(Some(Utc::now().naive_utc())))
+        .execute(connection)
+    }
+
+    /// Restores a deleted bundle
+    pub fn restore(bundle_uuid: Uuid, connection: &PgConnection) -> QueryResult<Option<Bundle>> {
+        use crate::db::schema::bundle::dsl::*;
+
+        diesel::update(
+            bundle
+                .filter(uuid.eq(bundle_uuid))
+                .filter(deleted_at.is_not_null()),
+        )
+        .set(deleted_at.eq(None::<NaiveDateTime>))
+        .get_result::<Bundle>(connection)
+        .optional()
+    }
+
+    /// Finds the media of a bundle that are not deleted, in the bundle order
+    pub fn find_media(bundle_uuid: Uuid, connection: &PgConnection) -> QueryResult<Vec<Media>> {
+        use crate::db::schema::media;
+
+        bundle_media::table
+            .inner_join(media::table)
+            .filter(bundle_media::bundle_uuid.eq(bundle_uuid))
+            .filter(media::deleted_at.is_null())
+            .order(bundle_media::position.asc())
+            .select(media::all_columns)
+            .load::<Media>(connection)
+    }
+
+    /// Checks a media belongs to a bundle
+    pub fn contains_media(
+        bundle_uuid: Uuid,
+        media_uuid: Uuid,
+        connection: &PgConnection,
+    ) -> QueryResult<bool> {
+        diesel::select(diesel::dsl::exists(
+            bundle_media::table
+                .filter(bundle_media::bundle_uuid.eq(bundle_uuid))
+                .filter(bundle_media::media_uuid.eq(media_uuid)),
+        ))
+        .get_result(connection)
+    }
+}
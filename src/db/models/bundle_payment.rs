@@ -0,0 +1,140 @@
+pub use crate::db::schema::bundle_payment;
+use crate::lnd::invoice::{InvoiceUtils, LndInvoice};
+use chrono::Duration;
+use chrono::NaiveDateTime;
+use chrono::Utc;
+use diesel;
+use diesel::prelude::*;
+use diesel::PgConnection;
+use tonic_lnd::rpc::invoice::InvoiceState;
+use uuid::Uuid;
+
+use super::bundle::Bundle;
+use super::download_token::ClientSecret;
+
+/// A payment unlocking every media of a bundle
+#[derive(Queryable, PartialEq, Associations, Debug, Clone)]
+#[table_name = "bundle_payment"]
+#[belongs_to(parent = Bundle, foreign_key = "bundle_uuid")]
+pub struct BundlePayment {
+    pub uuid: Uuid,
+    pub request: String,
+    pub state: Option<String>,
+    pub hash: String,
+    pub bundle_uuid: Uuid,
+    pub expires_at: NaiveDateTime,
+    pub valid_until: Option<NaiveDateTime>,
+    pub settled_at: Option<NaiveDateTime>,
+    pub client_secret_hash: Option<String>,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "bundle_payment"]
+pub struct NewBundlePayment {
+    uuid: Uuid,
+    hash: String,
+    request: String,
+    bundle_uuid: Uuid,
+    expires_at: NaiveDateTime,
+    valid_until: Option<NaiveDateTime>,
+    client_secret_hash: Option<String>,
+}
+
+impl From<(LndInvoice, &Bundle)> for NewBundlePayment {
+    fn from(data: (LndInvoice, &Bundle)) -> Self {
+        let valid_until = data.1.payment_duration.map(|duration| {
+            Utc::now()
+                .checked_add_signed(Duration::minutes(duration.into()))
+                .unwrap()
+                .naive_utc()
+        });
+
+        Self {
+            uuid: Uuid::new_v4(),
+            hash: data.0.r_hash,
+            request: data.0.payment_request,
+            bundle_uuid: data.1.uuid,
+            expires_at: data.0.expires_at,
+            valid_until,
+            client_secret_hash: None,
+        }
+    }
+}
+
+impl NewBundlePayment {
+    /// Records the secret returned to the client requesting the invoice
+    pub fn with_client_secret(mut self, secret: &ClientSecret) -> Self {
+        self.client_secret_hash = Some(secret.hash());
+        self
+    }
+}
+
+impl BundlePayment {
+    pub fn find_one_by_request(
+        payment_request: String,
+        connection: &PgConnection,
+    ) -> QueryResult<Option<BundlePayment>> {
+        use crate::db::schema::bundle_payment::dsl::*;
+
+        bundle_payment
+            .filter(request.eq(payment_request))
+            .first::<BundlePayment>(connection)
+            .optional()
+    }
+
+    pub fn create(
+        new_payment: NewBundlePayment,
+        connection: &PgConnection,
+    ) -> QueryResult<BundlePayment> {
+        use crate::db::schema::bundle_payment::dsl::*;
+
+        diesel::insert_into::<bundle_payment>(bundle_payment)
+            .values(&new_payment)
+            .get_result(connection)
+    }
+
+    /// Records the final state of the payment matching the provided hash
+    pub fn update_state_by_hash(
+        payment_hash: String,
+        payment_state: String,
+        settlement_date: Option<NaiveDateTime>,
+        connection: &PgConnection,
+    ) -> QueryResult<usize> {
+        use crate::db::schema::bundle_payment::dsl::*;
+
+        diesel::update(bundle_payment.filter(hash.eq(payment_hash)))
+            .set((state.eq(payment_state), settled_at.eq(settlement_date)))
+            .execute(connection)
+    }
+
+    /// Records the settlement of the payment proven by its preimage,
+    /// unless the final state of its invoice is already known
+    pub fn record_settlement(&self, connection: &PgConnection) -> QueryResult<usize> {
+        if InvoiceUtils::final_state(&self.state).is_some() {
+            return Ok(0);
+        }
+
+        Self::update_state_by_hash(
+            self.hash.clone(),
+            InvoiceUtils::state_label(InvoiceState::Settled).to_string(),
+            Some(Utc::now().naive_utc()),
+            connection,
+        )
+    }
+
+    /// Checks the provided secret is the one returned to the client that requested
+    /// the invoice. Any other client shall prove the payment with the invoice preimage.
+    pub fn is_requested_by(&self, client_secret: Option<&str>) -> bool {
+        match (&self.client_secret_hash, client_secret) {
+            (Some(hash), Some(secret)) => hash == &ClientSecret::hash_of(secret),
+            _ => false,
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        match &self.valid_until {
+            Some(valid_until) => valid_until < &Utc::now().naive_utc(),
+            None => false,
+        }
+    }
+}
@@ -0,0 +1,228 @@
+use std::env;
+
+use bitcoin_hashes::{sha256, Hash};
+use chrono::{Duration, NaiveDateTime, Utc};
+use jsonwebtoken::{errors::ErrorKind, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use serde::{Deserialize, Serialize};
+
+use super::{bundle_payment::BundlePayment, media_payment::MediaPayment};
+
+/// Default lifetime of a download token, in seconds
+const DEFAULT_DOWNLOAD_TOKEN_DURATION: i64 = 3600;
+
+/// Represents a download token.
+///
+/// The token is minted once a media or bundle payment is settled and grants
+/// access to the media - or to every media of the bundle - until it expires.
+/// It never outlives the validity of the payment it has been minted from.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DownloadToken {
+    // the media the token grants access to
+    #[serde(default)]
+    pub media: Option<uuid::Uuid>,
+    // the bundle the token grants access to
+    #[serde(default)]
+    pub bundle: Option<uuid::Uuid>,
+    // the settled payment the token has been minted from
+    pub payment: uuid::Uuid,
+    // issued at
+    pub iat: i64,
+    // expiration
+    pub exp: i64,
+}
+
+impl DownloadToken {
+    /// Builds a token for a settled payment.
+    /// It shall only be minted for a client that proved the payment.
+    pub fn new(payment: &MediaPayment) -> Self {
+        Self::build(
+            Some(payment.media_uuid),
+            None,
+            payment.uuid,
+            payment.valid_until,
+        )
+    }
+
+    /// Builds a token for a settled bundle payment
+    pub fn for_bundle(payment: &BundlePayment) -> Self {
+        Self::build(
+            None,
+            Some(payment.bundle_uuid),
+            payment.uuid,
+            payment.valid_until,
+        )
+    }
+
+    fn build(
+        media: Option<uuid::Uuid>,
+        bundle: Option<uuid::Uuid>,
+        payment: uuid::Uuid,
+        valid_until: Option<NaiveDateTime>,
+    ) -> Self {
+        let now = Utc::now();
+
+        let duration = env::var("DOWNLOAD_TOKEN_DURATION")
+            .ok()
+            .and_then(|duration| duration.parse::<i64>().ok())
+            .unwrap_or(DEFAULT_DOWNLOAD_TOKEN_DURATION);
+
+        let mut exp = (now + Duration::seconds(duration)).timestamp();
+        if let Some(valid_until) = valid_until {
+            exp = std::cmp::min(exp, valid_until.timestamp());
+        }
+
+        Self {
+            media,
+            bundle,
+            payment,
+            iat: now.timestamp(),
+            exp,
+        }
+    }
+
+    /// Encodes the DownloadToken object to a JWT Token
+    pub fn generate_token(&self) -> Result<String, jsonwebtoken::errors::Error> {
+        jsonwebtoken::encode(
+            &Header::default(),
+            self,
+            &EncodingKey::from_secret(Self::get_secret()?.as_ref()),
+        )
+    }
+
+    /// Decodes a JWT Token. The signature and expiry are checked.
+    pub fn decode(token: &str) -> Result<Self, jsonwebtoken::errors::Error> {
+        jsonwebtoken::decode::<Self>(
+            token,
+            &DecodingKey::from_secret(Self::get_secret()?.as_ref()),
+            &Validation::new(Algorithm::HS256),
+        )
+        .map(|token| token.claims)
+    }
+
+    /// Checks the token grants access to the provided media
+    pub fn grants_access(&self, media_uuid: &uuid::Uuid) -> bool {
+        self.media.as_ref() == Some(media_uuid)
+    }
+
+    /// Checks the token grants access to the provided bundle
+    pub fn grants_bundle_access(&self, bundle_uuid: &uuid::Uuid) -> bool {
+        self.bundle.as_ref() == Some(bundle_uuid)
+    }
+
+    /// A missing secret is reported as an invalid key, so the request fails with an internal error
+    fn get_secret() -> Result<String, jsonwebtoken::errors::Error> {
+        env::var("JWT_TOKEN_SECRET").map_err(|_| ErrorKind::InvalidKeyFormat.into())
+    }
+}
+
+/// The secret returned to the client requesting an invoice.
+///
+/// Anyone knowing a payment request can see its invoice is settled, so the client
+/// that requested the invoice proves it with this secret to get a download token
+/// without providing the invoice preimage. Only its hash is recorded.
+pub struct ClientSecret(String);
+
+impl ClientSecret {
+    /// Generates a new random secret
+    pub fn generate() -> Self {
+        Self(hex::encode(rand::random::<[u8; 32]>()))
+    }
+
+    /// Provides the hash recorded along with the payment
+    pub fn hash(&self) -> String {
+        Self::hash_of(&self.0)
+    }
+
+    /// Hashes a secret provided by a client
+    pub fn hash_of(secret: &str) -> String {
+        sha256::Hash::hash(secret.as_bytes()).to_string()
+    }
+
+    pub fn into_string(self) -> String {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(
+        media: Option<uuid::Uuid>,
+        bundle: Option<uuid::Uuid>,
+        valid_until: Option<NaiveDateTime>,
+    ) -> DownloadToken {
+        DownloadToken::build(media, bundle, uuid::Uuid::new_v4(), valid_until)
+    }
+
+    fn encode(token: &DownloadToken) -> String {
+        if env::var("JWT_TOKEN_SECRET").is_err() {
+            env::set_var("JWT_TOKEN_SECRET", "secret");
+        }
+
+        token.generate_token().unwrap()
+    }
+
+    #[test]
+    fn media_tokens_only_grant_access_to_their_media() {
+        let (media, other) = (uuid::Uuid::new_v4(), uuid::Uuid::new_v4());
+        let token = token(Some(media), None, None);
+
+        assert!(token.grants_access(&media));
+        assert!(!token.grants_access(&other));
+        assert!(!token.grants_bundle_access(&media));
+    }
+
+    #[test]
+    fn bundle_tokens_only_grant_access_to_their_bundle() {
+        let (bundle, other) = (uuid::Uuid::new_v4(), uuid::Uuid::new_v4());
+        let token = token(None, Some(bundle), None);
+
+        assert!(token.grants_bundle_access(&bundle));
+        assert!(!token.grants_bundle_access(&other));
+        assert!(!token.grants_access(&bundle));
+    }
+
+    #[test]
+    fn tokens_do_not_outlive_the_payment_validity() {
+        let valid_until = (Utc::now() + Duration::seconds(60)).naive_utc();
+        let token = token(Some(uuid::Uuid::new_v4()), None, Some(valid_until));
+
+        assert_eq!(token.exp, valid_until.timestamp());
+    }
+
+    #[test]
+    fn decoded_tokens_keep_their_grants() {
+        let media = uuid::Uuid::new_v4();
+        let token = token(Some(media), None, None);
+
+        let decoded = DownloadToken::decode(&encode(&token)).unwrap();
+        assert!(decoded.grants_access(&media));
+        assert_eq!(decoded.payment, token.payment);
+    }
+
+    #[test]
+    fn expired_or_altered_tokens_are_rejected() {
+        let valid_until = (Utc::now() - Duration::hours(2)).naive_utc();
+        let expired = token(Some(uuid::Uuid::new_v4()), None, Some(valid_until));
+        assert!(DownloadToken::decode(&encode(&expired)).is_err());
+
+        let mut altered = encode(&token(Some(uuid::Uuid::new_v4()), None, None));
+        altered.push('a');
+        assert!(DownloadToken::decode(&altered).is_err());
+    }
+
+    #[test]
+    fn client_secrets_are_random_and_only_their_hash_is_recorded() {
+        let secret = ClientSecret::generate();
+        let hash = secret.hash();
+        let other = ClientSecret::generate();
+        assert_ne!(hash, other.hash());
+
+        let secret = secret.into_string();
+        assert_eq!(secret.len(), 64);
+        assert_ne!(hash, secret);
+        assert_eq!(hash, ClientSecret::hash_of(&secret));
+        assert_ne!(hash, ClientSecret::hash_of(&other.into_string()));
+    }
+}
@@ -0,0 +1,28 @@
+use diesel::prelude::*;
+use diesel::PgConnection;
+
+/// The progress of the invoices subscription to the lightning backend
+pub struct InvoiceSubscription;
+
+impl InvoiceSubscription {
+    /// The settle index of the last invoice recorded by the subscription
+    pub fn settle_index(connection: &PgConnection) -> QueryResult<i64> {
+        use crate::db::schema::invoice_subscription::dsl::*;
+
+        invoice_subscription
+            .select(settle_index)
+            .first::<i64>(connection)
+            .optional()
+            .map(|index| index.unwrap_or(0))
+    }
+
+    /// Records the settle index of an invoice once recorded.
+    /// The recorded index never decreases as settlements can be replayed.
+    pub fn record_settle_index(index: i64, connection: &PgConnection) -> QueryResult<usize> {
+        use crate::db::schema::invoice_subscription::dsl::*;
+
+        diesel::update(invoice_subscription.filter(settle_index.lt(index)))
+            .set(settle_index.eq(index))
+            .execute(connection)
+    }
+}
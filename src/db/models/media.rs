@@ -1,12 +1,14 @@
 pub use crate::db::schema::media;
 
+use crate::extractor::FileMetadata;
 use crate::graphql::types::input::file::FileInput;
 use crate::graphql::types::input::media::EditMediaInput;
-use chrono::NaiveDateTime;
+use crate::storage::client::StoredFile;
+use crate::uploads::filename::{sanitize_filename, DEFAULT_FILENAME};
+use chrono::{NaiveDateTime, Utc};
 use diesel;
 use diesel::prelude::*;
 use diesel::PgConnection;
-use std::path::PathBuf;
 use uuid::Uuid;
 
 pub enum MediaModelType {
@@ -20,12 +22,29 @@ pub struct Media {
     pub uuid: uuid::Uuid,
     pub title: String,
     pub description: Option<String>,
-    pub absolute_path: String,
-    pub price: i32,
+    pub storage_key: String,
+    pub price_msat: i64,
     pub payment_duration: Option<i32>,
     pub published: bool,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
+    pub deleted_at: Option<NaiveDateTime>,
+    pub purged_at: Option<NaiveDateTime>,
+    pub checksum: Option<String>,
+    pub preview_key: Option<String>,
+    pub preview_filename: Option<String>,
+    pub thumbnail_key: Option<String>,
+    pub mime_type: Option<String>,
+    pub size_bytes: Option<i64>,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub duration: Option<f64>,
+    pub page_count: Option<i32>,
+    pub original_filename: String,
+    pub download_filename: Option<String>,
+    pub price_currency: Option<String>,
+    pub price_amount: Option<i32>,
+    pub owner_uuid: Option<Uuid>,
 }
 
 #[derive(Debug, Insertable)]
@@ -34,10 +53,24 @@ pub struct NewMedia {
     pub uuid: uuid::Uuid,
     pub title: String,
     pub description: Option<String>,
-    pub absolute_path: String,
+    pub storage_key: String,
     pub published: bool,
-    pub price: i32,
+    pub price_msat: i64,
     pub payment_duration: Option<i32>,
+    pub checksum: Option<String>,
+    pub original_filename: String,
+    pub preview_key: Option<String>,
+    pub preview_filename: Option<String>,
+    pub thumbnail_key: Option<String>,
+    pub mime_type: Option<String>,
+    pub size_bytes: Option<i64>,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub duration: Option<f64>,
+    pub page_count: Option<i32>,
+    pub price_currency: Option<String>,
+    pub price_amount: Option<i32>,
+    pub owner_uuid: Option<Uuid>,
 }
 
 #[derive(Debug, Queryable, AsChangeset)]
@@ -46,36 +79,101 @@ pub struct EditMedia {
     pub title: Option<String>,
     pub description: Option<String>,
     pub published: Option<bool>,
-    pub price: Option<i32>,
+    pub price_msat: Option<i64>,
     pub payment_duration: Option<i32>,
+    pub download_filename: Option<Option<String>>,
+    pub price_currency: Option<Option<String>>,
+    pub price_amount: Option<Option<i32>>,
 }
 
 impl From<EditMediaInput> for EditMedia {
     fn from(edited_media: EditMediaInput) -> Self {
+        // An empty currency restores the price in millisatoshis
+        let restores_sats = edited_media.price_currency.as_deref() == Some("");
+
         Self {
             title: edited_media.title,
             description: edited_media.description,
             published: edited_media.published,
-            price: edited_media.price,
+            price_msat: edited_media.price_msat.map(|price| price.0),
             payment_duration: edited_media.payment_duration,
+            // An empty filename restores the name of the uploaded file
+            download_filename: edited_media
+                .filename
+                .map(|filename| sanitize_filename(&filename)),
+            price_currency: edited_media
+                .price_currency
+                .map(|currency| Some(currency).filter(|_| !restores_sats)),
+            price_amount: match restores_sats {
+                true => Some(None),
+                false => edited_media.price_amount.map(Some),
+            },
         }
     }
 }
 
-impl From<(&PathBuf, FileInput)> for NewMedia {
-    fn from(file_data: (&PathBuf, FileInput)) -> Self {
+impl From<(StoredFile, String, FileInput)> for NewMedia {
+    fn from(file_data: (StoredFile, String, FileInput)) -> Self {
         Self {
             uuid: uuid::Uuid::new_v4(),
-            title: file_data.1.title,
-            description: file_data.1.description,
-            absolute_path: file_data.0.to_string_lossy().to_owned().to_string(),
-            price: file_data.1.price,
-            published: file_data.1.published,
-            payment_duration: file_data.1.payment_duration,
+            title: file_data.2.title,
+            description: file_data.2.description,
+            storage_key: file_data.0.key,
+            price_msat: file_data.2.price_msat.0,
+            published: file_data.2.published,
+            payment_duration: file_data.2.payment_duration,
+            checksum: Some(file_data.0.checksum),
+            original_filename: sanitize_filename(&file_data.1)
+                .unwrap_or_else(|| DEFAULT_FILENAME.to_string()),
+            preview_key: None,
+            preview_filename: None,
+            thumbnail_key: None,
+            mime_type: None,
+            size_bytes: None,
+            width: None,
+            height: None,
+            duration: None,
+            page_count: None,
+            price_currency: file_data.2.price_currency,
+            price_amount: file_data.2.price_amount,
+            owner_uuid: None,
         }
     }
 }
 
+impl NewMedia {
+    /// Attaches the stored preview of the media, freely accessible before payment
+    pub fn with_preview(mut self, preview: StoredFile, filename: String) -> Self {
+        self.preview_key = Some(preview.key);
+        self.preview_filename =
+            Some(sanitize_filename(&filename).unwrap_or_else(|| DEFAULT_FILENAME.to_string()));
+        self
+    }
+
+    /// Records the user who uploaded the media
+    pub fn with_owner(mut self, owner: Uuid) -> Self {
+        self.owner_uuid = Some(owner);
+        self
+    }
+
+    /// Attaches the stored thumbnail of the media
+    pub fn with_thumbnail(mut self, thumbnail: StoredFile) -> Self {
+        self.thumbnail_key = Some(thumbnail.key);
+        self
+    }
+
+    /// Records the metadata extracted from the media file
+    pub fn with_metadata(mut self, metadata: &FileMetadata) -> Self {
+        self.mime_type = metadata.mime_type.clone();
+        self.size_bytes = Some(metadata.size_bytes);
+        self.width = metadata.width;
+        self.height = metadata.height;
+        self.duration = metadata.duration;
+        self.page_count = metadata.page_count;
+        self
+    }
+}
+
 impl Media {
     pub fn create(new_media: NewMedia, connection: &PgConnection) -> QueryResult<Media> {
         use crate::db::schema::media::dsl::*;
@@ -85,14 +183,42 @@ impl Media {
             .get_result(connection)
     }
 
-    pub fn find_all(connection: &PgConnection) -> Vec<Media> {
+    pub fn find_all(connection: &PgConnection) -> QueryResult<Vec<Media>> {
+        use crate::db::schema::media::dsl::*;
+        media.filter(deleted_at.is_null()).load(connection)
+    }
+
+    pub fn find_all_published(connection: &PgConnection) -> QueryResult<Vec<Media>> {
         use crate::db::schema::media::dsl::*;
-        media.load(connection).unwrap()
+        media
+            .filter(published.eq(true))
+            .filter(deleted_at.is_null())
+            .load(connection)
     }
 
-    pub fn find_all_published(connection: &PgConnection) -> Vec<Media> {
+    /// Finds the published media along with the ones of the provided user
+    pub fn find_all_visible_to(
+        user_uuid: Uuid,
+        connection: &PgConnection,
+    ) -> QueryResult<Vec<Media>> {
         use crate::db::schema::media::dsl::*;
-        media.filter(published.eq(true)).load(connection).unwrap()
+        media
+            .filter(published.eq(true).or(owner_uuid.eq(user_uuid)))
+            .filter(deleted_at.is_null())
+            .load(connection)
+    }
+
+    /// Finds the media uploaded by a user, published or not
+    pub fn find_all_by_owner(
+        user_uuid: Uuid,
+        connection: &PgConnection,
+    ) -> QueryResult<Vec<Media>> {
+        use crate::db::schema::media::dsl::*;
+        media
+            .filter(owner_uuid.eq(user_uuid))
+            .filter(deleted_at.is_null())
+            .order(created_at.desc())
+            .load(connection)
     }
 
     pub fn find_one_by_uuid(
@@ -103,26 +229,303 @@ impl Media {
 
         media
             .filter(uuid.eq(media_uuid))
+            .filter(deleted_at.is_null())
             .first::<Media>(connection)
             .optional()
     }
 
+    /// Edits a media, unless it is deleted
     pub fn update(
         media_uuid: Uuid,
         edited_media_input: EditMediaInput,
         connection: &PgConnection,
-    ) -> QueryResult<Media> {
+    ) -> QueryResult<Option<Media>> {
         use crate::db::schema::media::dsl::*;
 
-        diesel::update(media.filter(uuid.eq(media_uuid)))
-            .set(EditMedia::from(edited_media_input))
-            .get_result::<Media>(connection)
+        diesel::update(
+            media
+                .filter(uuid.eq(media_uuid))
+                .filter(deleted_at.is_null()),
+        )
+        .set(EditMedia::from(edited_media_input))
+        .get_result::<Media>(connection)
+        .optional()
     }
 
+    /// Marks a media as deleted.
+    /// The media is kept along with its payments until its file is purged.
     pub fn delete(media_uuid: Uuid, connection: &PgConnection) -> QueryResult<usize> {
         use crate::db::schema::media::dsl::*;
-        diesel::delete(media)
-            .filter(uuid.eq(media_uuid))
+
+        diesel::update(
+            media
+                .filter(uuid.eq(media_uuid))
+                .filter(deleted_at.is_null()),
+        )
+        .set(deleted_at.eq(Some(Utc::now().naive_utc())))
+        .execute(connection)
+    }
+
+    /// Restores a deleted media which file has not been purged yet
+    pub fn restore(media_uuid: Uuid, connection: &PgConnection) -> QueryResult<Option<Media>> {
+        use crate::db::schema::media::dsl::*;
+
+        diesel::update(
+            media
+                .filter(uuid.eq(media_uuid))
+                .filter(deleted_at.is_not_null())
+                .filter(purged_at.is_null()),
+        )
+        .set(deleted_at.eq(None::<NaiveDateTime>))
+        .get_result::<Media>(connection)
+        .optional()
+    }
+
+    /// Finds the media deleted before the provided date which file has not been purged yet
+    pub fn find_purgeable(
+        deleted_before: NaiveDateTime,
+        connection: &PgConnection,
+    ) -> QueryResult<Vec<Media>> {
+        use crate::db::schema::media::dsl::*;
+
+        media
+            .filter(deleted_at.lt(deleted_before))
+            .filter(purged_at.is_null())
+            .load(connection)
+    }
+
+    /// Records the file of a deleted media has been removed from the storage
+    pub fn mark_purged(media_uuid: Uuid, connection: &PgConnection) -> QueryResult<usize> {
+        use crate::db::schema::media::dsl::*;
+
+        diesel::update(media.filter(uuid.eq(media_uuid)))
+            .set(purged_at.eq(Some(Utc::now().naive_utc())))
             .execute(connection)
     }
+
+    /// Finds the media uploaded before the size and type of their file were recorded
+    pub fn find_without_metadata(connection: &PgConnection) -> QueryResult<Vec<Media>> {
+        use crate::db::schema::media::dsl::*;
+
+        media
+            .filter(size_bytes.is_null())
+            .filter(purged_at.is_null())
+            .load(connection)
+    }
+
+    /// Records the size and type of the file of a media
+    pub fn record_file_metadata(
+        media_uuid: Uuid,
+        size: i64,
+        mime: Option<String>,
+        connection: &PgConnection,
+    ) -> QueryResult<usize> {
+        use crate::db::schema::media::dsl::*;
+
+        diesel::update(media.filter(uuid.eq(media_uuid)))
+            .set((size_bytes.eq(Some(size)), mime_type.eq(mime)))
+            .execute(connection)
+    }
+
+    /// Checks if another media not purged yet relies on the same file,
+    /// either as its own file or as its preview or thumbnail
+    pub fn is_storage_key_shared(
+        key: String,
+        media_uuid: Uuid,
+        connection: &PgConnection,
+    ) -> QueryResult<bool> {
+        use crate::db::schema::media::dsl::*;
+
+        diesel::select(diesel::dsl::exists(
+            media
+                .filter(
+                    storage_key
+                        .eq(key.clone())
+                        .or(preview_key.eq(key.clone()))
+                        .or(thumbnail_key.eq(key)),
+                )
+                .filter(uuid.ne(media_uuid))
+                .filter(purged_at.is_null()),
+        ))
+        .get_result(connection)
+    }
+
+    /// Lists the storage keys of the files that are still in use,
+    /// including the previews and thumbnails
+    pub fn find_storage_keys(connection: &PgConnection) -> QueryResult<Vec<String>> {
+        use crate::db::schema::media::dsl::*;
+
+        let keys = media
+            .filter(purged_at.is_null())
+            .select((storage_key, preview_key, thumbnail_key))
+            .load::<(String, Option<String>, Option<String>)>(connection)?;
+
+        Ok(keys
+            .into_iter()
+            .flat_map(|(key, preview, thumbnail)| {
+                std::iter::once(key).chain(preview).chain(thumbnail)
+            })
+            .collect())
+    }
+
+    /// Checks if the media can be accessed without any payment
+    pub fn is_free(&self) -> bool {
+        match (&self.price_currency, self.price_amount) {
+            (Some(_), Some(amount)) => amount <= 0,
+            _ => self.price_msat <= 0,
+        }
+    }
+
+    /// The name the file of the media is downloaded as:
+    /// the name set by the publisher if any, or the name of the uploaded file
+    pub fn filename(&self) -> &str {
+        self.download_filename
+            .as_deref()
+            .unwrap_or(&self.original_filename)
+    }
+
+    /// The stored files of the media: its file, preview and thumbnail
+    pub fn storage_keys(&self) -> Vec<String> {
+        std::iter::once(self.storage_key.clone())
+            .chain(self.preview_key.clone())
+            .chain(self.thumbnail_key.clone())
+            .collect()
+    }
+}
+
+/// The queries are run against the database set through `DATABASE_URL`,
+/// within a transaction rolled back once done.
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+    use crate::{
+        db::models::user::{NewUser, User, UserRoleEnum},
+        graphql::types::scalars::Msat,
+    };
+
+    fn connection() -> PgConnection {
+        let url = env::var("DATABASE_URL").expect("DATABASE_URL to be set");
+
+        PgConnection::establish(&url).unwrap()
+    }
+
+    fn create_user(connection: &PgConnection) -> User {
+        let login = format!("media-{}", Uuid::new_v4());
+
+        User::create(
+            NewUser {
+                uuid: Uuid::new_v4(),
+                email: format!("{}@lnfilestore.test", login),
+                login,
+                password: String::new(),
+                role: UserRoleEnum::Publisher,
+            },
+            connection,
+        )
+        .unwrap()
+    }
+
+    fn create_media(owner: Option<&User>, published: bool, connection: &PgConnection) -> Media {
+        let checksum = Uuid::new_v4().to_simple().to_string();
+        let stored = StoredFile {
+            key: format!("sha256/{}", checksum),
+            checksum,
+        };
+        let file_input = FileInput {
+            filename: "track.mp3".to_string(),
+            title: "Track".to_string(),
+            description: None,
+            price_msat: Msat(1000),
+            price_currency: None,
+            price_amount: None,
+            payment_duration: None,
+            published,
+            file: None,
+            preview: None,
+            thumbnail: None,
+        };
+
+        let mut new_media = NewMedia::from((stored, "track.mp3".to_string(), file_input));
+        if let Some(owner) = owner {
+            new_media = new_media.with_owner(owner.uuid);
+        }
+
+        Media::create(new_media, connection).unwrap()
+    }
+
+    fn uuids(media: Vec<Media>) -> Vec<Uuid> {
+        media.into_iter().map(|media| media.uuid).collect()
+    }
+
+    #[test]
+    #[ignore]
+    fn users_see_the_published_media_and_their_own() {
+        let connection = connection();
+        connection.test_transaction::<_, diesel::result::Error, _>(|| {
+            let (user, other) = (create_user(&connection), create_user(&connection));
+            let own = create_media(Some(&user), false, &connection);
+            let published = create_media(Some(&other), true, &connection);
+            let unpublished = create_media(Some(&other), false, &connection);
+            let without_owner = create_media(None, false, &connection);
+
+            let visible = uuids(Media::find_all_visible_to(user.uuid, &connection)?);
+            assert!(visible.contains(&own.uuid));
+            assert!(visible.contains(&published.uuid));
+            assert!(!visible.contains(&unpublished.uuid));
+            assert!(!visible.contains(&without_owner.uuid));
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    #[ignore]
+    fn owners_find_their_media_until_deleted() {
+        let connection = connection();
+        connection.test_transaction::<_, diesel::result::Error, _>(|| {
+            let (user, other) = (create_user(&connection), create_user(&connection));
+            let first = create_media(Some(&user), true, &connection);
+            let second = create_media(Some(&user), false, &connection);
+            create_media(Some(&other), true, &connection);
+
+            let owned = uuids(Media::find_all_by_owner(user.uuid, &connection)?);
+            assert_eq!(owned.len(), 2);
+            assert!(owned.contains(&first.uuid) && owned.contains(&second.uuid));
+
+            Media::delete(first.uuid, &connection)?;
+            let owned = uuids(Media::find_all_by_owner(user.uuid, &connection)?);
+            assert_eq!(owned, [second.uuid]);
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    #[ignore]
+    fn deleted_media_are_not_edited() {
+        let connection = connection();
+        connection.test_transaction::<_, diesel::result::Error, _>(|| {
+            let media = create_media(None, false, &connection);
+            let edition = EditMediaInput {
+                title: Some("Edited".to_string()),
+                description: None,
+                price_msat: None,
+                published: None,
+                payment_duration: None,
+                filename: None,
+                price_currency: None,
+                price_amount: None,
+            };
+
+            let edited = Media::update(media.uuid, edition.clone(), &connection)?;
+            assert_eq!(edited.map(|media| media.title).as_deref(), Some("Edited"));
+
+            Media::delete(media.uuid, &connection)?;
+            assert!(Media::update(media.uuid, edition, &connection)?.is_none());
+
+            Ok(())
+        });
+    }
 }
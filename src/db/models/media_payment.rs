@@ -1,15 +1,16 @@
-use std::time::Instant;
-
 pub use crate::db::schema::media_payment;
-use crate::lnd::invoice::LndInvoice;
+use crate::lnd::invoice::{InvoiceUtils, LndInvoice};
+use crate::rates::client::Quote;
 use chrono::Duration;
 use chrono::NaiveDateTime;
 use chrono::Utc;
 use diesel;
 use diesel::prelude::*;
 use diesel::PgConnection;
+use tonic_lnd::rpc::invoice::InvoiceState;
 use uuid::Uuid;
 
+use super::download_token::ClientSecret;
 use super::media::MediaModelType;
 
 #[derive(Queryable, PartialEq, Associations, Debug, Clone)]
@@ -23,6 +24,10 @@ pub struct MediaPayment {
     pub media_uuid: Uuid,
     pub expires_at: NaiveDateTime,
     pub valid_until: Option<NaiveDateTime>,
+    pub settled_at: Option<NaiveDateTime>,
+    pub price_currency: Option<String>,
+    pub exchange_rate: Option<f64>,
+    pub client_secret_hash: Option<String>,
 }
 
 #[derive(Debug, Insertable)]
@@ -34,6 +39,9 @@ pub struct NewMediaPayment {
     media_uuid: Uuid,
     expires_at: NaiveDateTime,
     valid_until: Option<NaiveDateTime>,
+    price_currency: Option<String>,
+    exchange_rate: Option<f64>,
+    client_secret_hash: Option<String>,
 }
 
 impl From<(LndInvoice, uuid::Uuid)> for NewMediaPayment {
@@ -45,6 +53,9 @@ impl From<(LndInvoice, uuid::Uuid)> for NewMediaPayment {
             media_uuid: data.1.to_owned(),
             expires_at: data.0.expires_at,
             valid_until: None,
+            price_currency: None,
+            exchange_rate: None,
+            client_secret_hash: None,
         }
     }
 }
@@ -79,10 +90,28 @@ impl From<(LndInvoice, uuid::Uuid, MediaModelType)> for NewMediaPayment {
             media_uuid: data.1.to_owned(),
             expires_at: data.0.expires_at,
             valid_until,
+            price_currency: None,
+            exchange_rate: None,
+            client_secret_hash: None,
         }
     }
 }
 
+impl NewMediaPayment {
+    /// Records the rate a fiat price was converted with to generate the invoice
+    pub fn with_quote(mut self, quote: &Quote) -> Self {
+        self.price_currency = quote.currency.clone();
+        self.exchange_rate = quote.rate;
+        self
+    }
+
+    /// Records the secret returned to the client requesting the invoice
+    pub fn with_client_secret(mut self, secret: &ClientSecret) -> Self {
+        self.client_secret_hash = Some(secret.hash());
+        self
+    }
+}
+
 impl MediaPayment {
     pub fn find_one_by_request(
         payment_request: String,
@@ -106,6 +135,44 @@ impl MediaPayment {
             .get_result(connection)
     }
 
+    /// Records the final state of the payment matching the provided hash
+    pub fn update_state_by_hash(
+        payment_hash: String,
+        payment_state: String,
+        settlement_date: Option<NaiveDateTime>,
+        connection: &PgConnection,
+    ) -> QueryResult<usize> {
+        use crate::db::schema::media_payment::dsl::*;
+
+        diesel::update(media_payment.filter(hash.eq(payment_hash)))
+            .set((state.eq(payment_state), settled_at.eq(settlement_date)))
+            .execute(connection)
+    }
+
+    /// Records the settlement of the payment proven by its preimage,
+    /// unless the final state of its invoice is already known
+    pub fn record_settlement(&self, connection: &PgConnection) -> QueryResult<usize> {
+        if InvoiceUtils::final_state(&self.state).is_some() {
+            return Ok(0);
+        }
+
+        Self::update_state_by_hash(
+            self.hash.clone(),
+            InvoiceUtils::state_label(InvoiceState::Settled).to_string(),
+            Some(Utc::now().naive_utc()),
+            connection,
+        )
+    }
+
+    /// Checks the provided secret is the one returned to the client that requested
+    /// the invoice. Any other client shall prove the payment with the invoice preimage.
+    pub fn is_requested_by(&self, client_secret: Option<&str>) -> bool {
+        match (&self.client_secret_hash, client_secret) {
+            (Some(hash), Some(secret)) => hash == &ClientSecret::hash_of(secret),
+            _ => false,
+        }
+    }
+
     pub fn is_expired(&self) -> bool {
         match &self.valid_until {
             Some(valid_until) => valid_until < &Utc::now().naive_utc(),
@@ -1,6 +1,11 @@
 pub mod api_payment;
+pub mod bundle;
+pub mod bundle_payment;
+pub mod download_token;
+pub mod invoice_subscription;
 pub mod media;
 pub mod media_payment;
 pub mod session;
+pub mod upload;
 pub mod user;
 pub mod user_token;
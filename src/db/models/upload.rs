@@ -0,0 +1,197 @@
+pub use crate::db::schema::upload;
+
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel;
+use diesel::prelude::*;
+use diesel::PgConnection;
+use uuid::Uuid;
+
+/// A resumable upload.
+///
+/// The received bytes are appended to a partial file until the
+/// offset reaches the announced length, the media is then created.
+#[derive(Identifiable, Queryable, PartialEq, Debug, Clone)]
+#[primary_key(uuid)]
+#[table_name = "upload"]
+pub struct Upload {
+    pub uuid: Uuid,
+    pub user_uuid: Uuid,
+    pub upload_length: i64,
+    pub upload_offset: i64,
+    // the `Upload-Metadata` header provided on creation
+    pub metadata: String,
+    // the media created once the upload is complete
+    pub media_uuid: Option<Uuid>,
+    pub created_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+    // the lease of the request or task writing to the upload
+    pub locked_by: Option<Uuid>,
+    pub locked_until: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "upload"]
+pub struct NewUpload {
+    pub uuid: Uuid,
+    pub user_uuid: Uuid,
+    pub upload_length: i64,
+    pub metadata: String,
+    pub expires_at: NaiveDateTime,
+}
+
+impl NewUpload {
+    /// Builds an upload that expires after the provided duration - in seconds -
+    /// if it is not complete by then
+    pub fn new(user_uuid: Uuid, upload_length: i64, metadata: String, duration: i64) -> Self {
+        Self {
+            uuid: Uuid::new_v4(),
+            user_uuid,
+            upload_length,
+            metadata,
+            expires_at: (Utc::now() + Duration::seconds(duration)).naive_utc(),
+        }
+    }
+}
+
+impl Upload {
+    pub fn create(new_upload: NewUpload, connection: &PgConnection) -> QueryResult<Upload> {
+        use crate::db::schema::upload::dsl::*;
+
+        diesel::insert_into::<upload>(upload)
+            .values(&new_upload)
+            .get_result(connection)
+    }
+
+    pub fn find_one_by_uuid(
+        upload_uuid: Uuid,
+        connection: &PgConnection,
+    ) -> QueryResult<Option<Upload>> {
+        use crate::db::schema::upload::dsl::*;
+
+        upload
+            .filter(uuid.eq(upload_uuid))
+            .first::<Upload>(connection)
+            .optional()
+    }
+
+    /// Records the bytes received for an upload
+    pub fn update_offset(
+        upload_uuid: Uuid,
+        offset: i64,
+        connection: &PgConnection,
+    ) -> QueryResult<Upload> {
+        use crate::db::schema::upload::dsl::*;
+
+        diesel::update(upload.filter(uuid.eq(upload_uuid)))
+            .set(upload_offset.eq(offset))
+            .get_result(connection)
+    }
+
+    /// Records the media created from a complete upload
+    pub fn complete(
+        upload_uuid: Uuid,
+        created_media_uuid: Uuid,
+        connection: &PgConnection,
+    ) -> QueryResult<Upload> {
+        use crate::db::schema::upload::dsl::*;
+
+        diesel::update(upload.filter(uuid.eq(upload_uuid)))
+            .set(media_uuid.eq(Some(created_media_uuid)))
+            .get_result(connection)
+    }
+
+    /// Leases an upload to the provided holder until the provided date.
+    /// Returns `false` if the upload is already leased to another holder.
+    pub fn acquire_lock(
+        upload_uuid: Uuid,
+        holder: Uuid,
+        until: NaiveDateTime,
+        connection: &PgConnection,
+    ) -> QueryResult<bool> {
+        use crate::db::schema::upload::dsl::*;
+
+        let now = Utc::now().naive_utc();
+        diesel::update(
+            upload
+                .filter(uuid.eq(upload_uuid))
+                .filter(locked_until.is_null().or(locked_until.lt(now))),
+        )
+        .set((locked_by.eq(Some(holder)), locked_until.eq(Some(until))))
+        .execute(connection)
+        .map(|updated| updated == 1)
+    }
+
+    /// Extends the lease of an upload while its holder still writes to it.
+    /// Returns `false` if the lease was lost.
+    pub fn renew_lock(
+        upload_uuid: Uuid,
+        holder: Uuid,
+        until: NaiveDateTime,
+        connection: &PgConnection,
+    ) -> QueryResult<bool> {
+        use crate::db::schema::upload::dsl::*;
+
+        diesel::update(
+            upload
+                .filter(uuid.eq(upload_uuid))
+                .filter(locked_by.eq(holder)),
+        )
+        .set(locked_until.eq(Some(until)))
+        .execute(connection)
+        .map(|updated| updated == 1)
+    }
+
+    pub fn release_lock(
+        upload_uuid: Uuid,
+        holder: Uuid,
+        connection: &PgConnection,
+    ) -> QueryResult<usize> {
+        use crate::db::schema::upload::dsl::*;
+
+        diesel::update(
+            upload
+                .filter(uuid.eq(upload_uuid))
+                .filter(locked_by.eq(holder)),
+        )
+        .set((
+            locked_by.eq(None::<Uuid>),
+            locked_until.eq(None::<NaiveDateTime>),
+        ))
+        .execute(connection)
+    }
+
+    pub fn delete(upload_uuid: Uuid, connection: &PgConnection) -> QueryResult<usize> {
+        use crate::db::schema::upload::dsl::*;
+
+        diesel::delete(upload.filter(uuid.eq(upload_uuid))).execute(connection)
+    }
+
+    /// Deletes the uploads expired before the provided date and returns them.
+    /// The uploads still leased, e.g: being completed, are kept until released.
+    pub fn delete_expired(
+        expired_before: NaiveDateTime,
+        connection: &PgConnection,
+    ) -> QueryResult<Vec<Upload>> {
+        use crate::db::schema::upload::dsl::*;
+
+        diesel::delete(
+            upload
+                .filter(expires_at.lt(expired_before))
+                .filter(locked_until.is_null().or(locked_until.lt(expired_before))),
+        )
+        .get_results(connection)
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.media_uuid.is_some()
+    }
+
+    /// All the announced bytes are received, the media may not be created yet
+    pub fn is_received(&self) -> bool {
+        self.upload_offset >= self.upload_length
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at < Utc::now().naive_utc()
+    }
+}
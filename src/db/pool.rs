@@ -0,0 +1,70 @@
+use std::{env, time::Duration};
+
+use diesel::{
+    r2d2::{ConnectionManager, Pool},
+    result::{DatabaseErrorKind, Error},
+    PgConnection, QueryResult,
+};
+use rocket::tokio::task::spawn_blocking;
+use rocket::{Build, Rocket};
+use rocket_sync_db_pools::Config;
+
+/// The default number of connections of the pool, set through `DATABASE_TASKS_POOL_SIZE`
+const DEFAULT_POOL_SIZE: u32 = 4;
+
+/// A pool of connections to the `main_db` database for the tasks running
+/// outside of requests, such as the invoices and GraphQL subscriptions
+/// or the background collections.
+///
+/// Unlike the `PostgresConn` request guard, a connection is only taken from the pool
+/// for each call, so long-running tasks don't hold one while idle.
+/// The pool is managed by the server, so cloning it only clones a handle to it.
+#[derive(Clone)]
+pub struct DbPool(Pool<ConnectionManager<PgConnection>>);
+
+impl DbPool {
+    /// Builds the pool from the `main_db` configuration.
+    /// Connections are only opened once needed, up to `DATABASE_TASKS_POOL_SIZE`
+    /// on top of the ones of the requests pool.
+    pub fn from(rocket: &Rocket<Build>) -> Result<Self, String> {
+        let config = Config::from("main_db", rocket).map_err(|e| e.to_string())?;
+        let size = env::var("DATABASE_TASKS_POOL_SIZE")
+            .ok()
+            .and_then(|size| size.parse::<u32>().ok())
+            .filter(|size| *size > 0)
+            .unwrap_or(DEFAULT_POOL_SIZE);
+
+        Pool::builder()
+            .max_size(size)
+            .min_idle(Some(0))
+            .connection_timeout(Duration::from_secs(config.timeout as u64))
+            .build(ConnectionManager::<PgConnection>::new(&config.url))
+            .map(Self)
+            .map_err(|e| e.to_string())
+    }
+
+    /// Runs the provided closure with a connection taken from the pool,
+    /// on a thread dedicated to blocking tasks.
+    pub async fn run<F, T>(&self, f: F) -> QueryResult<T>
+    where
+        F: FnOnce(&PgConnection) -> QueryResult<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let pool = self.0.clone();
+        let job = spawn_blocking(move || match pool.get() {
+            Ok(connection) => f(&connection),
+            Err(e) => Err(Error::DatabaseError(
+                DatabaseErrorKind::UnableToSendCommand,
+                Box::new(format!("No database connection available: {}", e)),
+            )),
+        });
+
+        match job.await {
+            Ok(result) => result,
+            Err(e) => match e.try_into_panic() {
+                Ok(panic) => std::panic::resume_unwind(panic),
+                Err(_) => unreachable!("blocking tasks are never cancelled"),
+            },
+        }
+    }
+}
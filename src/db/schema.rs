@@ -5,6 +5,50 @@ table! {
         state -> Nullable<Text>,
         hash -> Text,
         expires_at -> Timestamptz,
+        settled_at -> Nullable<Timestamptz>,
+    }
+}
+
+table! {
+    bundle (uuid) {
+        uuid -> Uuid,
+        title -> Text,
+        description -> Nullable<Text>,
+        price_msat -> Int8,
+        payment_duration -> Nullable<Int4>,
+        published -> Bool,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+        deleted_at -> Nullable<Timestamptz>,
+    }
+}
+
+table! {
+    bundle_media (bundle_uuid, media_uuid) {
+        bundle_uuid -> Uuid,
+        media_uuid -> Uuid,
+        position -> Int4,
+    }
+}
+
+table! {
+    bundle_payment (uuid) {
+        uuid -> Uuid,
+        request -> Text,
+        state -> Nullable<Text>,
+        hash -> Text,
+        bundle_uuid -> Uuid,
+        expires_at -> Timestamptz,
+        valid_until -> Nullable<Timestamptz>,
+        settled_at -> Nullable<Timestamptz>,
+        client_secret_hash -> Nullable<Text>,
+    }
+}
+
+table! {
+    invoice_subscription (id) {
+        id -> Int2,
+        settle_index -> Int8,
     }
 }
 
@@ -13,12 +57,29 @@ table! {
         uuid -> Uuid,
         title -> Text,
         description -> Nullable<Text>,
-        absolute_path -> Text,
-        price -> Int4,
+        storage_key -> Text,
+        price_msat -> Int8,
         payment_duration -> Nullable<Int4>,
         published -> Bool,
         created_at -> Timestamptz,
         updated_at -> Timestamptz,
+        deleted_at -> Nullable<Timestamptz>,
+        purged_at -> Nullable<Timestamptz>,
+        checksum -> Nullable<Text>,
+        preview_key -> Nullable<Text>,
+        preview_filename -> Nullable<Text>,
+        thumbnail_key -> Nullable<Text>,
+        mime_type -> Nullable<Text>,
+        size_bytes -> Nullable<Int8>,
+        width -> Nullable<Int4>,
+        height -> Nullable<Int4>,
+        duration -> Nullable<Float8>,
+        page_count -> Nullable<Int4>,
+        original_filename -> Text,
+        download_filename -> Nullable<Text>,
+        price_currency -> Nullable<Text>,
+        price_amount -> Nullable<Int4>,
+        owner_uuid -> Nullable<Uuid>,
     }
 }
 
@@ -31,6 +92,10 @@ table! {
         media_uuid -> Uuid,
         expires_at -> Timestamptz,
         valid_until -> Nullable<Timestamptz>,
+        settled_at -> Nullable<Timestamptz>,
+        price_currency -> Nullable<Text>,
+        exchange_rate -> Nullable<Float8>,
+        client_secret_hash -> Nullable<Text>,
     }
 }
 
@@ -44,6 +109,21 @@ table! {
     }
 }
 
+table! {
+    upload (uuid) {
+        uuid -> Uuid,
+        user_uuid -> Uuid,
+        upload_length -> Int8,
+        upload_offset -> Int8,
+        metadata -> Text,
+        media_uuid -> Nullable<Uuid>,
+        created_at -> Timestamptz,
+        expires_at -> Timestamptz,
+        locked_by -> Nullable<Uuid>,
+        locked_until -> Nullable<Timestamptz>,
+    }
+}
+
 table! {
     use diesel::sql_types::*;
     use crate::db::models::user::UserRoleEnumMapping;
@@ -60,6 +140,21 @@ table! {
     }
 }
 
+joinable!(bundle_media -> bundle (bundle_uuid));
+joinable!(bundle_media -> media (media_uuid));
+joinable!(bundle_payment -> bundle (bundle_uuid));
 joinable!(media_payment -> media (media_uuid));
+joinable!(upload -> media (media_uuid));
 
-allow_tables_to_appear_in_same_query!(api_payment, media, media_payment, session, user,);
+allow_tables_to_appear_in_same_query!(
+    api_payment,
+    bundle,
+    bundle_media,
+    bundle_payment,
+    invoice_subscription,
+    media,
+    media_payment,
+    session,
+    upload,
+    user,
+);
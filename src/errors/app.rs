@@ -0,0 +1,379 @@
+use juniper::{FieldError, IntoFieldError, Object, ScalarValue, Value};
+
+use super::{
+    lightning::LightningError, paywall::PaywallError, rate::RateError, upload::UploadError,
+};
+use crate::{graphql::policy::Denial, lnd::invoice::InvoiceDetails};
+
+/// Errors reported to the clients of the GraphQL API, along with a stable code
+/// in their `extensions` so that they can be told apart, e.g: `{"code": "NOT_FOUND"}`.
+///
+/// Payment requirements provide the invoice to be paid in their `extensions` as well.
+///
+/// The error does not implement `Display` on purpose: juniper turns any
+/// displayable error into a `FieldError` without extensions.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AppError {
+    Unauthenticated,
+    Forbidden,
+    /// The invoice shall be paid to access the resource
+    PaymentRequired(InvoiceDetails),
+    /// The invoice is paid but held by the lightning backend until it is settled
+    InvoiceNotSettled,
+    /// The invoice is settled but the client secret returned along with it
+    /// is not provided, its preimage shall be provided as a proof of payment
+    PreimageRequired,
+    /// The requested resource does not exist
+    NotFound(String),
+    /// The provided input is invalid
+    BadInput(String),
+    /// The resource to create already exists
+    AlreadyExists(String),
+    /// The client requested too many invoices, it shall retry later
+    TooManyRequests,
+    /// The lightning backend can't be reached or refused the request
+    LnUnavailable,
+    /// The exchange rate used to convert a price can't be provided
+    RateUnavailable,
+    DatabaseError,
+    StorageError,
+    InternalError,
+}
+
+impl AppError {
+    /// The stable code of the error provided in its `extensions`
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::Unauthenticated => "UNAUTHENTICATED",
+            AppError::Forbidden => "FORBIDDEN",
+            AppError::PaymentRequired(_) => "PAYMENT_REQUIRED",
+            AppError::InvoiceNotSettled => "INVOICE_NOT_SETTLED",
+            AppError::PreimageRequired => "PREIMAGE_REQUIRED",
+            AppError::NotFound(_) => "NOT_FOUND",
+            AppError::BadInput(_) => "BAD_USER_INPUT",
+            AppError::AlreadyExists(_) => "ALREADY_EXISTS",
+            AppError::TooManyRequests => "TOO_MANY_REQUESTS",
+            AppError::LnUnavailable => "LN_UNAVAILABLE",
+            AppError::RateUnavailable => "RATE_UNAVAILABLE",
+            AppError::DatabaseError => "DATABASE_ERROR",
+            AppError::StorageError => "STORAGE_ERROR",
+            AppError::InternalError => "INTERNAL_ERROR",
+        }
+    }
+
+    /// The message of the error, meant for the clients
+    pub fn message(&self) -> String {
+        match self {
+            AppError::Unauthenticated => {
+                "You need to be authenticated to perform this action".to_string()
+            }
+            AppError::Forbidden => {
+                "You do not have the required permission to perform this action".to_string()
+            }
+            AppError::PaymentRequired(_) => {
+                "The media shall be paid to be accessed, use the provided payment request"
+                    .to_string()
+            }
+            AppError::InvoiceNotSettled => "The invoice is accepted but not settled yet".to_string(),
+            AppError::PreimageRequired => {
+                "Provide the client secret returned along with the invoice or its preimage as a proof of payment"
+                    .to_string()
+            }
+            AppError::NotFound(message)
+            | AppError::BadInput(message)
+            | AppError::AlreadyExists(message) => message.clone(),
+            AppError::TooManyRequests => {
+                "Too many invoices were requested, retry in a minute".to_string()
+            }
+            AppError::LnUnavailable => {
+                "Error while requesting lightning network registry".to_string()
+            }
+            AppError::RateUnavailable => "Unable to convert the price of the media".to_string(),
+            AppError::DatabaseError => "Error while requesting database".to_string(),
+            AppError::StorageError => "Error while requesting storage".to_string(),
+            AppError::InternalError => "An internal error happened".to_string(),
+        }
+    }
+}
+
+impl<S: ScalarValue> IntoFieldError<S> for AppError {
+    fn into_field_error(self) -> FieldError<S> {
+        let mut extensions = Object::with_capacity(6);
+        extensions.add_field("code", Value::scalar(self.code().to_string()));
+
+        if let AppError::PaymentRequired(invoice) = &self {
+            extensions.add_field(
+                "paymentRequest",
+                Value::scalar(invoice.payment_request.clone()),
+            );
+            extensions.add_field("hash", Value::scalar(invoice.hash.clone()));
+            // Amounts are provided as strings as GraphQL integers are limited to 32 bits
+            extensions.add_field(
+                "amountMsat",
+                match invoice.amount {
+                    Some(amount) => Value::scalar(amount.to_string()),
+                    None => Value::null(),
+                },
+            );
+            extensions.add_field("expiresAt", Value::scalar(invoice.expires_at.to_rfc3339()));
+            if let Some(secret) = &invoice.client_secret {
+                extensions.add_field("clientSecret", Value::scalar(secret.clone()));
+            }
+        }
+
+        FieldError::new(self.message(), Value::Object(extensions))
+    }
+}
+
+impl From<AppError> for FieldError {
+    fn from(error: AppError) -> Self {
+        error.into_field_error()
+    }
+}
+
+impl From<Denial> for AppError {
+    fn from(denial: Denial) -> Self {
+        match denial {
+            Denial::Unauthenticated => AppError::Unauthenticated,
+            Denial::Forbidden => AppError::Forbidden,
+        }
+    }
+}
+
+impl From<diesel::result::Error> for AppError {
+    fn from(error: diesel::result::Error) -> Self {
+        error!("Database error: {}", error);
+        AppError::DatabaseError
+    }
+}
+
+impl From<LightningError> for AppError {
+    fn from(error: LightningError) -> Self {
+        error!("Lightning backend error: {}", error);
+        AppError::LnUnavailable
+    }
+}
+
+impl From<PaywallError> for AppError {
+    fn from(error: PaywallError) -> Self {
+        match error {
+            PaywallError::InvalidPreimage => AppError::BadInput(
+                "The provided preimage does not match the payment_request".to_string(),
+            ),
+            PaywallError::InvoiceNotFound => AppError::NotFound(
+                "No invoice found with the current payment request on the lightning network service"
+                    .to_string(),
+            ),
+            PaywallError::LightningError(error) => AppError::from(error),
+        }
+    }
+}
+
+impl From<jsonwebtoken::errors::Error> for AppError {
+    fn from(error: jsonwebtoken::errors::Error) -> Self {
+        error!("Unable to generate a download token: {}", error);
+        AppError::InternalError
+    }
+}
+
+impl From<RateError> for AppError {
+    fn from(error: RateError) -> Self {
+        match error {
+            RateError::UnsupportedCurrency(_) | RateError::InvalidPrice(_) => {
+                AppError::BadInput(error.to_string())
+            }
+            RateError::MissingRate(_) => {
+                error!("Exchange rate error: {}", error);
+                AppError::InternalError
+            }
+            RateError::ProviderError(_) => {
+                error!("Exchange rate error: {}", error);
+                AppError::RateUnavailable
+            }
+        }
+    }
+}
+
+impl From<UploadError> for AppError {
+    fn from(error: UploadError) -> Self {
+        match error {
+            UploadError::InvalidMetadata(_) | UploadError::InvalidAsset(_) => {
+                AppError::BadInput(error.to_string())
+            }
+            UploadError::DbError(_) => {
+                error!("Unable to store uploaded file: {}", error);
+                AppError::DatabaseError
+            }
+            UploadError::IoError(_) | UploadError::StorageError(_) => {
+                error!("Unable to store uploaded file: {}", error);
+                AppError::StorageError
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDateTime;
+    use juniper::DefaultScalarValue;
+
+    use super::*;
+    use crate::{db::models::download_token::ClientSecret, errors::storage::StorageError};
+
+    fn extensions_of(error: AppError) -> Object<DefaultScalarValue> {
+        let error: FieldError<DefaultScalarValue> = error.into_field_error();
+
+        match error.extensions().as_object_value() {
+            Some(extensions) => extensions.clone(),
+            None => panic!("the error provides no extensions"),
+        }
+    }
+
+    fn field(extensions: &Object<DefaultScalarValue>, name: &str) -> Option<String> {
+        extensions
+            .get_field_value(name)
+            .and_then(|value| value.as_string_value())
+            .map(str::to_string)
+    }
+
+    fn invoice() -> InvoiceDetails {
+        InvoiceDetails::new(
+            "lnbcrt1",
+            "66687aadf862bd776c8fc18b8e9f8e20089714856ee233b3902a591d0d5f2925",
+            NaiveDateTime::from_timestamp(1_700_000_000, 0),
+        )
+    }
+
+    #[test]
+    fn every_error_provides_its_code_in_its_extensions() {
+        let errors = [
+            (AppError::Unauthenticated, "UNAUTHENTICATED"),
+            (AppError::Forbidden, "FORBIDDEN"),
+            (AppError::PaymentRequired(invoice()), "PAYMENT_REQUIRED"),
+            (AppError::InvoiceNotSettled, "INVOICE_NOT_SETTLED"),
+            (AppError::PreimageRequired, "PREIMAGE_REQUIRED"),
+            (AppError::NotFound("missing".to_string()), "NOT_FOUND"),
+            (AppError::BadInput("invalid".to_string()), "BAD_USER_INPUT"),
+            (
+                AppError::AlreadyExists("taken".to_string()),
+                "ALREADY_EXISTS",
+            ),
+            (AppError::TooManyRequests, "TOO_MANY_REQUESTS"),
+            (AppError::LnUnavailable, "LN_UNAVAILABLE"),
+            (AppError::RateUnavailable, "RATE_UNAVAILABLE"),
+            (AppError::DatabaseError, "DATABASE_ERROR"),
+            (AppError::StorageError, "STORAGE_ERROR"),
+            (AppError::InternalError, "INTERNAL_ERROR"),
+        ];
+
+        for (error, code) in errors {
+            let message = error.message();
+            let field_error: FieldError<DefaultScalarValue> = error.clone().into_field_error();
+
+            assert_eq!(field_error.message(), message);
+            assert_eq!(field(&extensions_of(error), "code").as_deref(), Some(code));
+        }
+    }
+
+    #[test]
+    fn payment_requirements_provide_the_invoice() {
+        let extensions = extensions_of(AppError::PaymentRequired(invoice()));
+
+        assert_eq!(
+            field(&extensions, "paymentRequest").as_deref(),
+            Some("lnbcrt1")
+        );
+        assert_eq!(field(&extensions, "hash"), Some(invoice().hash));
+        assert_eq!(
+            field(&extensions, "expiresAt").as_deref(),
+            Some("2023-11-14T22:13:20+00:00")
+        );
+        // the amount of an invoice that can't be decoded is unknown
+        assert!(extensions
+            .get_field_value("amountMsat")
+            .is_some_and(Value::is_null));
+        assert!(extensions.get_field_value("clientSecret").is_none());
+
+        let secret = ClientSecret::generate();
+        let expected = secret.hash();
+        let extensions = extensions_of(AppError::PaymentRequired(
+            invoice().with_client_secret(secret),
+        ));
+        let provided = field(&extensions, "clientSecret").unwrap();
+        assert_eq!(ClientSecret::hash_of(&provided), expected);
+    }
+
+    #[test]
+    fn resolvers_errors_are_reported_with_their_code() {
+        let errors = [
+            (AppError::from(Denial::Unauthenticated), "UNAUTHENTICATED"),
+            (AppError::from(Denial::Forbidden), "FORBIDDEN"),
+            (
+                AppError::from(diesel::result::Error::NotFound),
+                "DATABASE_ERROR",
+            ),
+            (
+                AppError::from(LightningError::ConnectionError("down".to_string())),
+                "LN_UNAVAILABLE",
+            ),
+            (
+                AppError::from(PaywallError::InvalidPreimage),
+                "BAD_USER_INPUT",
+            ),
+            (AppError::from(PaywallError::InvoiceNotFound), "NOT_FOUND"),
+            (
+                AppError::from(RateError::UnsupportedCurrency("XYZ".to_string())),
+                "BAD_USER_INPUT",
+            ),
+            (
+                AppError::from(RateError::InvalidPrice("negative".to_string())),
+                "BAD_USER_INPUT",
+            ),
+            (
+                AppError::from(RateError::MissingRate("EUR".to_string())),
+                "INTERNAL_ERROR",
+            ),
+            (
+                AppError::from(RateError::ProviderError("down".to_string())),
+                "RATE_UNAVAILABLE",
+            ),
+            (
+                AppError::from(UploadError::InvalidMetadata("title".to_string())),
+                "BAD_USER_INPUT",
+            ),
+            (
+                AppError::from(UploadError::InvalidAsset("thumbnail".to_string())),
+                "BAD_USER_INPUT",
+            ),
+            (
+                AppError::from(UploadError::DbError("down".to_string())),
+                "DATABASE_ERROR",
+            ),
+            (
+                AppError::from(UploadError::IoError("full".to_string())),
+                "STORAGE_ERROR",
+            ),
+            (
+                AppError::from(UploadError::StorageError(StorageError::BackendError(
+                    "down".to_string(),
+                ))),
+                "STORAGE_ERROR",
+            ),
+        ];
+
+        for (error, code) in errors {
+            assert_eq!(error.code(), code);
+            assert_eq!(field(&extensions_of(error), "code").as_deref(), Some(code));
+        }
+    }
+
+    #[test]
+    fn invalid_inputs_are_reported_with_the_cause() {
+        let error = AppError::from(RateError::UnsupportedCurrency("XYZ".to_string()));
+
+        assert_eq!(
+            error,
+            AppError::BadInput(RateError::UnsupportedCurrency("XYZ".to_string()).to_string())
+        );
+    }
+}
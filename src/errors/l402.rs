@@ -0,0 +1,24 @@
+#[derive(Debug, Clone)]
+pub enum L402Error {
+    MalformedAuthorization(String),
+    MalformedMacaroon(String),
+    InvalidSignature,
+    InvalidPreimage,
+    CaveatNotSatisfied(String),
+    MissingRootKey,
+}
+
+impl std::fmt::Display for L402Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            L402Error::MalformedAuthorization(message) => {
+                write!(f, "malformed authorization: {}", message)
+            }
+            L402Error::MalformedMacaroon(message) => write!(f, "malformed macaroon: {}", message),
+            L402Error::InvalidSignature => write!(f, "invalid macaroon signature"),
+            L402Error::InvalidPreimage => write!(f, "preimage does not match the payment hash"),
+            L402Error::CaveatNotSatisfied(caveat) => write!(f, "caveat not satisfied: {}", caveat),
+            L402Error::MissingRootKey => write!(f, "L402_ROOT_KEY is not configured"),
+        }
+    }
+}
@@ -0,0 +1,18 @@
+#[derive(Debug, Clone)]
+pub enum LightningError {
+    ConnectionError(String),
+    InvoiceError(String),
+    MalformedPaymentRequest(String),
+}
+
+impl std::fmt::Display for LightningError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            LightningError::ConnectionError(message) => write!(f, "connection error: {}", message),
+            LightningError::InvoiceError(message) => write!(f, "invoice error: {}", message),
+            LightningError::MalformedPaymentRequest(message) => {
+                write!(f, "malformed payment request: {}", message)
+            }
+        }
+    }
+}
@@ -1 +1,9 @@
+pub mod app;
 pub mod authentication;
+pub mod l402;
+pub mod lightning;
+pub mod payment;
+pub mod paywall;
+pub mod rate;
+pub mod storage;
+pub mod upload;
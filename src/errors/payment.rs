@@ -0,0 +1,21 @@
+use super::lightning::LightningError;
+
+/// Errors that can happen while registering a new payment,
+/// either while requesting the lightning node or the database.
+#[derive(Debug)]
+pub enum PaymentError {
+    DbError(String),
+    LightningError(LightningError),
+}
+
+impl From<LightningError> for PaymentError {
+    fn from(error: LightningError) -> Self {
+        PaymentError::LightningError(error)
+    }
+}
+
+impl From<diesel::result::Error> for PaymentError {
+    fn from(error: diesel::result::Error) -> Self {
+        PaymentError::DbError(error.to_string())
+    }
+}
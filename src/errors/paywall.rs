@@ -0,0 +1,28 @@
+use super::lightning::LightningError;
+
+/// Errors that can happen while resolving the state of a payment
+/// behind the paywall.
+#[derive(Debug, Clone)]
+pub enum PaywallError {
+    /// The provided preimage is not the one of the invoice
+    InvalidPreimage,
+    /// The lightning backend does not know the invoice of the payment
+    InvoiceNotFound,
+    LightningError(LightningError),
+}
+
+impl std::fmt::Display for PaywallError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            PaywallError::InvalidPreimage => write!(f, "preimage does not match the payment hash"),
+            PaywallError::InvoiceNotFound => write!(f, "invoice not found"),
+            PaywallError::LightningError(error) => write!(f, "lightning error: {}", error),
+        }
+    }
+}
+
+impl From<LightningError> for PaywallError {
+    fn from(error: LightningError) -> Self {
+        PaywallError::LightningError(error)
+    }
+}
@@ -0,0 +1,44 @@
+/// Errors that can happen while converting a fiat price into satoshis
+#[derive(Debug, Clone)]
+pub enum RateError {
+    /// No exchange rate is known for the currency
+    UnsupportedCurrency(String),
+    /// No exchange rate is known for the currency a media is already priced in
+    MissingRate(String),
+    /// The price of a media is negative, or its fiat price misses its currency or amount
+    InvalidPrice(String),
+    ProviderError(String),
+}
+
+impl std::fmt::Display for RateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            RateError::UnsupportedCurrency(currency) => {
+                write!(f, "no exchange rate available for {}", currency)
+            }
+            RateError::MissingRate(currency) => {
+                write!(
+                    f,
+                    "no exchange rate available to quote a price in {}",
+                    currency
+                )
+            }
+            RateError::InvalidPrice(message) => write!(f, "invalid price: {}", message),
+            RateError::ProviderError(message) => {
+                write!(f, "exchange rate provider error: {}", message)
+            }
+        }
+    }
+}
+
+impl From<std::io::Error> for RateError {
+    fn from(error: std::io::Error) -> Self {
+        RateError::ProviderError(error.to_string())
+    }
+}
+
+impl From<serde_json::Error> for RateError {
+    fn from(error: serde_json::Error) -> Self {
+        RateError::ProviderError(error.to_string())
+    }
+}
@@ -0,0 +1,22 @@
+#[derive(Debug, Clone)]
+pub enum StorageError {
+    NotFound(String),
+    IoError(String),
+    BackendError(String),
+}
+
+impl std::fmt::Display for StorageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            StorageError::NotFound(key) => write!(f, "object not found: {}", key),
+            StorageError::IoError(message) => write!(f, "io error: {}", message),
+            StorageError::BackendError(message) => write!(f, "storage backend error: {}", message),
+        }
+    }
+}
+
+impl From<std::io::Error> for StorageError {
+    fn from(error: std::io::Error) -> Self {
+        StorageError::IoError(error.to_string())
+    }
+}
@@ -0,0 +1,43 @@
+use super::storage::StorageError;
+
+/// Errors that can happen while receiving an upload
+#[derive(Debug)]
+pub enum UploadError {
+    /// The `Upload-Metadata` header misses a value or holds an invalid one
+    InvalidMetadata(String),
+    /// A preview or thumbnail can't be attached to the uploaded media
+    InvalidAsset(String),
+    DbError(String),
+    IoError(String),
+    StorageError(StorageError),
+}
+
+impl std::fmt::Display for UploadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            UploadError::InvalidMetadata(message) => write!(f, "invalid metadata: {}", message),
+            UploadError::InvalidAsset(message) => write!(f, "invalid asset: {}", message),
+            UploadError::DbError(message) => write!(f, "database error: {}", message),
+            UploadError::IoError(message) => write!(f, "io error: {}", message),
+            UploadError::StorageError(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl From<StorageError> for UploadError {
+    fn from(error: StorageError) -> Self {
+        UploadError::StorageError(error)
+    }
+}
+
+impl From<diesel::result::Error> for UploadError {
+    fn from(error: diesel::result::Error) -> Self {
+        UploadError::DbError(error.to_string())
+    }
+}
+
+impl From<std::io::Error> for UploadError {
+    fn from(error: std::io::Error) -> Self {
+        UploadError::IoError(error.to_string())
+    }
+}
@@ -0,0 +1,81 @@
+use rocket::{futures::future::BoxFuture, Orbit, Rocket};
+
+use super::detect_mime_type;
+use crate::{
+    db::{models::media::Media, PostgresConn},
+    storage::client::StorageClient,
+};
+
+// Records the size and type of the files of the media uploaded before they were extracted on upload,
+// so that listings and downloads never have to read the storage for them.
+// The media are updated in the background once the server is launched.
+pub fn backfill_file_metadata<'a>(rocket: &'a Rocket<Orbit>) -> BoxFuture<'a, ()> {
+    Box::pin(async move {
+        let storage = match rocket.state::<StorageClient>() {
+            Some(storage) => storage.clone(),
+            None => return,
+        };
+
+        let db = match PostgresConn::get_one(rocket).await {
+            Some(db) => db,
+            None => {
+                error!("Unable to get a database connection for the media metadata backfill");
+                return;
+            }
+        };
+
+        rocket::tokio::spawn(async move {
+            let legacy_media = match db.run(|c| Media::find_without_metadata(c)).await {
+                Ok(legacy_media) => legacy_media,
+                Err(e) => {
+                    error!("Unable to find the media without metadata: {}", e);
+                    return;
+                }
+            };
+
+            if legacy_media.is_empty() {
+                return;
+            }
+
+            info!(
+                "Recording the file metadata of {} media",
+                legacy_media.len()
+            );
+
+            for media in legacy_media {
+                backfill_media(&db, &storage, media).await;
+            }
+        });
+    })
+}
+
+/// Reads the size and type of the file of a media from the storage
+async fn backfill_media(db: &PostgresConn, storage: &StorageClient, media: Media) {
+    let uuid = media.uuid;
+
+    let size = match storage.0.metadata(&media.storage_key).await {
+        Ok(metadata) => metadata.size as i64,
+        Err(e) => {
+            warn!("Unable to read the file metadata of media {}: {}", uuid, e);
+            return;
+        }
+    };
+
+    let mime_type = match storage.read_head(&media.storage_key, 8192).await {
+        Ok(head) => detect_mime_type(&head, &media.original_filename),
+        Err(e) => {
+            warn!("Unable to read the file of media {}: {}", uuid, e);
+            return;
+        }
+    };
+
+    if let Err(e) = db
+        .run(move |c| Media::record_file_metadata(uuid, size, mime_type, c))
+        .await
+    {
+        error!(
+            "Unable to record the file metadata of media {}: {}",
+            uuid, e
+        );
+    }
+}
@@ -0,0 +1,41 @@
+use symphonia::core::{
+    formats::FormatOptions, io::MediaSourceStream, meta::MetadataOptions, probe::Hint,
+};
+
+use super::Source;
+
+/// Reads the duration - in seconds - of an audio or video file from its container.
+/// The longest track gives the duration of the file.
+/// Returns `None` if the container is not supported or does not record the duration.
+pub fn read_duration<S: Source>(source: S, extension: Option<&str>) -> Option<f64> {
+    let stream = MediaSourceStream::new(Box::new(source), Default::default());
+
+    let mut hint = Hint::new();
+    if let Some(extension) = extension {
+        hint.with_extension(extension);
+    }
+
+    let probed = symphonia::default::get_probe()
+        .format(
+            &hint,
+            stream,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .ok()?;
+
+    probed
+        .format
+        .tracks()
+        .iter()
+        .filter_map(|track| {
+            let params = &track.codec_params;
+            let time = params.time_base?.calc_time(params.n_frames?);
+
+            Some(time.seconds as f64 + time.frac)
+        })
+        .fold(None, |longest: Option<f64>, duration| match longest {
+            Some(longest) if longest >= duration => Some(longest),
+            _ => Some(duration),
+        })
+}
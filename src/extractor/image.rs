@@ -0,0 +1,58 @@
+use std::io::{BufReader, Cursor};
+
+use ::image::{io::Reader, DynamicImage, ImageOutputFormat};
+
+use super::Source;
+
+/// JPEG quality of the generated thumbnails
+const THUMBNAIL_QUALITY: u8 = 80;
+
+/// The dimensions of an image along with its thumbnail
+pub struct ImageDetails {
+    pub width: u32,
+    pub height: u32,
+    pub thumbnail: Option<Vec<u8>>,
+}
+
+/// Reads the dimensions of an image and generates a thumbnail which longest side
+/// is at most `thumbnail_size` pixels. Images smaller than the thumbnail are kept as is.
+/// Returns `None` if the image format is not supported or the image can't be decoded.
+pub fn read_image<S: Source>(source: S, thumbnail_size: u32) -> Option<ImageDetails> {
+    let image = Reader::new(BufReader::new(source))
+        .with_guessed_format()
+        .ok()?
+        .decode()
+        .ok()?;
+
+    let (width, height) = (image.width(), image.height());
+    let resized = match width.max(height) > thumbnail_size {
+        true => image.thumbnail(thumbnail_size, thumbnail_size),
+        false => image.clone(),
+    };
+    let thumbnail = match encode_thumbnail(&resized) {
+        Ok(thumbnail) => Some(thumbnail),
+        Err(e) => {
+            warn!("Unable to generate a thumbnail: {}", e);
+            None
+        }
+    };
+
+    Some(ImageDetails {
+        width,
+        height,
+        thumbnail,
+    })
+}
+
+/// Encodes a thumbnail as a JPEG, or as a PNG to keep its transparency
+fn encode_thumbnail(thumbnail: &DynamicImage) -> ::image::ImageResult<Vec<u8>> {
+    let mut encoded = Cursor::new(Vec::new());
+
+    match thumbnail.color().has_alpha() {
+        true => thumbnail.write_to(&mut encoded, ImageOutputFormat::Png)?,
+        false => DynamicImage::ImageRgb8(thumbnail.to_rgb8())
+            .write_to(&mut encoded, ImageOutputFormat::Jpeg(THUMBNAIL_QUALITY))?,
+    }
+
+    Ok(encoded.into_inner())
+}
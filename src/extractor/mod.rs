@@ -0,0 +1,212 @@
+pub mod backfill;
+pub mod duration;
+pub mod image;
+pub mod pdf;
+
+use std::{
+    env,
+    fs::File,
+    io::{self, Cursor, Read, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+use bytes::Bytes;
+use infer::{Infer, MatcherType};
+use rocket::{http::ContentType, tokio::task};
+use symphonia::core::io::MediaSource;
+
+use crate::{db::models::media::NewMedia, storage::client::StorageClient};
+
+/// Default size - in pixels - of the longest side of a generated thumbnail
+const DEFAULT_THUMBNAIL_SIZE: u32 = 320;
+
+/// A readable file the metadata are extracted from, e.g: a `File` or a `Cursor`.
+/// The media source of the audio and video decoders is a seekable reader
+/// which knows its length.
+pub trait Source: MediaSource + 'static {}
+
+impl<T: MediaSource + 'static> Source for T {}
+
+/// The metadata of an uploaded file.
+/// Values that do not apply to the type of the file are left empty.
+#[derive(Debug, Default)]
+pub struct FileMetadata {
+    pub mime_type: Option<String>,
+    pub size_bytes: i64,
+    // dimensions of an image, in pixels
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    // duration of an audio or video file, in seconds
+    pub duration: Option<f64>,
+    // number of pages of a PDF document
+    pub page_count: Option<i32>,
+    // a generated thumbnail for images, encoded as a PNG or JPEG
+    pub thumbnail: Option<Vec<u8>>,
+}
+
+/// Extracts the metadata of an uploaded file held in memory
+pub async fn extract_from_bytes(content: Bytes, filename: String) -> FileMetadata {
+    let size = content.len() as u64;
+    run_extraction(move || extract(Cursor::new(content), size, &filename)).await
+}
+
+/// Extracts the metadata of an uploaded file written on disk
+pub async fn extract_from_file(path: PathBuf, filename: String) -> FileMetadata {
+    run_extraction(move || {
+        let file = File::open(&path)?;
+        let size = file.metadata()?.len();
+        extract(file, size, &filename)
+    })
+    .await
+}
+
+/// Records the extracted metadata on a new media.
+/// The generated thumbnail is stored unless the publisher provided one.
+pub async fn apply_metadata(
+    new_media: NewMedia,
+    metadata: FileMetadata,
+    storage: &StorageClient,
+) -> NewMedia {
+    let new_media = new_media.with_metadata(&metadata);
+
+    match (&new_media.thumbnail_key, metadata.thumbnail) {
+        (None, Some(thumbnail)) => match storage.store(Bytes::from(thumbnail)).await {
+            Ok(stored) => new_media.with_thumbnail(stored),
+            Err(e) => {
+                warn!("Unable to store a generated thumbnail: {}", e);
+                new_media
+            }
+        },
+        _ => new_media,
+    }
+}
+
+/// Runs the extraction apart from the async runtime as decoding is CPU bound.
+/// The upload is never rejected because of its metadata: an unreadable file has none.
+async fn run_extraction<F>(extraction: F) -> FileMetadata
+where
+    F: FnOnce() -> io::Result<FileMetadata> + Send + 'static,
+{
+    match task::spawn_blocking(extraction).await {
+        Ok(Ok(metadata)) => metadata,
+        Ok(Err(e)) => {
+            warn!("Unable to extract the metadata of an uploaded file: {}", e);
+            FileMetadata::default()
+        }
+        Err(e) => {
+            error!("The metadata extraction of an uploaded file failed: {}", e);
+            FileMetadata::default()
+        }
+    }
+}
+
+/// Detects the type of the file from its content, then reads the metadata relevant to it
+fn extract<S: Source>(mut source: S, size: u64, filename: &str) -> io::Result<FileMetadata> {
+    let mut head = Vec::with_capacity(8192);
+    (&mut source).take(8192).read_to_end(&mut head)?;
+    source.seek(SeekFrom::Start(0))?;
+
+    let extension = Path::new(filename)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_lowercase());
+
+    let kind = Infer::new().get(&head);
+    let mime_type = detect_mime_type(&head, filename);
+
+    let mut metadata = FileMetadata {
+        mime_type,
+        size_bytes: size as i64,
+        ..Default::default()
+    };
+
+    match kind.map(|kind| kind.matcher_type()) {
+        Some(MatcherType::Image) => {
+            if let Some(image) = image::read_image(source, thumbnail_size()) {
+                metadata.width = Some(image.width as i32);
+                metadata.height = Some(image.height as i32);
+                metadata.thumbnail = image.thumbnail;
+            }
+        }
+        Some(MatcherType::Audio) | Some(MatcherType::Video) => {
+            metadata.duration = duration::read_duration(source, extension.as_deref());
+        }
+        _ if metadata.mime_type.as_deref() == Some("application/pdf") => {
+            metadata.page_count = pdf::read_page_count(source).map(|count| count as i32);
+        }
+        _ => {}
+    }
+
+    Ok(metadata)
+}
+
+/// Detects the MIME type of a file from its first bytes.
+/// The extension of the file is only used as a fallback, e.g: for text files.
+pub fn detect_mime_type(head: &[u8], filename: &str) -> Option<String> {
+    match Infer::new().get(head) {
+        Some(kind) => Some(kind.mime_type().to_string()),
+        None => Path::new(filename)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(|extension| ContentType::from_extension(&extension.to_lowercase()))
+            .map(|content_type| {
+                // parameters, e.g: the charset, are not part of the type
+                let media_type = content_type.media_type();
+                format!("{}/{}", media_type.top(), media_type.sub())
+            }),
+    }
+}
+
+fn thumbnail_size() -> u32 {
+    env::var("THUMBNAIL_SIZE")
+        .ok()
+        .and_then(|size| size.parse::<u32>().ok())
+        .filter(|size| *size > 0)
+        .unwrap_or(DEFAULT_THUMBNAIL_SIZE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PNG_HEAD: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+
+    #[test]
+    fn the_type_is_detected_from_the_content() {
+        assert_eq!(
+            detect_mime_type(PNG_HEAD, "cover.png").as_deref(),
+            Some("image/png")
+        );
+        assert_eq!(
+            detect_mime_type(b"%PDF-1.7\n", "book").as_deref(),
+            Some("application/pdf")
+        );
+    }
+
+    #[test]
+    fn the_content_prevails_over_the_extension() {
+        assert_eq!(
+            detect_mime_type(PNG_HEAD, "cover.txt").as_deref(),
+            Some("image/png")
+        );
+    }
+
+    #[test]
+    fn the_extension_is_a_fallback_without_parameters() {
+        assert_eq!(
+            detect_mime_type(b"plain notes", "notes.txt").as_deref(),
+            Some("text/plain")
+        );
+        assert_eq!(
+            detect_mime_type(b"plain notes", "NOTES.TXT").as_deref(),
+            Some("text/plain")
+        );
+    }
+
+    #[test]
+    fn unknown_types_are_not_guessed() {
+        assert_eq!(detect_mime_type(b"plain notes", "notes"), None);
+        assert_eq!(detect_mime_type(b"plain notes", "notes.unknown"), None);
+        assert_eq!(detect_mime_type(b"", ""), None);
+    }
+}
@@ -0,0 +1,15 @@
+use lopdf::Document;
+
+use super::Source;
+
+/// Reads the number of pages of a PDF document.
+/// Returns `None` if the document can't be parsed, e.g: if it is encrypted.
+pub fn read_page_count<S: Source>(source: S) -> Option<usize> {
+    match Document::load_from(source) {
+        Ok(document) => Some(document.get_pages().len()),
+        Err(e) => {
+            warn!("Unable to read the pages of a PDF document: {}", e);
+            None
+        }
+    }
+}
@@ -1,18 +1,24 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, net::IpAddr};
 
 use crate::{
-    db::{
-        models::user::{User, UserRoleEnum},
-        PostgresConn,
-    },
-    lnd::client::LndClient,
+    db::{models::user::User, DbConnection, PostgresConn},
+    errors::app::AppError,
+    graphql::policy::{self, Action},
+    guards::userguard::UserGuard,
+    lnd::{client::LndClient, subscriber::InvoiceEvents, throttle::InvoiceThrottle},
+    rates::client::RateClient,
+    storage::client::StorageClient,
 };
 
 use derive_more::Deref;
 use juniper_rocket_multipart_handler::temp_file::TempFile;
-// use tonic::codegen::InterceptedService;
-use tonic_lnd::tonic::codegen::InterceptedService;
-use tonic_lnd::{rpc::lightning_client::LightningClient, MacaroonInterceptor};
+use rocket::{
+    http::Status,
+    outcome::try_outcome,
+    request::{FromRequest, Outcome},
+    Request,
+};
+use uuid::Uuid;
 
 /*
    The GQLContext struct provides an extended juniper context
@@ -25,11 +31,17 @@ use tonic_lnd::{rpc::lightning_client::LightningClient, MacaroonInterceptor};
 #[derive(Deref)]
 pub struct GQLContext {
     #[deref]
-    pub pool: PostgresConn,
+    pub pool: DbConnection,
     pub lnd: LndClient,
+    pub storage: StorageClient,
+    pub rates: RateClient,
     pub files: Option<HashMap<String, TempFile>>,
     pub user: Option<User>,
     pub server_config: Option<String>,
+    pub invoice_events: InvoiceEvents,
+    pub invoice_throttle: InvoiceThrottle,
+    /// The IP address of the client, if known
+    pub client_ip: Option<IpAddr>,
 }
 
 impl juniper::Context for GQLContext {}
@@ -43,14 +55,27 @@ impl AsRef<Self> for GQLContext {
 
 impl GQLContext {
     // Provides the instance of the LN Client
-    pub fn get_lnd_client(
-        &self,
-    ) -> &LightningClient<InterceptedService<tonic::transport::Channel, MacaroonInterceptor>> {
-        return &self.lnd.0;
+    pub fn get_lnd_client(&self) -> &LndClient {
+        return &self.lnd;
+    }
+
+    /// Provides the instance of the storage backend
+    pub fn get_storage(&self) -> &StorageClient {
+        &self.storage
+    }
+
+    /// Provides the exchange rates used to price media in a fiat currency
+    pub fn get_rates(&self) -> &RateClient {
+        &self.rates
+    }
+
+    /// Provides the invoice states broadcast used by subscriptions
+    pub fn get_invoice_events(&self) -> &InvoiceEvents {
+        &self.invoice_events
     }
 
     // Provides the instance of DB pool
-    pub fn get_db_connection(&self) -> &PostgresConn {
+    pub fn get_db_connection(&self) -> &DbConnection {
         return &self.pool;
     }
 
@@ -64,19 +89,50 @@ impl GQLContext {
         return &self.user;
     }
 
-    // Checks if user is authenticated
-    pub fn is_authenticated(&self) -> bool {
-        match &self.user {
-            Some(_) => true,
-            None => false,
-        }
+    /// Checks the user may perform an action, on a resource owned by `owner` if any.
+    /// The rules of each action are defined by the policy.
+    pub fn authorize(&self, action: Action, owner: Option<Uuid>) -> Result<(), AppError> {
+        policy::authorize(self.user.as_ref(), action, owner).map_err(AppError::from)
     }
 
-    // Checks if user is granted with role from a list of roles
-    pub fn has_permissioned_role(&self, roles: Vec<UserRoleEnum>) -> bool {
-        match &self.user {
-            Some(user) => roles.contains(&user.role),
-            None => false,
-        }
+    /// Counts a new invoice for the client, unless it requested too many of them
+    pub fn allow_new_invoice(&self) -> bool {
+        self.invoice_throttle.allow(self.client_ip)
+    }
+
+    /// Checks if the user may perform an action, on a resource owned by `owner` if any
+    pub fn is_allowed(&self, action: Action, owner: Option<Uuid>) -> bool {
+        policy::authorize(self.user.as_ref(), action, owner).is_ok()
+    }
+}
+
+/// Builds the context of the GraphQL requests sent over HTTP.
+/// The uploaded files are provided by the handlers, as they are read from the request body.
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for GQLContext {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let invoice_events = match request.rocket().state::<InvoiceEvents>() {
+            Some(invoice_events) => invoice_events.clone(),
+            None => return Outcome::Failure((Status::ServiceUnavailable, ())),
+        };
+        let invoice_throttle = match request.rocket().state::<InvoiceThrottle>() {
+            Some(invoice_throttle) => invoice_throttle.clone(),
+            None => return Outcome::Failure((Status::ServiceUnavailable, ())),
+        };
+
+        Outcome::Success(GQLContext {
+            pool: DbConnection::Request(try_outcome!(request.guard::<PostgresConn>().await)),
+            lnd: try_outcome!(request.guard::<LndClient>().await),
+            storage: try_outcome!(request.guard::<StorageClient>().await),
+            rates: try_outcome!(request.guard::<RateClient>().await),
+            files: None,
+            user: try_outcome!(request.guard::<UserGuard>().await).0,
+            server_config: None,
+            invoice_events,
+            invoice_throttle,
+            client_ip: request.client_ip(),
+        })
     }
 }
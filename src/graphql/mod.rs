@@ -1,6 +1,10 @@
 pub mod context;
 pub mod mutation;
 pub mod mutations;
+pub mod policy;
 pub mod queries;
 pub mod query;
+pub mod subscription;
+pub mod subscriptions;
 pub mod types;
+pub mod websocket;
@@ -1,20 +1,25 @@
-use juniper::{FieldError, FieldResult, Value};
-
-use crate::db::models::user::UserRoleEnum;
+use crate::errors::app::AppError;
+use crate::graphql::policy::Action;
 use crate::graphql::types::input::user::EditUserInput;
 
 use super::{
-    context::GQLContext, types::input::file::FileInput, types::input::media::EditMediaInput,
-    types::input::user::NewUserInput, types::output::media::MediaType,
+    context::GQLContext, types::input::bundle::NewBundleInput, types::input::file::FileInput,
+    types::input::media::EditMediaInput, types::input::user::NewUserInput,
+    types::output::bundle::BundleType, types::output::media::MediaType,
     types::output::user::UserType,
 };
+use crate::graphql::mutations::create_bundle;
 use crate::graphql::mutations::create_user;
+use crate::graphql::mutations::delete_bundle;
 use crate::graphql::mutations::delete_media;
 use crate::graphql::mutations::delete_user;
 use crate::graphql::mutations::edit_media;
 use crate::graphql::mutations::edit_user;
+use crate::graphql::mutations::restore_bundle;
+use crate::graphql::mutations::restore_media;
 use crate::graphql::mutations::update_password;
 use crate::graphql::mutations::upload_file;
+use crate::graphql::mutations::upload_files;
 
 pub struct Mutation;
 
@@ -24,13 +29,8 @@ impl Mutation {
     async fn create_user<'a>(
         context: &'a GQLContext,
         new_user_input: NewUserInput,
-    ) -> FieldResult<UserType> {
-        if !&context.is_authenticated() {
-            return Err(FieldError::new(
-                "You need to be authenticated to use this mutation",
-                Value::null(),
-            ));
-        }
+    ) -> Result<UserType, AppError> {
+        context.authorize(Action::CreateUser, None)?;
 
         create_user::create_user(context, new_user_input).await
     }
@@ -41,39 +41,15 @@ impl Mutation {
         context: &'a GQLContext,
         uuid: uuid::Uuid,
         edit_user_input: EditUserInput,
-    ) -> FieldResult<UserType> {
-        if !&context.is_authenticated() {
-            return Err(FieldError::new(
-                "You need to be authenticated to use this mutation",
-                Value::null(),
-            ));
-        }
-
-        if !&context.has_permissioned_role(vec![UserRoleEnum::Admin]) {
-            return Err(FieldError::new(
-                "You do not have the required permission to perform this action",
-                Value::null(),
-            ));
-        }
+    ) -> Result<UserType, AppError> {
+        context.authorize(Action::EditUser, None)?;
 
         edit_user::edit_user(context, uuid, edit_user_input).await
     }
 
     #[graphql(description = "Deletes a user")]
-    async fn delete_user<'a>(context: &'a GQLContext, uuid: uuid::Uuid) -> FieldResult<bool> {
-        if !&context.is_authenticated() {
-            return Err(FieldError::new(
-                "You need to be authenticated to use this mutation",
-                Value::null(),
-            ));
-        }
-
-        if !&context.has_permissioned_role(vec![UserRoleEnum::Admin]) {
-            return Err(FieldError::new(
-                "You do not have the required permission to perform this action",
-                Value::null(),
-            ));
-        }
+    async fn delete_user<'a>(context: &'a GQLContext, uuid: uuid::Uuid) -> Result<bool, AppError> {
+        context.authorize(Action::DeleteUser, None)?;
 
         delete_user::delete_user(context, uuid).await
     }
@@ -82,61 +58,95 @@ impl Mutation {
     async fn upload_file<'a>(
         context: &'a GQLContext,
         file_input: FileInput,
-    ) -> FieldResult<MediaType> {
-        if !&context.is_authenticated() {
-            return Err(FieldError::new(
-                "You need to be authenticated to use this mutation",
-                Value::null(),
-            ));
-        }
+    ) -> Result<MediaType, AppError> {
+        context.authorize(Action::UploadMedia, None)?;
 
         upload_file::upload_file(context, file_input).await
     }
 
-    #[graphql(description = "Edit a media")]
+    #[graphql(description = r#"
+        Uploads and stores several payable media onto the server.
+        The files are matched with the inputs in the order of their multipart field names.
+    "#)]
+    async fn upload_files<'a>(
+        context: &'a GQLContext,
+        file_inputs: Vec<FileInput>,
+    ) -> Result<Vec<MediaType>, AppError> {
+        context.authorize(Action::UploadMedia, None)?;
+
+        upload_files::upload_files(context, file_inputs).await
+    }
+
+    #[graphql(description = "Creates a bundle of media unlocked by a single payment")]
+    async fn create_bundle<'a>(
+        context: &'a GQLContext,
+        bundle: NewBundleInput,
+    ) -> Result<BundleType, AppError> {
+        context.authorize(Action::CreateBundle, None)?;
+
+        create_bundle::create_bundle(context, bundle).await
+    }
+
+    #[graphql(description = r#"
+        Deletes a bundle. The bundle is kept along with its payments and its media,
+        so that it can be restored.
+    "#)]
+    async fn delete_bundle<'a>(
+        context: &'a GQLContext,
+        uuid: uuid::Uuid,
+    ) -> Result<bool, AppError> {
+        context.authorize(Action::DeleteBundle, None)?;
+
+        delete_bundle::delete_bundle(context, uuid).await
+    }
+
+    #[graphql(description = "Restores a deleted bundle")]
+    async fn restore_bundle(
+        context: &GQLContext,
+        uuid: uuid::Uuid,
+    ) -> Result<BundleType, AppError> {
+        context.authorize(Action::RestoreBundle, None)?;
+
+        restore_bundle::restore_bundle(context, uuid).await
+    }
+
+    #[graphql(description = "Edit a media. Publishers can only edit the media they uploaded")]
     async fn edit_media<'a>(
         context: &'a GQLContext,
         uuid: uuid::Uuid,
         media: EditMediaInput,
-    ) -> FieldResult<MediaType> {
-        if !&context.is_authenticated() {
-            return Err(FieldError::new(
-                "You need to be authenticated to use this mutation",
-                Value::null(),
-            ));
-        }
-
-        if !&context.has_permissioned_role(vec![UserRoleEnum::Admin, UserRoleEnum::Moderator]) {
-            return Err(FieldError::new(
-                "You do not have the required permission to perform this action",
-                Value::null(),
-            ));
-        }
-
+    ) -> Result<MediaType, AppError> {
+        // The policy is checked once the media is found, as it depends on its owner
         edit_media::edit_media(context, uuid, media).await
     }
 
-    #[graphql(description = "Deletes a media")]
-    async fn delete_media<'a>(context: &'a GQLContext, uuid: uuid::Uuid) -> FieldResult<bool> {
-        if !&context.is_authenticated() {
-            return Err(FieldError::new(
-                "You need to be authenticated to use this mutation",
-                Value::null(),
-            ));
-        }
-
-        if !&context.has_permissioned_role(vec![UserRoleEnum::Admin]) {
-            return Err(FieldError::new(
-                "You do not have the required permission to perform this action",
-                Value::null(),
-            ));
-        }
-
+    #[graphql(description = r#"
+        Deletes a media. Publishers can only delete the media they uploaded.
+        The media file is removed from the storage once the deletion grace period is over,
+        until then the media can be restored.
+    "#)]
+    async fn delete_media<'a>(context: &'a GQLContext, uuid: uuid::Uuid) -> Result<bool, AppError> {
+        // The policy is checked once the media is found, as it depends on its owner
         delete_media::delete_media(context, uuid).await
     }
 
+    #[graphql(description = "Restores a deleted media which file has not been purged yet")]
+    async fn restore_media<'a>(
+        context: &'a GQLContext,
+        uuid: uuid::Uuid,
+    ) -> Result<MediaType, AppError> {
+        context.authorize(Action::RestoreMedia, None)?;
+
+        restore_media::restore_media(context, uuid).await
+    }
+
     // Changes password for current user
-    async fn change_password<'a>(context: &'a GQLContext, password: String) -> FieldResult<bool> {
+    async fn change_password<'a>(
+        context: &'a GQLContext,
+        password: String,
+    ) -> Result<bool, AppError> {
+        context.authorize(Action::ChangePassword, None)?;
+
         update_password::update_password(context, password).await
     }
 }
@@ -0,0 +1,66 @@
+use std::collections::HashSet;
+
+use crate::{
+    db::models::{
+        bundle::{Bundle, NewBundle},
+        media::Media,
+    },
+    errors::app::AppError,
+    graphql::{
+        context::GQLContext,
+        types::{input::bundle::NewBundleInput, output::bundle::BundleType},
+    },
+};
+
+pub async fn create_bundle(
+    context: &GQLContext,
+    bundle_input: NewBundleInput,
+) -> Result<BundleType, AppError> {
+    let connection = context.get_db_connection();
+    let media_uuids = bundle_input.media_uuids.clone();
+
+    if media_uuids.is_empty() {
+        return Err(AppError::BadInput(
+            "A bundle requires at least one media".to_string(),
+        ));
+    }
+
+    if bundle_input.price_msat.0 < 0 {
+        return Err(AppError::BadInput(
+            "The price of a bundle can't be negative".to_string(),
+        ));
+    }
+
+    let unique = media_uuids.iter().collect::<HashSet<&uuid::Uuid>>();
+    if unique.len() != media_uuids.len() {
+        return Err(AppError::BadInput(
+            "A media can only be provided once in a bundle".to_string(),
+        ));
+    }
+
+    for uuid in media_uuids.clone() {
+        let media = connection
+            .run(move |c| Media::find_one_by_uuid(uuid, c))
+            .await?;
+
+        if media.is_none() {
+            return Err(AppError::NotFound(format!(
+                "No media found with uuid {}",
+                uuid
+            )));
+        }
+    }
+
+    let new_bundle = NewBundle::from(bundle_input);
+    let bundle = connection
+        .run(move |c| Bundle::create(new_bundle, media_uuids, c))
+        .await;
+
+    match bundle {
+        Ok(bundle) => Ok(BundleType::from(bundle)),
+        Err(diesel::result::Error::NotFound) => Err(AppError::NotFound(
+            "A media of the bundle has been deleted meanwhile".to_string(),
+        )),
+        Err(e) => Err(e.into()),
+    }
+}
@@ -1,7 +1,6 @@
-use juniper::{FieldError, FieldResult, Value};
-
 use crate::{
     db::models::user::{NewUser, User},
+    errors::app::AppError,
     graphql::{
         context::GQLContext,
         types::{input::user::NewUserInput, output::user::UserType},
@@ -11,7 +10,7 @@ use crate::{
 pub async fn create_user<'a>(
     context: &'a GQLContext,
     new_user_input: NewUserInput,
-) -> FieldResult<UserType> {
+) -> Result<UserType, AppError> {
     let connection = context.get_db_connection();
 
     let login = new_user_input.login.clone();
@@ -19,39 +18,20 @@ pub async fn create_user<'a>(
 
     let result = connection
         .run(move |c| User::find_one_by_username_or_email(login, email, c))
-        .await;
-
-    if result.is_err() {
-        return Err(FieldError::new(
-            "An error happened while ensure user does not exist in db",
-            Value::null(),
-        ));
-    };
-
-    let result = result.unwrap();
+        .await?;
 
     match result {
-        Some(_) => {
-            return Err(FieldError::new(
-                "A user already exists with the provided user or email",
-                Value::null(),
-            ));
-        }
+        Some(_) => Err(AppError::AlreadyExists(
+            "A user already exists with the provided user or email".to_string(),
+        )),
         None => {
             let connection = context.get_db_connection();
 
             let user = connection
                 .run(move |c| User::create(NewUser::from(new_user_input), c))
-                .await;
-
-            if user.is_err() {
-                return Err(FieldError::new(
-                    "An error happened while registering new user",
-                    Value::null(),
-                ));
-            }
+                .await?;
 
-            Ok(UserType::from(user.unwrap()))
+            Ok(UserType::from(user))
         }
     }
 }
@@ -0,0 +1,14 @@
+use crate::{db::models::bundle::Bundle, errors::app::AppError, graphql::context::GQLContext};
+
+pub async fn delete_bundle(context: &GQLContext, uuid: uuid::Uuid) -> Result<bool, AppError> {
+    let connection = context.get_db_connection();
+
+    let count = connection.run(move |c| Bundle::delete(uuid, c)).await?;
+
+    match count {
+        1 => Ok(true),
+        _ => Err(AppError::NotFound(
+            "No bundle found with provided uuid".to_string(),
+        )),
+    }
+}
@@ -1,26 +1,26 @@
-use juniper::{FieldError, FieldResult};
+use crate::{
+    db::models::media::Media,
+    errors::app::AppError,
+    graphql::{context::GQLContext, policy::Action},
+};
 
-use crate::{db::models::media::Media, graphql::context::GQLContext};
-
-pub async fn delete_media<'a>(context: &'a GQLContext, uuid: uuid::Uuid) -> FieldResult<bool> {
+pub async fn delete_media<'a>(context: &'a GQLContext, uuid: uuid::Uuid) -> Result<bool, AppError> {
     let connection = context.get_db_connection();
 
-    let result = connection.run(move |c| Media::delete(uuid, c)).await;
+    let media = connection
+        .run(move |c| Media::find_one_by_uuid(uuid, c))
+        .await?
+        .ok_or_else(|| AppError::NotFound("No media found with provided uuid".to_string()))?;
+
+    context.authorize(Action::DeleteMedia, media.owner_uuid)?;
+
+    let count = connection.run(move |c| Media::delete(uuid, c)).await?;
 
-    match result {
-        Ok(count) => {
-            if count == 1 {
-                Ok(true)
-            } else {
-                Err(FieldError::new(
-                    "An error happened while trying to delete media",
-                    juniper::Value::Null,
-                ))
-            }
-        }
-        Err(_) => Err(FieldError::new(
-            "An error happened while trying to delete media",
-            juniper::Value::Null,
+    match count {
+        1 => Ok(true),
+        // Deleted meanwhile
+        _ => Err(AppError::NotFound(
+            "No media found with provided uuid".to_string(),
         )),
     }
 }
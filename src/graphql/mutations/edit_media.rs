@@ -1,9 +1,9 @@
-use juniper::{FieldError, FieldResult, Value};
-
 use crate::{
     db::models::media::Media,
+    errors::app::AppError,
     graphql::{
         context::GQLContext,
+        policy::Action,
         types::{input::media::EditMediaInput, output::media::MediaType},
     },
 };
@@ -11,43 +11,48 @@ use crate::{
 pub async fn edit_media<'a>(
     context: &'a GQLContext,
     uuid: uuid::Uuid,
-    edited_media_input: EditMediaInput,
-) -> FieldResult<MediaType> {
+    mut edited_media_input: EditMediaInput,
+) -> Result<MediaType, AppError> {
     let connection = context.get_db_connection();
 
     let media = connection
         .run(move |c| Media::find_one_by_uuid(uuid, c))
-        .await;
+        .await?
+        .ok_or_else(|| AppError::NotFound("No media found with provided uuid".to_string()))?;
+
+    context.authorize(Action::EditMedia, media.owner_uuid)?;
 
-    if media.is_err() {
-        return Err(FieldError::new(
-            "An error happened while fetch the media target in db",
-            Value::null(),
-        ));
+    // The fiat price is checked along with the current one, unless the price in millisatoshis is restored
+    let restores_sats = edited_media_input.price_currency.as_deref() == Some("");
+    let (currency, amount) = match restores_sats {
+        true => (None, None),
+        false => (
+            edited_media_input
+                .price_currency
+                .clone()
+                .or_else(|| media.price_currency.clone()),
+            edited_media_input.price_amount.or(media.price_amount),
+        ),
     };
 
-    let media = media.unwrap();
-
-    match media {
-        Some(media) => {
-            let result = connection
-                .run(move |c| Media::update(media.uuid, edited_media_input, c))
-                .await;
-            match result {
-                Ok(result) => Ok(MediaType::from(result)),
-                Err(_) => {
-                    return Err(FieldError::new(
-                        "An error happened while updating media in  db",
-                        Value::null(),
-                    ));
-                }
-            }
-        }
-        None => {
-            return Err(FieldError::new(
-                "No media found with provided uuid",
-                Value::null(),
-            ));
-        }
+    let currency = context
+        .get_rates()
+        .check_price(
+            edited_media_input.price_msat.as_ref().map(|price| price.0),
+            currency.as_deref(),
+            amount,
+        )
+        .await?;
+
+    if edited_media_input.price_currency.is_some() && !restores_sats {
+        edited_media_input.price_currency = currency;
     }
+
+    // The media may have been deleted meanwhile
+    let media = connection
+        .run(move |c| Media::update(media.uuid, edited_media_input, c))
+        .await?
+        .ok_or_else(|| AppError::NotFound("No media found with provided uuid".to_string()))?;
+
+    Ok(MediaType::from(media))
 }
@@ -1,7 +1,6 @@
-use juniper::{FieldError, FieldResult, Value};
-
 use crate::{
     db::models::user::{EditUser, User},
+    errors::app::AppError,
     graphql::{
         context::GQLContext,
         types::{input::user::EditUserInput, output::user::UserType},
@@ -12,42 +11,24 @@ pub async fn edit_user<'a>(
     context: &'a GQLContext,
     uuid: uuid::Uuid,
     edited_user_input: EditUserInput,
-) -> FieldResult<UserType> {
+) -> Result<UserType, AppError> {
     let connection = context.get_db_connection();
 
     let user = connection
         .run(move |c| User::find_one_by_uuid(uuid, c))
-        .await;
-
-    if user.is_err() {
-        return Err(FieldError::new(
-            "An error happened while fetch the media target in db",
-            Value::null(),
-        ));
-    };
-
-    let user = user.unwrap();
+        .await?;
 
     match user {
         Some(user) => {
             let edit_user = EditUser::from(edited_user_input);
             let result = connection
                 .run(move |c| User::update(user.uuid, edit_user, c))
-                .await;
-
-            if result.is_err() {
-                return Err(FieldError::new("Error while updating user", Value::null()));
-            }
-
-            let result = result.unwrap();
+                .await?;
 
             Ok(UserType::from(result))
         }
-        None => {
-            return Err(FieldError::new(
-                "No user found with the provided uuid",
-                Value::null(),
-            ));
-        }
+        None => Err(AppError::NotFound(
+            "No user found with the provided uuid".to_string(),
+        )),
     }
 }
@@ -1,7 +1,12 @@
+pub mod create_bundle;
 pub mod create_user;
+pub mod delete_bundle;
 pub mod delete_media;
 pub mod delete_user;
 pub mod edit_media;
 pub mod edit_user;
+pub mod restore_bundle;
+pub mod restore_media;
 pub mod update_password;
 pub mod upload_file;
+pub mod upload_files;
@@ -0,0 +1,21 @@
+use crate::{
+    db::models::bundle::Bundle,
+    errors::app::AppError,
+    graphql::{context::GQLContext, types::output::bundle::BundleType},
+};
+
+pub async fn restore_bundle(
+    context: &GQLContext,
+    uuid: uuid::Uuid,
+) -> Result<BundleType, AppError> {
+    let connection = context.get_db_connection();
+
+    let bundle = connection.run(move |c| Bundle::restore(uuid, c)).await?;
+
+    match bundle {
+        Some(bundle) => Ok(BundleType::from(bundle)),
+        None => Err(AppError::NotFound(
+            "No deleted bundle found with provided uuid".to_string(),
+        )),
+    }
+}
@@ -0,0 +1,19 @@
+use crate::{
+    db::models::media::Media,
+    errors::app::AppError,
+    graphql::{context::GQLContext, types::output::media::MediaType},
+};
+
+pub async fn restore_media(context: &GQLContext, uuid: uuid::Uuid) -> Result<MediaType, AppError> {
+    let connection = context.get_db_connection();
+
+    let media = connection.run(move |c| Media::restore(uuid, c)).await?;
+
+    match media {
+        Some(media) => Ok(MediaType::from(media)),
+        None => Err(AppError::NotFound(
+            "No deleted media found with provided uuid. Its file may have been purged already"
+                .to_string(),
+        )),
+    }
+}
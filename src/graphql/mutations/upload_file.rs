@@ -1,62 +1,64 @@
-use juniper::{FieldError, FieldResult, Value};
-
 use crate::{
-    db::models::media::{Media, NewMedia},
+    db::models::media::Media,
+    errors::app::AppError,
     graphql::{
         context::GQLContext,
         types::{input::file::FileInput, output::media::MediaType},
     },
+    uploads::multipart::{group_media_files, store_media_files},
 };
 
 pub async fn upload_file<'a>(
     context: &'a GQLContext,
-    file_input: FileInput,
-) -> FieldResult<MediaType> {
+    mut file_input: FileInput,
+) -> Result<MediaType, AppError> {
+    // The media is owned by the user who uploads it
+    let owner = context
+        .get_user()
+        .as_ref()
+        .ok_or(AppError::Unauthenticated)?
+        .uuid;
+
+    file_input.price_currency = context
+        .get_rates()
+        .check_price(
+            Some(file_input.price_msat.0),
+            file_input.price_currency.as_deref(),
+            file_input.price_amount,
+        )
+        .await?;
+
     let files_map = context.get_files();
     let connection = context.get_db_connection();
 
     match files_map {
         Some(files_map) => {
             if files_map.len() == 0 {
-                return Err(FieldError::new(
-                    "Current mutation requires a single file for upload. No file provided",
-                    Value::null(),
+                return Err(AppError::BadInput(
+                    "Current mutation requires a single file for upload. No file provided"
+                        .to_string(),
                 ));
             }
 
-            let file = files_map.into_iter().next();
+            let files = group_media_files(files_map)?;
 
-            match file {
-                Some(file) => {
-                    let persisted_path = file.1.persist_file();
+            match files.first() {
+                Some(files) => {
+                    let new_media =
+                        store_media_files(context.get_storage(), files, file_input, owner).await?;
 
-                    match persisted_path {
-                        Ok(path) => {
-                            let new_media = NewMedia::from((&path, file_input));
-                            let media = connection.run(move |c| Media::create(new_media, c)).await;
-                            match media {
-                                Ok(media) => Ok(MediaType::from(media)),
-                                Err(_) => Err(FieldError::new(
-                                    "Error while persisting file. Aborting",
-                                    Value::null(),
-                                )),
-                            }
-                        }
-                        Err(_) => Err(FieldError::new(
-                            "Error while writing file on filesystem.",
-                            Value::null(),
-                        )),
-                    }
+                    let media = connection.run(move |c| Media::create(new_media, c)).await?;
+                    Ok(MediaType::from(media))
                 }
-                None => Err(FieldError::new(
-                    "Current mutation requires a single file for upload. No file provided",
-                    Value::null(),
+                None => Err(AppError::BadInput(
+                    "Current mutation requires a single file for upload. No file provided"
+                        .to_string(),
                 )),
             }
         }
-        None => Err(FieldError::new(
-            "Current mutation accepts a single file for upload. Multiple files uploaded provided",
-            Value::null(),
+        None => Err(AppError::BadInput(
+            "Current mutation accepts a single file for upload. Multiple files uploaded provided"
+                .to_string(),
         )),
     }
 }
@@ -0,0 +1,69 @@
+use diesel::Connection;
+
+use crate::{
+    db::models::media::Media,
+    errors::app::AppError,
+    graphql::{
+        context::GQLContext,
+        types::{input::file::FileInput, output::media::MediaType},
+    },
+    uploads::multipart::{group_media_files, store_media_files},
+};
+
+/// Stores several files and creates a media for each of them.
+///
+/// Files are matched with the inputs in the order of their multipart field names
+/// (`0`, `1`, ...), along with their preview and thumbnail if any (`0.preview`, `0.thumbnail`).
+/// The media are created only if every file has been stored.
+pub async fn upload_files(
+    context: &GQLContext,
+    mut file_inputs: Vec<FileInput>,
+) -> Result<Vec<MediaType>, AppError> {
+    // The media are owned by the user who uploads them
+    let owner = context
+        .get_user()
+        .as_ref()
+        .ok_or(AppError::Unauthenticated)?
+        .uuid;
+
+    for file_input in file_inputs.iter_mut() {
+        file_input.price_currency = context
+            .get_rates()
+            .check_price(
+                Some(file_input.price_msat.0),
+                file_input.price_currency.as_deref(),
+                file_input.price_amount,
+            )
+            .await?;
+    }
+
+    let files = match context.get_files() {
+        Some(files_map) => group_media_files(files_map)?,
+        None => vec![],
+    };
+
+    if files.is_empty() || files.len() != file_inputs.len() {
+        return Err(AppError::BadInput(
+            "Current mutation requires a file for each file input".to_string(),
+        ));
+    }
+
+    let mut new_media = Vec::with_capacity(file_inputs.len());
+    for (files, file_input) in files.iter().zip(file_inputs) {
+        new_media.push(store_media_files(context.get_storage(), files, file_input, owner).await?);
+    }
+
+    let media = context
+        .get_db_connection()
+        .run(move |c| {
+            c.transaction(|| {
+                new_media
+                    .into_iter()
+                    .map(|new_media| Media::create(new_media, c))
+                    .collect::<Result<Vec<Media>, diesel::result::Error>>()
+            })
+        })
+        .await?;
+
+    Ok(media.into_iter().map(MediaType::from).collect())
+}
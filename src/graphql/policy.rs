@@ -0,0 +1,217 @@
+use juniper::FieldError;
+use uuid::Uuid;
+
+use crate::{
+    db::models::user::{User, UserRoleEnum},
+    errors::app::AppError,
+};
+
+use UserRoleEnum::{Admin, Moderator, Publisher};
+
+/// The operations of the GraphQL API restricted to some users
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    CreateUser,
+    EditUser,
+    DeleteUser,
+    ListUsers,
+    UploadMedia,
+    EditMedia,
+    DeleteMedia,
+    RestoreMedia,
+    // Listing the unpublished media of every user
+    ListAllMedia,
+    ListOwnMedia,
+    CreateBundle,
+    DeleteBundle,
+    RestoreBundle,
+    // Listing the unpublished bundles
+    ListAllBundles,
+    ChangePassword,
+}
+
+/// The users allowed to perform an action: the users granted with one of
+/// the roles, and the owner of the resource if ownership is enough
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rule {
+    pub roles: &'static [UserRoleEnum],
+    pub owner: bool,
+}
+
+impl Action {
+    /// Provides the rule of the policy for an action
+    pub fn rule(self) -> Rule {
+        let (roles, owner): (&'static [UserRoleEnum], bool) = match self {
+            // Any authenticated user may create users
+            Action::CreateUser => (&[Admin, Moderator, Publisher], false),
+            Action::EditUser => (&[Admin], false),
+            Action::DeleteUser => (&[Admin], false),
+            Action::ListUsers => (&[Admin], false),
+            Action::UploadMedia => (&[Admin, Moderator, Publisher], false),
+            Action::EditMedia => (&[Admin, Moderator], true),
+            Action::DeleteMedia => (&[Admin], true),
+            Action::RestoreMedia => (&[Admin], false),
+            Action::ListAllMedia => (&[Admin], false),
+            Action::ListOwnMedia => (&[Admin, Moderator, Publisher], false),
+            Action::CreateBundle => (&[Admin, Moderator], false),
+            Action::DeleteBundle => (&[Admin], false),
+            Action::RestoreBundle => (&[Admin], false),
+            Action::ListAllBundles => (&[Admin, Moderator], false),
+            Action::ChangePassword => (&[Admin, Moderator, Publisher], false),
+        };
+
+        Rule { roles, owner }
+    }
+}
+
+/// The reason an action is refused
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Denial {
+    Unauthenticated,
+    Forbidden,
+}
+
+impl From<Denial> for FieldError {
+    fn from(denial: Denial) -> Self {
+        AppError::from(denial).into()
+    }
+}
+
+/// Checks if a user may perform an action on a resource owned by `owner`, if any.
+/// Anonymous users are never allowed.
+pub fn authorize(user: Option<&User>, action: Action, owner: Option<Uuid>) -> Result<(), Denial> {
+    let user = user.ok_or(Denial::Unauthenticated)?;
+    let rule = action.rule();
+
+    let is_owner = rule.owner && owner == Some(user.uuid);
+    match is_owner || rule.roles.contains(&user.role) {
+        true => Ok(()),
+        false => Err(Denial::Forbidden),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+
+    fn user(role: UserRoleEnum) -> User {
+        let now = Utc::now().naive_utc();
+
+        User {
+            id: 1,
+            uuid: Uuid::new_v4(),
+            login: "satoshi".to_string(),
+            email: "satoshi@nakamoto.btc".to_string(),
+            password: String::new(),
+            created_at: now,
+            updated_at: now,
+            role,
+        }
+    }
+
+    /// Checks the roles allowed to perform an action on a resource they do not own
+    fn assert_roles(action: Action, allowed: &[UserRoleEnum]) {
+        for role in [Admin, Moderator, Publisher] {
+            let expected = match allowed.contains(&role) {
+                true => Ok(()),
+                false => Err(Denial::Forbidden),
+            };
+            assert_eq!(
+                authorize(Some(&user(role.clone())), action, Some(Uuid::new_v4())),
+                expected,
+                "{:?} by {}",
+                action,
+                role
+            );
+        }
+    }
+
+    #[test]
+    fn role_matrix() {
+        assert_roles(Action::CreateUser, &[Admin, Moderator, Publisher]);
+        assert_roles(Action::EditUser, &[Admin]);
+        assert_roles(Action::DeleteUser, &[Admin]);
+        assert_roles(Action::ListUsers, &[Admin]);
+        assert_roles(Action::UploadMedia, &[Admin, Moderator, Publisher]);
+        assert_roles(Action::EditMedia, &[Admin, Moderator]);
+        assert_roles(Action::DeleteMedia, &[Admin]);
+        assert_roles(Action::RestoreMedia, &[Admin]);
+        assert_roles(Action::ListAllMedia, &[Admin]);
+        assert_roles(Action::ListOwnMedia, &[Admin, Moderator, Publisher]);
+        assert_roles(Action::CreateBundle, &[Admin, Moderator]);
+        assert_roles(Action::DeleteBundle, &[Admin]);
+        assert_roles(Action::RestoreBundle, &[Admin]);
+        assert_roles(Action::ListAllBundles, &[Admin, Moderator]);
+        assert_roles(Action::ChangePassword, &[Admin, Moderator, Publisher]);
+    }
+
+    #[test]
+    fn anonymous_users_are_unauthenticated() {
+        for action in [
+            Action::CreateUser,
+            Action::UploadMedia,
+            Action::EditMedia,
+            Action::ListOwnMedia,
+        ] {
+            assert_eq!(
+                authorize(None, action, Some(Uuid::new_v4())),
+                Err(Denial::Unauthenticated)
+            );
+        }
+    }
+
+    #[test]
+    fn owners_manage_their_media() {
+        let publisher = user(Publisher);
+
+        for action in [Action::EditMedia, Action::DeleteMedia] {
+            assert_eq!(
+                authorize(Some(&publisher), action, Some(publisher.uuid)),
+                Ok(())
+            );
+        }
+    }
+
+    #[test]
+    fn ownership_is_not_enough_for_restricted_actions() {
+        let publisher = user(Publisher);
+
+        for action in [
+            Action::RestoreMedia,
+            Action::ListAllMedia,
+            Action::DeleteBundle,
+            Action::RestoreBundle,
+        ] {
+            assert_eq!(
+                authorize(Some(&publisher), action, Some(publisher.uuid)),
+                Err(Denial::Forbidden)
+            );
+        }
+    }
+
+    #[test]
+    fn resources_without_owner_require_a_role() {
+        assert_eq!(
+            authorize(Some(&user(Publisher)), Action::EditMedia, None),
+            Err(Denial::Forbidden)
+        );
+        assert_eq!(
+            authorize(Some(&user(Moderator)), Action::EditMedia, None),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn denials_provide_their_code() {
+        let error = FieldError::from(Denial::Forbidden);
+        assert_eq!(error.extensions(), &graphql_value!({ "code": "FORBIDDEN" }));
+
+        let error = FieldError::from(Denial::Unauthenticated);
+        assert_eq!(
+            error.extensions(),
+            &graphql_value!({ "code": "UNAUTHENTICATED" })
+        );
+    }
+}
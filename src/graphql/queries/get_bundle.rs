@@ -0,0 +1,33 @@
+use crate::db::models::bundle::Bundle;
+use crate::errors::app::AppError;
+use crate::graphql::context::GQLContext;
+use crate::graphql::policy::Action;
+use crate::graphql::types::output::bundle::BundleType;
+use uuid::Uuid;
+
+/// Gets a bundle. Unpublished bundles are only provided to the admins and moderators
+pub async fn get_bundle(context: &GQLContext, uuid: Uuid) -> Result<BundleType, AppError> {
+    let connection = context.get_db_connection();
+    let can_list_all = context.is_allowed(Action::ListAllBundles, None);
+    let bundle = connection
+        .run(move |c| Bundle::find_one_by_uuid(uuid, c))
+        .await?;
+
+    match bundle {
+        Some(bundle) if bundle.published || can_list_all => Ok(BundleType::from(bundle)),
+        _ => Err(AppError::NotFound(
+            "No bundle found with the provided Uuid".to_string(),
+        )),
+    }
+}
+
+/// Lists the published bundles, or every bundle for the admins and moderators
+pub async fn get_bundles_list(context: &GQLContext) -> Result<Vec<BundleType>, AppError> {
+    let connection = context.get_db_connection();
+    let bundles = match context.is_allowed(Action::ListAllBundles, None) {
+        true => connection.run(Bundle::find_all).await?,
+        false => connection.run(Bundle::find_all_published).await?,
+    };
+
+    Ok(bundles.into_iter().map(BundleType::from).collect())
+}
@@ -1,20 +1,24 @@
 use crate::db::models::media::Media;
-use crate::db::models::user::UserRoleEnum;
+use crate::errors::app::AppError;
 use crate::graphql::context::GQLContext;
+use crate::graphql::policy::Action;
 use crate::graphql::types::output::media::MediaType;
-use juniper::FieldError;
 
-pub async fn get_files_list<'a>(context: &'a GQLContext) -> Result<Vec<MediaType>, FieldError> {
+pub async fn get_files_list<'a>(context: &'a GQLContext) -> Result<Vec<MediaType>, AppError> {
     let connection = context.get_db_connection();
-    let db_results = match context.is_authenticated() && context.has_permissioned_role(vec![UserRoleEnum::Admin]) {
-        true => {
-            connection.run(move |c| Media::find_all(c)).await
-
-        },
-        false => {
-            connection.run(move |c| Media::find_all_published(c)).await
+    let db_results = match context.get_user() {
+        Some(_) if context.is_allowed(Action::ListAllMedia, None) => {
+            connection.run(Media::find_all).await
+        }
+        // Other users also see the unpublished media they uploaded
+        Some(user) => {
+            let user_uuid = user.uuid;
+            connection
+                .run(move |c| Media::find_all_visible_to(user_uuid, c))
+                .await
         }
-    };
+        None => connection.run(Media::find_all_published).await,
+    }?;
     Ok(db_results
         .into_iter()
         .map(|p| MediaType::from(p))
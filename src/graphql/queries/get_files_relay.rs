@@ -1,9 +1,9 @@
-use juniper::FieldResult;
 use juniper_relay_connection::RelayConnection;
 
 use crate::{
-    db::models::{media::Media, user::UserRoleEnum},
-    graphql::{context::GQLContext, types::output::media::MediaType},
+    db::models::media::Media,
+    errors::app::AppError,
+    graphql::{context::GQLContext, policy::Action, types::output::media::MediaType},
 };
 
 pub async fn get_files_list_relay<'a>(
@@ -12,26 +12,30 @@ pub async fn get_files_list_relay<'a>(
     after: Option<String>,
     last: Option<i32>,
     before: Option<String>,
-) -> FieldResult<RelayConnection<MediaType>> {
+) -> Result<RelayConnection<MediaType>, AppError> {
     let connection = context.get_db_connection();
 
-    let db_results = match context.is_authenticated() && context.has_permissioned_role(vec![UserRoleEnum::Admin]) {
-        true => {
-            connection.run(move |c| Media::find_all(c)).await
-
-        },
-        false => {
-            connection.run(move |c| Media::find_all_published(c)).await
+    let db_results = match context.get_user() {
+        Some(_) if context.is_allowed(Action::ListAllMedia, None) => {
+            connection.run(Media::find_all).await
+        }
+        // Other users also see the unpublished media they uploaded
+        Some(user) => {
+            let user_uuid = user.uuid;
+            connection
+                .run(move |c| Media::find_all_visible_to(user_uuid, c))
+                .await
         }
-    };
+        None => connection.run(Media::find_all_published).await,
+    }?;
 
     // Ok(RelayConnection::empty())
-    let result = RelayConnection::new(first, after, last, before, |first, after, last| {
+    // The pagination arguments are checked by the connection
+    RelayConnection::new(first, after, last, before, |first, after, last| {
         Ok(db_results
             .into_iter()
             .map(|p| MediaType::from(p))
             .collect::<Vec<MediaType>>())
-    });
-
-    result
+    })
+    .map_err(|e| AppError::BadInput(e.message().to_string()))
 }
@@ -1,30 +1,169 @@
 use crate::db::models::media::Media;
+use crate::db::models::media_payment::MediaPayment;
+use crate::errors::app::AppError;
 use crate::graphql::context::GQLContext;
+use crate::graphql::policy::Action;
+use crate::graphql::queries::request_invoice_for_media::generate_media_payment;
 use crate::graphql::types::output::media::MediaType;
-use juniper::{FieldError, Value};
+use crate::lnd::invoice::InvoiceDetails;
+use crate::lnd::paywall::{PaymentStatus, Paywall, PaywallPayment};
 use uuid::Uuid;
 
-pub async fn get_media<'a, 'b>(
+/// Provides a media behind the paywall.
+///
+/// A paid media is only provided once the provided `payment_request` is settled,
+/// along with its download URL and its full description. Otherwise a `PAYMENT_REQUIRED`
+/// error provides the invoice to be paid: the pending one if any or a new one, as long as
+/// the client did not request more than `INVOICE_RATE_LIMIT` new invoices within a minute.
+/// An `INVOICE_NOT_SETTLED` error is reported while a paid invoice is held.
+/// The users allowed to edit the media get it without paying.
+///
+/// Only the client providing the `client_secret` returned along with the invoice
+/// gets the media unlocked without the `preimage` of the invoice, which is a proof of payment.
+pub async fn get_media<'a>(
     context: &'a GQLContext,
     uuid: Uuid,
     payment_request: Option<String>,
-) -> Result<MediaType, FieldError> {
+    preimage: Option<String>,
+    client_secret: Option<String>,
+) -> Result<MediaType, AppError> {
     let connection = context.get_db_connection();
-    let result = connection
+    let media = match connection
         .run(move |c| Media::find_one_by_uuid(uuid, c))
-        .await;
-
-    match result {
-        Ok(result) => match result {
-            Some(media) => match payment_request {
-                Some(payment_request) => Ok(MediaType::from((media, payment_request))),
-                None => Ok(MediaType::from(media)),
-            },
-            None => Err(FieldError::new(
-                "No media found with the provided Uuid",
-                Value::null(),
-            )),
-        },
-        Err(_) => Err(FieldError::new("Error while fetching media", Value::null())),
+        .await?
+    {
+        Some(media) => media,
+        None => {
+            return Err(AppError::NotFound(
+                "No media found with the provided Uuid".to_string(),
+            ))
+        }
+    };
+
+    // The users allowed to edit the media are not required to pay it
+    if media.is_free() || context.is_allowed(Action::EditMedia, media.owner_uuid) {
+        return Ok(MediaType::from(media));
+    }
+
+    let payment = match payment_request {
+        Some(payment_request) => {
+            connection
+                .run(move |c| MediaPayment::find_one_by_request(payment_request, c))
+                .await?
+        }
+        None => None,
+    };
+
+    // A new invoice is required unless one is provided
+    let payment = match payment {
+        Some(payment) if payment.media_uuid != media.uuid => {
+            return Err(AppError::BadInput(
+                "payment_request does not match with the request media".to_string(),
+            ))
+        }
+        Some(payment) => payment,
+        None => return Err(new_payment_required(context, media).await),
+    };
+
+    let paywall = Paywall::new(
+        connection,
+        context.get_lnd_client(),
+        context.get_invoice_events(),
+    );
+    let status = paywall
+        .resolve(&payment, preimage.as_deref(), client_secret.as_deref())
+        .await?;
+
+    match status {
+        PaymentStatus::Unlocked => {
+            let token = payment.download_token().generate_token()?;
+            Ok(MediaType::unlocked(media, token))
+        }
+        PaymentStatus::ProofRequired => Err(AppError::PreimageRequired),
+        PaymentStatus::Canceled | PaymentStatus::Expired => {
+            Err(new_payment_required(context, media).await)
+        }
+        status => Err(pending_payment_error(&payment, status)),
+    }
+}
+
+/// Provides the error reported while the invoice of a payment is pending.
+/// A held invoice is already paid, so it shall not be paid again.
+fn pending_payment_error(payment: &MediaPayment, status: PaymentStatus) -> AppError {
+    match status {
+        PaymentStatus::Held => AppError::InvoiceNotSettled,
+        _ => payment_required(payment),
+    }
+}
+
+/// Generates a new payment for a media and provides its requirements.
+/// Each client is limited to a number of new invoices per minute.
+async fn new_payment_required(context: &GQLContext, media: Media) -> AppError {
+    if !context.allow_new_invoice() {
+        return AppError::TooManyRequests;
+    }
+
+    let payment = generate_media_payment(
+        context.get_db_connection(),
+        context.get_lnd_client(),
+        context.get_rates(),
+        media,
+    )
+    .await;
+
+    match payment {
+        Ok((payment, secret)) => {
+            AppError::PaymentRequired(invoice_details(&payment).with_client_secret(secret))
+        }
+        Err(e) => e,
+    }
+}
+
+fn payment_required(payment: &MediaPayment) -> AppError {
+    AppError::PaymentRequired(invoice_details(payment))
+}
+
+fn invoice_details(payment: &MediaPayment) -> InvoiceDetails {
+    InvoiceDetails::new(&payment.request, &payment.hash, payment.expires_at)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+
+    use super::*;
+
+    fn payment() -> MediaPayment {
+        MediaPayment {
+            uuid: Uuid::new_v4(),
+            request: "lnbcrt1".to_string(),
+            state: None,
+            hash: "66687aadf862bd776c8fc18b8e9f8e20089714856ee233b3902a591d0d5f2925".to_string(),
+            media_uuid: Uuid::new_v4(),
+            expires_at: (Utc::now() + Duration::hours(1)).naive_utc(),
+            valid_until: None,
+            settled_at: None,
+            price_currency: None,
+            exchange_rate: None,
+            client_secret_hash: None,
+        }
+    }
+
+    #[test]
+    fn held_invoices_are_not_paid_again() {
+        assert_eq!(
+            pending_payment_error(&payment(), PaymentStatus::Held),
+            AppError::InvoiceNotSettled
+        );
+    }
+
+    #[test]
+    fn open_invoices_shall_be_paid() {
+        let payment = payment();
+
+        assert_eq!(
+            pending_payment_error(&payment, PaymentStatus::Open),
+            AppError::PaymentRequired(invoice_details(&payment))
+        );
     }
 }
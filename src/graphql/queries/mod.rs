@@ -1,5 +1,8 @@
+pub mod get_bundle;
 pub mod get_files_list;
 pub mod get_files_relay;
 pub mod get_media;
+pub mod my_media;
+pub mod request_invoice_for_bundle;
 pub mod request_invoice_for_media;
 pub mod users_relay;
@@ -0,0 +1,22 @@
+use crate::{
+    db::models::media::Media,
+    errors::app::AppError,
+    graphql::{context::GQLContext, policy::Action, types::output::media::MediaType},
+};
+
+/// Lists the media uploaded by the current user, the latest first
+pub async fn my_media(context: &GQLContext) -> Result<Vec<MediaType>, AppError> {
+    context.authorize(Action::ListOwnMedia, None)?;
+    let user_uuid = context
+        .get_user()
+        .as_ref()
+        .map(|user| user.uuid)
+        .ok_or(AppError::Unauthenticated)?;
+
+    let media = context
+        .get_db_connection()
+        .run(move |c| Media::find_all_by_owner(user_uuid, c))
+        .await?;
+
+    Ok(media.into_iter().map(MediaType::from).collect())
+}
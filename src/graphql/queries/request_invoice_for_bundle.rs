@@ -0,0 +1,124 @@
+use crate::db::models::bundle::Bundle;
+use crate::db::models::bundle_payment::{BundlePayment, NewBundlePayment};
+use crate::db::models::download_token::ClientSecret;
+use crate::db::DbConnection;
+use crate::errors::app::AppError;
+use crate::graphql::context::GQLContext;
+use crate::graphql::policy::Action;
+use crate::graphql::types::output::bundle::BundleInvoice;
+use crate::lnd::client::LndClient;
+use crate::lnd::invoice::{InvoiceParams, InvoiceUtils};
+use crate::lnd::paywall::{PaymentStatus, Paywall, PaywallPayment};
+use tonic_lnd::rpc::invoice::InvoiceState;
+
+/// Requests an invoice and/or its state for a bundle.
+/// The request can get an optional `payment_request`
+/// that if provided will have its validity checked.
+/// The `preimage` of the payment_request or the `client_secret` returned
+/// along with it can be provided as a proof of payment.
+pub async fn request_invoice_for_bundle(
+    context: &GQLContext,
+    uuid: uuid::Uuid,
+    payment_request: Option<String>,
+    preimage: Option<String>,
+    client_secret: Option<String>,
+) -> Result<BundleInvoice, AppError> {
+    let connection = context.get_db_connection();
+    let client = context.get_lnd_client();
+
+    // Unpublished bundles are only invoiced to the users allowed to list them
+    let can_list_all = context.is_allowed(Action::ListAllBundles, None);
+    let bundle = match connection
+        .run(move |c| Bundle::find_one_by_uuid(uuid, c))
+        .await?
+    {
+        Some(bundle) if bundle.published || can_list_all => bundle,
+        _ => {
+            return Err(AppError::NotFound(
+                "No bundle found with provided uuid".to_string(),
+            ))
+        }
+    };
+
+    let payment_request = match payment_request {
+        Some(payment_request) => payment_request,
+        None => {
+            return generate_bundle_payment(connection, client, &bundle)
+                .await
+                .map(|(payment, secret)| {
+                    BundleInvoice::from((payment, InvoiceState::Open)).with_client_secret(secret)
+                })
+        }
+    };
+
+    let payment = match connection
+        .run(move |c| BundlePayment::find_one_by_request(payment_request, c))
+        .await?
+    {
+        Some(payment) => payment,
+        None => {
+            return Err(AppError::NotFound(
+                "No payment found with the provided payment_request".to_string(),
+            ))
+        }
+    };
+
+    if payment.bundle_uuid != bundle.uuid {
+        return Err(AppError::BadInput(
+            "payment_request does not match with the requested bundle".to_string(),
+        ));
+    }
+
+    let paywall = Paywall::new(connection, client, context.get_invoice_events());
+    let status = paywall
+        .resolve(&payment, preimage.as_deref(), client_secret.as_deref())
+        .await?;
+
+    match status {
+        PaymentStatus::Unlocked => proven_bundle_invoice(payment),
+        PaymentStatus::ProofRequired => Err(AppError::PreimageRequired),
+        PaymentStatus::Held => Ok(BundleInvoice::from((payment, InvoiceState::Accepted))),
+        PaymentStatus::Open => Ok(BundleInvoice::from((payment, InvoiceState::Open))),
+        PaymentStatus::Canceled => generate_bundle_payment(connection, client, &bundle)
+            .await
+            .map(|(payment, secret)| {
+                BundleInvoice::from((payment, InvoiceState::Canceled)).with_client_secret(secret)
+            }),
+        // A payment which validity is over is replaced by a new one
+        PaymentStatus::Expired => generate_bundle_payment(connection, client, &bundle)
+            .await
+            .map(|(payment, secret)| {
+                BundleInvoice::from((payment, InvoiceState::Open))
+                    .expired()
+                    .with_client_secret(secret)
+            }),
+    }
+}
+
+/// Provides the bundle invoice which payment has been proven
+/// along with the download token minted for the client.
+fn proven_bundle_invoice(payment: BundlePayment) -> Result<BundleInvoice, AppError> {
+    let token = payment.download_token().generate_token()?;
+
+    Ok(BundleInvoice::from((payment, InvoiceState::Settled)).with_download_token(token))
+}
+
+/// Generates a bundle payment with invoice registering on LND,
+/// along with the secret to be returned to the requesting client
+pub async fn generate_bundle_payment(
+    connection: &DbConnection,
+    lnd: &LndClient,
+    bundle: &Bundle,
+) -> Result<(BundlePayment, ClientSecret), AppError> {
+    let memo = format!("Buy bundle \"{}\" with uuid: {}", bundle.title, bundle.uuid);
+    let params = InvoiceParams::new(Some(bundle.price_msat), Some(memo), None);
+    let invoice = InvoiceUtils::generate_invoice(lnd, params).await?;
+
+    let secret = ClientSecret::generate();
+    let new_payment = NewBundlePayment::from((invoice, bundle)).with_client_secret(&secret);
+    let payment = connection
+        .run(move |c| BundlePayment::create(new_payment, c))
+        .await?;
+
+    Ok((payment, secret))
+}
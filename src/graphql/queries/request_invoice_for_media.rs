@@ -1,226 +1,170 @@
+use crate::db::models::download_token::ClientSecret;
 use crate::db::models::media::Media;
 use crate::db::models::media::MediaModelType;
 use crate::db::models::media_payment::MediaPayment;
 use crate::db::models::media_payment::NewMediaPayment;
-use crate::db::PostgresConn;
+use crate::db::DbConnection;
+use crate::errors::app::AppError;
 use crate::graphql::context::GQLContext;
 use crate::graphql::types::output::invoices::CustomInvoiceStateFlag;
 use crate::graphql::types::output::invoices::MediaInvoice;
+use crate::lnd::client::LndClient;
 use crate::lnd::invoice::InvoiceParams;
 use crate::lnd::invoice::InvoiceUtils;
-use juniper::{FieldError, Value};
-use tonic::codegen::InterceptedService;
-use tonic::transport::Channel;
+use crate::lnd::paywall::{PaymentStatus, Paywall, PaywallPayment};
+use crate::rates::client::RateClient;
 use tonic_lnd::rpc::invoice::InvoiceState;
-use tonic_lnd::MacaroonInterceptor;
-
-use tonic_lnd::rpc::lightning_client::LightningClient;
 
 /// Requests an invoice and/or its state for a media.
 /// The request can get an optional `payment_request`
 /// that if provided will have its validity checked.
+/// The `preimage` of the payment_request or the `client_secret` returned
+/// along with it can be provided as a proof of payment.
 pub async fn request_invoice_for_media<'a>(
     context: &'a GQLContext,
     uuid: uuid::Uuid,
     payment_request: Option<String>,
-) -> Result<MediaInvoice, FieldError> {
+    preimage: Option<String>,
+    client_secret: Option<String>,
+) -> Result<MediaInvoice, AppError> {
     let connection = context.get_db_connection();
     let client = context.get_lnd_client();
+    let rates = context.get_rates();
 
     // Get media from db
     let media = match connection
         .run(move |c| Media::find_one_by_uuid(uuid, c))
-        .await
+        .await?
     {
-        Ok(media) => match media {
-            Some(media) => media,
-            None => {
-                return Err(FieldError::new(
-                    "No media found with provided uuid",
-                    Value::Null,
-                ));
-            }
-        },
-        Err(e) => {
-            return Err(FieldError::new(
-                "Error while requesting database",
-                Value::Null,
-            ));
+        Some(media) => media,
+        None => {
+            return Err(AppError::NotFound(
+                "No media found with provided uuid".to_string(),
+            ))
         }
     };
 
     // Dispatch action based on presence of payment_request in request input
     match payment_request {
         Some(payment_request) => {
-            check_provided_payment_request(connection, client.clone(), media, payment_request).await
+            check_provided_payment_request(context, media, payment_request, preimage, client_secret)
+                .await
         }
-        None => create_media_invoice(connection, client, media).await,
+        None => create_media_invoice(connection, client, rates, media).await,
     }
 }
 
 async fn create_media_invoice(
-    connection: &PostgresConn,
-    lnd: &LightningClient<InterceptedService<Channel, MacaroonInterceptor>>,
+    connection: &DbConnection,
+    lnd: &LndClient,
+    rates: &RateClient,
     media: Media,
-) -> Result<MediaInvoice, FieldError> {
-    let payment = generate_media_payment(connection, lnd, media).await;
-    match payment {
-        Ok(payment) => Ok(MediaInvoice::from((payment, InvoiceState::Open))),
-        Err(_) => Err(FieldError::new(
-            "Error while registering payment request.",
-            Value::Null,
-        )),
-    }
+) -> Result<MediaInvoice, AppError> {
+    let (payment, secret) = generate_media_payment(connection, lnd, rates, media).await?;
+    Ok(MediaInvoice::from((payment, InvoiceState::Open)).with_client_secret(secret))
 }
 
 /// Processes a check of an invoice state when payment_request input field is provided
 async fn check_provided_payment_request(
-    connection: &PostgresConn,
-    lnd: LightningClient<InterceptedService<Channel, MacaroonInterceptor>>,
+    context: &GQLContext,
     media: Media,
     payment_request: String,
-) -> Result<MediaInvoice, FieldError> {
+    preimage: Option<String>,
+    client_secret: Option<String>,
+) -> Result<MediaInvoice, AppError> {
+    let connection = context.get_db_connection();
+    let lnd = context.get_lnd_client();
+    let rates = context.get_rates();
+
     // Request db to find payment
     let payment = match connection
         .run(move |c| MediaPayment::find_one_by_request(payment_request, c))
-        .await
+        .await?
     {
-        Ok(payment) => match payment {
-            Some(payment) => payment,
-            None => {
-                return Err(FieldError::new(
-                    "No payment found with the provided payment_request",
-                    Value::Null,
-                ))
-            }
-        },
-        Err(e) => {
-            return Err(FieldError::new(
-                "Error while requesting database",
-                Value::Null,
+        Some(payment) => payment,
+        None => {
+            return Err(AppError::NotFound(
+                "No payment found with the provided payment_request".to_string(),
             ))
         }
     };
 
-    // No matter any other condition, if the payment validity is considered as expired
-    // we shall return a new invoice
-    if payment.is_expired() {
-        let media_for_payment = media.clone();
-        match generate_media_payment(connection, &lnd, media_for_payment).await {
-            Ok(r) => {
-                return Ok(MediaInvoice::from((
-                    r,
-                    CustomInvoiceStateFlag::ExpiredInvoice,
-                )));
-            }
-            Err(e) => {
-                return Err(FieldError::new(
-                    "Error while requesting lightning network registry",
-                    Value::Null,
-                ));
-            }
-        };
-    }
-
     // Ensure the request media is the same that is associated in the payment
     if payment.media_uuid != media.uuid {
-        return Err(FieldError::new(
-            "payment_request does not match with the request media",
-            Value::Null,
+        return Err(AppError::BadInput(
+            "payment_request does not match with the request media".to_string(),
         ));
     }
 
-    let payment_request = payment.request.clone();
+    let paywall = Paywall::new(connection, lnd, context.get_invoice_events());
+    let status = paywall
+        .resolve(&payment, preimage.as_deref(), client_secret.as_deref())
+        .await?;
 
-    let invoice = match InvoiceUtils::get_invoice_state_from_payment_request(&lnd, payment_request)
-        .await
-    {
-        Ok(r) => match r {
-            Some(invoice) => invoice,
-            None => {
-                return Err(FieldError::new(
-                     "No invoice found with the current payment request on the lightning network service",
-         Value::Null,
-                    ));
-            }
-        },
-        Err(e) => {
-            return Err(FieldError::new(
-                "Error while requesting lightning network registry",
-                Value::Null,
-            ));
+    // Return result based on the payment status
+    match status {
+        PaymentStatus::Unlocked => proven_media_invoice(payment),
+        PaymentStatus::ProofRequired => Err(AppError::PreimageRequired),
+        // If invoice is in accepted state we return the current media invoice
+        // with the current state.
+        PaymentStatus::Held => Ok(MediaInvoice::from((payment, InvoiceState::Accepted))),
+        PaymentStatus::Open => Ok(MediaInvoice::from((payment, InvoiceState::Open))),
+        // If invoice has been canceled we generate a new one
+        PaymentStatus::Canceled => replace_media_payment(connection, lnd, rates, media).await,
+        // No matter any other condition, if the payment validity is considered as expired
+        // we shall return a new invoice
+        PaymentStatus::Expired => {
+            let (payment, secret) = generate_media_payment(connection, lnd, rates, media).await?;
+            Ok(
+                MediaInvoice::from((payment, CustomInvoiceStateFlag::ExpiredInvoice))
+                    .with_client_secret(secret),
+            )
         }
-    };
+    }
+}
 
-    // Return result based on the invoice state
-    match invoice.state() {
-        InvoiceState::Accepted => {
-            // If invoice is in accepted state we return the current media invoice
-            // with the current state.
-            Ok(MediaInvoice::from((payment, invoice.state())))
-        }
-        InvoiceState::Canceled => {
-            // If invoice has been canceled we generate a new one
-            let payment = generate_media_payment(connection, &lnd, media).await;
+/// Generates a new payment replacing a canceled one.
+/// We provide invoice state for previous invoice as
+/// this will help returning the replacementpayment output type
+async fn replace_media_payment(
+    connection: &DbConnection,
+    lnd: &LndClient,
+    rates: &RateClient,
+    media: Media,
+) -> Result<MediaInvoice, AppError> {
+    let (payment, secret) = generate_media_payment(connection, lnd, rates, media).await?;
+    Ok(MediaInvoice::from((payment, InvoiceState::Canceled)).with_client_secret(secret))
+}
 
-            // We catch the result and return the new media payment.
-            // We provide invoice state for previous invoice as
-            // this will help returning the replacementpayment output type
-            match payment {
-                Ok(payment) => Ok(MediaInvoice::from((payment, InvoiceState::Canceled))),
-                Err(error) => Err(error),
-            }
-        }
-        InvoiceState::Open => Ok(MediaInvoice::from((payment, invoice.state()))),
-        InvoiceState::Settled => Ok(MediaInvoice::from((payment, invoice.state()))),
-    }
+/// Provides the media invoice which payment has been proven
+/// along with the download token minted for the client.
+fn proven_media_invoice(payment: MediaPayment) -> Result<MediaInvoice, AppError> {
+    let token = payment.download_token().generate_token()?;
+
+    Ok(MediaInvoice::from((payment, InvoiceState::Settled)).with_download_token(token))
 }
 
 /// Method to generate a media payment
-/// With invoice registering on LND
-async fn generate_media_payment(
-    connection: &PostgresConn,
-    lnd: &LightningClient<InterceptedService<Channel, MacaroonInterceptor>>,
+/// With invoice registering on LND, along with the secret
+/// to be returned to the requesting client
+pub async fn generate_media_payment(
+    connection: &DbConnection,
+    lnd: &LndClient,
+    rates: &RateClient,
     media: Media,
-) -> Result<MediaPayment, FieldError> {
+) -> Result<(MediaPayment, ClientSecret), AppError> {
+    // The price of a media priced in a fiat currency is converted at the current rate
+    let quote = rates.quote(&media).await?;
     let memo = format!("Buy file \"{}\" with uuid: {}", media.title, media.uuid);
-    let params = InvoiceParams::new(Some(media.price as i64), Some(memo), None);
-    let invoice = InvoiceUtils::generate_invoice(lnd.clone(), params).await;
+    let params = InvoiceParams::new(Some(quote.value_msat), Some(memo), None);
+    let invoice = InvoiceUtils::generate_invoice(lnd, params).await?;
+    let secret = ClientSecret::generate();
+    let new_payment = NewMediaPayment::from((invoice, media.uuid, MediaModelType::Media(media)))
+        .with_quote(&quote)
+        .with_client_secret(&secret);
     let payment = connection
-        .run(move |c| {
-            MediaPayment::create(
-                NewMediaPayment::from((invoice, media.uuid, MediaModelType::Media(media))),
-                c,
-            )
-        })
-        .await;
-
-    match payment {
-        Ok(payment) => Ok(payment),
-        Err(_) => Err(FieldError::new(
-            "Error while registering payment request.",
-            Value::Null,
-        )),
-    }
-}
+        .run(move |c| MediaPayment::create(new_payment, c))
+        .await?;
 
-/// Method to generate a field error
-/// from a media payment
-/// Shall be deleted as not used anymore
-fn _field_error_from_media_payment(
-    media_payment: MediaPayment,
-    message: Option<String>,
-) -> FieldError {
-    let message = message.unwrap_or("Payment required".to_string());
-    let request = media_payment.request.as_str();
-    let expires_at = media_payment.expires_at.timestamp() as i32;
-    let state = media_payment.state;
-    FieldError::new(
-        message,
-        graphql_value!({
-            "state": state,
-            "paymentRequest": request,
-            "expiresAt": expires_at
-        }),
-    )
+    Ok((payment, secret))
 }
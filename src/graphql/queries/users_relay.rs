@@ -1,7 +1,8 @@
 use crate::db::models::user::User;
+use crate::errors::app::AppError;
 use crate::graphql::context::GQLContext;
+use crate::graphql::policy::Action;
 use crate::graphql::types::output::user::UserType;
-use juniper::{FieldError, FieldResult, Value};
 use juniper_relay_connection::RelayConnection;
 
 pub async fn users_relay<'a, 'b>(
@@ -10,20 +11,18 @@ pub async fn users_relay<'a, 'b>(
     after: Option<String>,
     last: Option<i32>,
     before: Option<String>,
-) -> FieldResult<RelayConnection<UserType>> {
+) -> Result<RelayConnection<UserType>, AppError> {
+    context.authorize(Action::ListUsers, None)?;
+
     let connection = context.get_db_connection();
-    let db_results = connection.run(move |c| User::find(c)).await;
+    let results = connection.run(User::find).await?;
 
-    match db_results {
-        Ok(results) => RelayConnection::new(first, after, last, before, |_, _, _| {
-            Ok(results
-                .into_iter()
-                .map(|p| UserType::from(p))
-                .collect::<Vec<UserType>>())
-        }),
-        Err(_) => Err(FieldError::new(
-            "Error while fetching the users list",
-            Value::null(),
-        )),
-    }
+    // The pagination arguments are checked by the connection
+    RelayConnection::new(first, after, last, before, |_, _, _| {
+        Ok(results
+            .into_iter()
+            .map(|p| UserType::from(p))
+            .collect::<Vec<UserType>>())
+    })
+    .map_err(|e| AppError::BadInput(e.message().to_string()))
 }
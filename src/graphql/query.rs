@@ -1,13 +1,17 @@
+use super::queries::get_bundle::{get_bundle, get_bundles_list};
 use super::queries::get_files_relay::get_files_list_relay;
 use super::queries::get_media::get_media;
+use super::queries::my_media::my_media;
+use super::queries::request_invoice_for_bundle::request_invoice_for_bundle;
 use super::queries::request_invoice_for_media::request_invoice_for_media;
 use super::queries::users_relay::users_relay;
+use super::types::output::bundle::{BundleInvoice, BundleType};
 use super::types::output::media::MediaType;
 use super::{queries::get_files_list::get_files_list, types::output::user::UserType};
 use crate::db::models::media::Media;
+use crate::errors::app::AppError;
 use crate::graphql::context::GQLContext;
 use crate::graphql::types::output::invoices::MediaInvoice;
-use juniper::{FieldError, FieldResult};
 use juniper_relay_connection::RelayConnection;
 use uuid::Uuid;
 pub struct Query;
@@ -15,7 +19,7 @@ pub struct Query;
 #[juniper::graphql_object(context = GQLContext)]
 impl Query {
     #[graphql(description = "Requests list of files")]
-    async fn get_files_list(context: &'a GQLContext) -> Result<Vec<MediaType>, FieldError> {
+    async fn get_files_list(context: &'a GQLContext) -> Result<Vec<MediaType>, AppError> {
         get_files_list(context).await
     }
 
@@ -24,28 +28,68 @@ impl Query {
         If a payment_request is provided, the query will check
         for the provided payment_request status and provide a new onee
         if necessary.
+        A new invoice comes with a client_secret. Once settled, either the client_secret
+        or the preimage of the payment_request shall be provided as a proof of payment.
     "#)]
     async fn request_invoice_for_media(
         context: &'a GQLContext,
         uuid: uuid::Uuid,
         payment_request: Option<String>,
-    ) -> Result<MediaInvoice, FieldError> {
-        request_invoice_for_media(context, uuid, payment_request).await
+        preimage: Option<String>,
+        client_secret: Option<String>,
+    ) -> Result<MediaInvoice, AppError> {
+        request_invoice_for_media(context, uuid, payment_request, preimage, client_secret).await
     }
 
-    #[graphql(description = "Gets a specific post. The query is protected through a paywall")]
+    #[graphql(description = r#"
+        Requests an invoice for a bundle, which unlocks every media of the bundle once paid.
+        If a payment_request is provided, the query will check
+        for the provided payment_request status and provide a new one
+        if necessary.
+        A new invoice comes with a client_secret. Once settled, either the client_secret
+        or the preimage of the payment_request shall be provided as a proof of payment.
+    "#)]
+    async fn request_invoice_for_bundle(
+        context: &'a GQLContext,
+        uuid: uuid::Uuid,
+        payment_request: Option<String>,
+        preimage: Option<String>,
+        client_secret: Option<String>,
+    ) -> Result<BundleInvoice, AppError> {
+        request_invoice_for_bundle(context, uuid, payment_request, preimage, client_secret).await
+    }
+
+    #[graphql(description = "Gets a specific bundle")]
+    async fn get_bundle(context: &'a GQLContext, uuid: Uuid) -> Result<BundleType, AppError> {
+        get_bundle(context, uuid).await
+    }
+
+    #[graphql(description = "Gets the list of available bundles")]
+    async fn get_bundles_list(context: &'a GQLContext) -> Result<Vec<BundleType>, AppError> {
+        get_bundles_list(context).await
+    }
+
+    #[graphql(description = r#"
+        Gets a specific media. The query is protected through a paywall :
+        a paid media is provided once its payment_request is settled, along with its download URL.
+        Otherwise a PAYMENT_REQUIRED error provides the invoice to be paid in its extensions.
+        A new invoice comes with a clientSecret extension. Once settled, either the client_secret
+        or the preimage of the payment_request shall be provided as a proof of payment.
+    "#)]
     async fn get_media<'a, 'b>(
         context: &'a GQLContext,
         uuid: Uuid,
         payment_request: Option<String>,
-    ) -> Result<MediaType, FieldError> {
-        get_media(context, uuid, payment_request).await
+        preimage: Option<String>,
+        client_secret: Option<String>,
+    ) -> Result<MediaType, AppError> {
+        get_media(context, uuid, payment_request, preimage, client_secret).await
     }
 
     #[graphql(description = "Gets the list of available medias")]
-    async fn get_medias_list(context: &'a GQLContext) -> Result<Vec<MediaType>, FieldError> {
+    async fn get_medias_list(context: &'a GQLContext) -> Result<Vec<MediaType>, AppError> {
         let connection = context.get_db_connection();
-        let db_results = connection.run(move |c| Media::find_all_published(c)).await;
+        let db_results = connection.run(Media::find_all_published).await?;
 
         Ok(db_results
             .into_iter()
@@ -53,8 +97,15 @@ impl Query {
             .collect::<Vec<MediaType>>())
     }
 
+    #[graphql(description = "Gets the media uploaded by the current user, published or not")]
+    async fn my_media(context: &'a GQLContext) -> Result<Vec<MediaType>, AppError> {
+        my_media(context).await
+    }
+
     #[graphql(description = "Gets available files with relay pagination")]
-    async fn get_files_relay(context: &'a GQLContext) -> FieldResult<RelayConnection<MediaType>> {
+    async fn get_files_relay(
+        context: &'a GQLContext,
+    ) -> Result<RelayConnection<MediaType>, AppError> {
         get_files_list_relay(context, None, None, None, None).await
     }
 
@@ -65,7 +116,7 @@ impl Query {
         after: Option<String>,
         last: Option<i32>,
         before: Option<String>,
-    ) -> FieldResult<RelayConnection<UserType>> {
+    ) -> Result<RelayConnection<UserType>, AppError> {
         users_relay(context, first, after, last, before).await
     }
 }
@@ -0,0 +1,38 @@
+use super::subscriptions::invoice_status::invoice_status;
+use super::subscriptions::media_unlocked::media_unlocked;
+use super::types::output::media::MediaType;
+use super::types::output::payment::PaymentType;
+use crate::errors::app::AppError;
+use crate::graphql::context::GQLContext;
+use futures::stream::BoxStream;
+use uuid::Uuid;
+
+pub struct Subscription;
+
+#[juniper::graphql_subscription(context = GQLContext)]
+impl Subscription {
+    #[graphql(description = r#"
+        Follows the state of a media payment request.
+        The current state is pushed first, then every change
+        until the invoice is settled or canceled.
+    "#)]
+    async fn invoice_status(
+        context: &GQLContext,
+        payment_request: String,
+    ) -> Result<BoxStream<'static, Result<PaymentType, AppError>>, AppError> {
+        invoice_status(context, payment_request).await
+    }
+
+    #[graphql(description = r#"
+        Pushes the media as soon as the provided payment request is settled.
+        The client_secret returned along with the invoice shall be provided.
+    "#)]
+    async fn media_unlocked(
+        context: &GQLContext,
+        uuid: Uuid,
+        payment_request: String,
+        client_secret: String,
+    ) -> Result<BoxStream<'static, Result<MediaType, AppError>>, AppError> {
+        media_unlocked(context, uuid, payment_request, client_secret).await
+    }
+}
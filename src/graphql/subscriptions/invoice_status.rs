@@ -0,0 +1,19 @@
+use super::watch_payment;
+use crate::errors::app::AppError;
+use crate::graphql::context::GQLContext;
+use crate::graphql::types::output::payment::PaymentType;
+use futures::stream::{BoxStream, StreamExt};
+
+/// Streams the state of a media payment.
+/// An update is pushed each time the invoice changes
+/// until it gets settled or canceled.
+pub async fn invoice_status(
+    context: &GQLContext,
+    payment_request: String,
+) -> Result<BoxStream<'static, Result<PaymentType, AppError>>, AppError> {
+    let (payment, states) = watch_payment(context, payment_request).await?;
+
+    Ok(states
+        .map(move |state| Ok(PaymentType::from((payment.clone(), state))))
+        .boxed())
+}
@@ -0,0 +1,65 @@
+use super::watch_payment;
+use crate::db::models::download_token::DownloadToken;
+use crate::db::models::media::Media;
+use crate::errors::app::AppError;
+use crate::graphql::context::GQLContext;
+use crate::graphql::types::output::media::MediaType;
+use futures::future;
+use futures::stream::{BoxStream, StreamExt};
+use tonic_lnd::rpc::invoice::InvoiceState;
+use uuid::Uuid;
+
+/// Pushes the media once the provided payment_request is settled,
+/// unlocked with the download token minted for the client.
+/// Only the client holding the secret returned along with the invoice can subscribe to it.
+/// An error is pushed instead if the invoice gets canceled.
+pub async fn media_unlocked(
+    context: &GQLContext,
+    uuid: Uuid,
+    payment_request: String,
+    client_secret: String,
+) -> Result<BoxStream<'static, Result<MediaType, AppError>>, AppError> {
+    let media = context
+        .get_db_connection()
+        .run(move |c| Media::find_one_by_uuid(uuid, c))
+        .await?
+        .ok_or_else(|| AppError::NotFound("No media found with provided uuid".to_string()))?;
+
+    let (payment, states) = watch_payment(context, payment_request).await?;
+
+    // Ensure the request media is the same that is associated in the payment
+    if payment.media_uuid != media.uuid {
+        return Err(AppError::BadInput(
+            "payment_request does not match with the request media".to_string(),
+        ));
+    }
+
+    if payment.is_expired() {
+        return Err(AppError::BadInput(
+            "The payment validity has expired".to_string(),
+        ));
+    }
+
+    if !payment.is_requested_by(Some(&client_secret)) {
+        return Err(AppError::PreimageRequired);
+    }
+
+    Ok(states
+        .filter_map(move |state| {
+            future::ready(match state {
+                InvoiceState::Settled => {
+                    let token = DownloadToken::new(&payment).generate_token();
+                    Some(
+                        token
+                            .map(|token| MediaType::unlocked(media.clone(), token))
+                            .map_err(AppError::from),
+                    )
+                }
+                InvoiceState::Canceled => Some(Err(AppError::BadInput(
+                    "The invoice has been canceled".to_string(),
+                ))),
+                _ => None,
+            })
+        })
+        .boxed())
+}
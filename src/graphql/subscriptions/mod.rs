@@ -0,0 +1,124 @@
+pub mod invoice_status;
+pub mod media_unlocked;
+
+use crate::db::models::media_payment::MediaPayment;
+use crate::db::pool::DbPool;
+use crate::db::DbConnection;
+use crate::errors::app::AppError;
+use crate::graphql::context::GQLContext;
+use crate::lnd::invoice::InvoiceUtils;
+use crate::lnd::subscriber::InvoiceEvent;
+use futures::stream::{self, BoxStream, StreamExt};
+use rocket::tokio::sync::broadcast::{error::RecvError, Receiver};
+use tonic_lnd::rpc::invoice::InvoiceState;
+
+/// Finds the payment matching the provided payment_request and provides
+/// the stream of its invoice states.
+/// The stream starts with the current state and ends once the invoice
+/// is either settled or canceled.
+pub async fn watch_payment(
+    context: &GQLContext,
+    payment_request: String,
+) -> Result<(MediaPayment, BoxStream<'static, InvoiceState>), AppError> {
+    // Subscriptions are served over WebSocket, with connections taken from the pool
+    let pool = match context.get_db_connection() {
+        DbConnection::Pool(pool) => pool.clone(),
+        DbConnection::Request(_) => return Err(AppError::InternalError),
+    };
+
+    // Listen before reading the current state so no update is missed in between
+    let receiver = context.get_invoice_events().subscribe();
+
+    let payment = context
+        .get_db_connection()
+        .run(move |c| MediaPayment::find_one_by_request(payment_request, c))
+        .await?
+        .ok_or_else(|| {
+            AppError::NotFound("No payment found with the provided payment_request".to_string())
+        })?;
+
+    let state = match InvoiceUtils::final_state(&payment.state) {
+        Some(state) => state,
+        None => context
+            .get_lnd_client()
+            .get_invoice_status(payment.request.clone())
+            .await
+            .map_err(|e| {
+                error!("Unable to get the invoice status: {}", e);
+                AppError::LnUnavailable
+            })?,
+    };
+
+    let states = payment_states(&payment, state, receiver, pool);
+
+    Ok((payment, states))
+}
+
+/// The invoice payment followed by a subscription
+struct Watcher {
+    pending: Option<InvoiceState>,
+    receiver: Receiver<InvoiceEvent>,
+    payment_request: String,
+    hash: String,
+    pool: DbPool,
+}
+
+fn payment_states(
+    payment: &MediaPayment,
+    state: InvoiceState,
+    receiver: Receiver<InvoiceEvent>,
+    pool: DbPool,
+) -> BoxStream<'static, InvoiceState> {
+    let watcher = Watcher {
+        pending: Some(state),
+        receiver,
+        payment_request: payment.request.clone(),
+        hash: payment.hash.clone(),
+        pool,
+    };
+
+    stream::unfold(Some(watcher), |watcher| async move {
+        let mut watcher = watcher?;
+
+        let state = match watcher.pending.take() {
+            Some(state) => state,
+            None => loop {
+                match watcher.receiver.recv().await {
+                    Ok(event) if event.hash == watcher.hash => break event.state,
+                    Ok(_) => continue,
+                    // The event of this invoice may have been dropped,
+                    // so its recorded state is read again before waiting
+                    Err(RecvError::Lagged(_)) => match recorded_state(&watcher).await {
+                        Some(state) => break state,
+                        None => continue,
+                    },
+                    Err(RecvError::Closed) => return None,
+                }
+            },
+        };
+
+        let next = match state {
+            InvoiceState::Settled | InvoiceState::Canceled => None,
+            _ => Some(watcher),
+        };
+
+        Some((state, next))
+    })
+    .boxed()
+}
+
+/// Provides the final state of the watched payment, once recorded in database
+async fn recorded_state(watcher: &Watcher) -> Option<InvoiceState> {
+    let payment_request = watcher.payment_request.clone();
+    match watcher
+        .pool
+        .run(move |c| MediaPayment::find_one_by_request(payment_request, c))
+        .await
+    {
+        Ok(payment) => payment.and_then(|payment| InvoiceUtils::final_state(&payment.state)),
+        Err(e) => {
+            error!("Unable to read the payment state: {}", e);
+            None
+        }
+    }
+}
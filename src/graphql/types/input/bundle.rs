@@ -0,0 +1,13 @@
+use crate::graphql::types::scalars::Msat;
+
+#[derive(Clone, GraphQLInputObject)]
+pub struct NewBundleInput {
+    pub title: String,
+    pub description: Option<String>,
+    // The price of the bundle in millisatoshis
+    pub price_msat: Msat,
+    pub payment_duration: Option<i32>,
+    pub published: bool,
+    // The media of the bundle, in the order they are provided
+    pub media_uuids: Vec<uuid::Uuid>,
+}
@@ -1,12 +1,26 @@
+use crate::graphql::types::scalars::Msat;
+
 #[derive(Clone, GraphQLInputObject)]
 pub struct FileInput {
     pub filename: String,
     pub title: String,
     pub description: Option<String>,
-    pub price: i32,
+    // The price of the media in millisatoshis
+    pub price_msat: Msat,
+    // The ISO 4217 code of the fiat currency the media is priced in, e.g: `EUR`.
+    // The price is then converted to millisatoshis at the current rate for each invoice.
+    pub price_currency: Option<String>,
+    // The price in the minor unit of the currency, e.g: cents for EUR, required along with the currency
+    pub price_amount: Option<i32>,
     pub payment_duration: Option<i32>,
     pub published: bool,
     // We expect this to be always `null` as per the spec
     // see : https://github.com/jaydenseric/graphql-multipart-request-spec
     pub file: Option<String>,
+    // The free preview of the media, e.g: a short clip or the first pages.
+    // Provided through the `<file field>.preview` multipart field.
+    pub preview: Option<String>,
+    // The thumbnail image of the media.
+    // Provided through the `<file field>.thumbnail` multipart field.
+    pub thumbnail: Option<String>,
 }
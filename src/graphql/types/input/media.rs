@@ -1,8 +1,19 @@
+use crate::graphql::types::scalars::Msat;
+
 #[derive(Clone, GraphQLInputObject)]
 pub struct EditMediaInput {
     pub title: Option<String>,
     pub description: Option<String>,
-    pub price: Option<i32>,
+    // The price of the media in millisatoshis
+    pub price_msat: Option<Msat>,
     pub published: Option<bool>,
     pub payment_duration: Option<i32>,
+    // The name the file is downloaded as, instead of the name of the uploaded file.
+    // An empty filename restores the name of the uploaded file.
+    pub filename: Option<String>,
+    // The ISO 4217 code of the fiat currency the media is priced in, e.g: `EUR`.
+    // An empty currency restores the price in millisatoshis.
+    pub price_currency: Option<String>,
+    // The price in the minor unit of the currency, e.g: cents for EUR
+    pub price_amount: Option<i32>,
 }
@@ -1,3 +1,4 @@
+pub mod bundle;
 pub mod file;
 pub mod media;
 pub mod user;
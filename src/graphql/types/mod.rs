@@ -1,2 +1,3 @@
 pub mod input;
 pub mod output;
+pub mod scalars;
@@ -0,0 +1,160 @@
+use crate::{
+    db::models::{bundle::Bundle, bundle_payment::BundlePayment, download_token::ClientSecret},
+    errors::app::AppError,
+    graphql::{
+        context::GQLContext,
+        types::{output::media::MediaType, scalars::Msat},
+    },
+};
+use chrono::NaiveDateTime;
+use tonic_lnd::rpc::invoice::InvoiceState;
+
+#[derive(Clone)]
+pub struct BundleType {
+    pub uuid: uuid::Uuid,
+    pub title: String,
+    pub description: Option<String>,
+    pub price_msat: i64,
+    pub payment_duration: Option<i32>,
+    pub published: bool,
+    pub created_at: NaiveDateTime,
+}
+
+impl From<Bundle> for BundleType {
+    fn from(item: Bundle) -> Self {
+        Self {
+            uuid: item.uuid,
+            title: item.title,
+            description: item.description,
+            price_msat: item.price_msat,
+            payment_duration: item.payment_duration,
+            published: item.published,
+            created_at: item.created_at,
+        }
+    }
+}
+
+#[graphql_object(
+    name = "Bundle",
+    description = "A priced collection of media unlocked by a single payment"
+    context = GQLContext
+)]
+impl BundleType {
+    #[graphql(description = "The bundle internal id")]
+    fn uuid(&self) -> uuid::Uuid {
+        self.uuid
+    }
+
+    #[graphql(description = "Bundle's title")]
+    fn title(&self) -> &String {
+        &self.title
+    }
+
+    #[graphql(description = "Description of bundle")]
+    fn description(&self) -> Option<&String> {
+        self.description.as_ref()
+    }
+
+    #[graphql(description = "Price of the bundle access in millisatoshis. If free is 0")]
+    fn price_msat(&self) -> Msat {
+        Msat(self.price_msat)
+    }
+
+    #[graphql(description = "The validity of a payment - in minutes - if limited")]
+    fn payment_duration(&self) -> Option<i32> {
+        self.payment_duration
+    }
+
+    #[graphql(description = "Is the bundle published")]
+    fn published(&self) -> bool {
+        self.published
+    }
+
+    #[graphql(description = "Creation date of bundle")]
+    fn created_at(&self) -> NaiveDateTime {
+        self.created_at
+    }
+
+    #[graphql(description = "The media of the bundle")]
+    async fn media<'a>(&self, context: &'a GQLContext) -> Result<Vec<MediaType>, AppError> {
+        let uuid = self.uuid;
+        let media = context
+            .get_db_connection()
+            .run(move |c| Bundle::find_media(uuid, c))
+            .await?;
+
+        Ok(media.into_iter().map(MediaType::from).collect())
+    }
+
+    #[graphql(description = "the URL to download every media of the bundle as a zip archive")]
+    fn download_url(&self) -> String {
+        format!("/bundle/{}", &self.uuid)
+    }
+}
+
+#[derive(GraphQLObject)]
+#[graphql(description = "The payment of a bundle")]
+pub struct BundleInvoice {
+    #[graphql(description = "The related bundle uuid")]
+    bundle_uuid: uuid::Uuid,
+    #[graphql(description = "The paywall ln invoice payment request string")]
+    payment_request: String,
+    #[graphql(description = "The expiry time of current invoice")]
+    expires_at: NaiveDateTime,
+    #[graphql(description = "The current state of the payment request")]
+    state: String,
+    #[graphql(description = "The secret to get the download token once settled, for new invoices")]
+    client_secret: Option<String>,
+    #[graphql(description = "The signed token that unlocks the bundle download once settled")]
+    download_token: Option<String>,
+    #[graphql(description = "The URL to download the bundle with the download token")]
+    download_url: Option<String>,
+}
+
+impl From<(BundlePayment, InvoiceState)> for BundleInvoice {
+    fn from(data: (BundlePayment, InvoiceState)) -> Self {
+        let state = match data.1 {
+            InvoiceState::Accepted => "accepted",
+            InvoiceState::Settled => "settled",
+            // A canceled invoice is replaced by a new one
+            InvoiceState::Open | InvoiceState::Canceled => "open",
+        };
+
+        Self {
+            bundle_uuid: data.0.bundle_uuid,
+            payment_request: data.0.request,
+            expires_at: data.0.expires_at,
+            state: state.to_string(),
+            client_secret: None,
+            download_token: None,
+            download_url: None,
+        }
+    }
+}
+
+impl BundleInvoice {
+    /// Flags a new invoice replacing a payment which validity is over
+    pub fn expired(self) -> Self {
+        Self {
+            state: "expired".to_string(),
+            ..self
+        }
+    }
+
+    /// Attaches the secret returned to the client requesting a new invoice
+    pub fn with_client_secret(self, secret: ClientSecret) -> Self {
+        Self {
+            client_secret: Some(secret.into_string()),
+            ..self
+        }
+    }
+
+    /// Attaches the download token to a settled payment
+    pub fn with_download_token(self, token: String) -> Self {
+        Self {
+            download_url: Some(format!("/bundle/{}?token={}", self.bundle_uuid, token)),
+            download_token: Some(token),
+            ..self
+        }
+    }
+}
@@ -2,6 +2,7 @@ use chrono::NaiveDateTime;
 use tonic_lnd::rpc::invoice::InvoiceState;
 use uuid::Uuid;
 
+use crate::db::models::download_token::ClientSecret;
 use crate::db::models::media_payment::MediaPayment;
 
 pub enum CustomInvoiceStateFlag {
@@ -18,6 +19,8 @@ pub struct ExpiredValidityPayment {
     expires_at: NaiveDateTime,
     #[graphql(description = "The current state of the new payment request")]
     state: Option<String>,
+    #[graphql(description = "The secret to get the download token once settled, for new invoices")]
+    client_secret: Option<String>,
 }
 #[derive(GraphQLObject)]
 pub struct AvailablePayment {
@@ -29,6 +32,8 @@ pub struct AvailablePayment {
     expires_at: NaiveDateTime,
     #[graphql(description = "The current state of the payment request")]
     state: Option<String>,
+    #[graphql(description = "The secret to get the download token once settled, for new invoices")]
+    client_secret: Option<String>,
 }
 
 #[derive(GraphQLObject)]
@@ -41,6 +46,8 @@ pub struct ReplacementPayment {
     expires_at: NaiveDateTime,
     #[graphql(description = "The current state of the payment request")]
     state: Option<String>,
+    #[graphql(description = "The secret to get the download token once settled, for new invoices")]
+    client_secret: Option<String>,
 }
 
 #[derive(GraphQLObject)]
@@ -51,6 +58,10 @@ pub struct SettledPayment {
     payment_request: String,
     #[graphql(description = "The current state of the payment request")]
     state: Option<String>,
+    #[graphql(description = "The signed token that unlocks the media download")]
+    download_token: Option<String>,
+    #[graphql(description = "The URL to download the media with the download token")]
+    download_url: Option<String>,
 }
 
 #[derive(GraphQLUnion)]
@@ -69,28 +80,69 @@ impl From<(MediaPayment, InvoiceState)> for MediaInvoice {
                 payment_request: data.0.request,
                 expires_at: data.0.expires_at,
                 state: Some("accepted".to_string()),
+                client_secret: None,
             }),
             InvoiceState::Open => Self::AvailablePayment(AvailablePayment {
                 media_uuid: data.0.media_uuid,
                 payment_request: data.0.request,
                 expires_at: data.0.expires_at,
                 state: Some("open".to_string()),
+                client_secret: None,
             }),
             InvoiceState::Settled => Self::SettledPayment(SettledPayment {
                 media_uuid: data.0.media_uuid,
                 payment_request: data.0.request,
                 state: Some("settled".to_string()),
+                download_token: None,
+                download_url: None,
             }),
             InvoiceState::Canceled => Self::ReplacementPayment(ReplacementPayment {
                 media_uuid: data.0.media_uuid,
                 payment_request: data.0.request,
                 expires_at: data.0.expires_at,
                 state: Some("open".to_string()),
+                client_secret: None,
             }),
         }
     }
 }
 
+impl MediaInvoice {
+    /// Attaches the download token to a settled payment
+    pub fn with_download_token(self, token: String) -> Self {
+        match self {
+            Self::SettledPayment(payment) => Self::SettledPayment(SettledPayment {
+                download_url: Some(format!("/file/{}?token={}", payment.media_uuid, token)),
+                download_token: Some(token),
+                ..payment
+            }),
+            invoice => invoice,
+        }
+    }
+
+    /// Attaches the secret returned to the client requesting a new invoice
+    pub fn with_client_secret(self, secret: ClientSecret) -> Self {
+        let client_secret = Some(secret.into_string());
+        match self {
+            Self::AvailablePayment(payment) => Self::AvailablePayment(AvailablePayment {
+                client_secret,
+                ..payment
+            }),
+            Self::ReplacementPayment(payment) => Self::ReplacementPayment(ReplacementPayment {
+                client_secret,
+                ..payment
+            }),
+            Self::ExpiredValidityPayment(payment) => {
+                Self::ExpiredValidityPayment(ExpiredValidityPayment {
+                    client_secret,
+                    ..payment
+                })
+            }
+            invoice => invoice,
+        }
+    }
+}
+
 impl From<(MediaPayment, CustomInvoiceStateFlag)> for MediaInvoice {
     fn from(data: (MediaPayment, CustomInvoiceStateFlag)) -> Self {
         match data.1 {
@@ -99,6 +151,7 @@ impl From<(MediaPayment, CustomInvoiceStateFlag)> for MediaInvoice {
                 payment_request: data.0.request,
                 expires_at: data.0.expires_at,
                 state: Some("expired".to_string()),
+                client_secret: None,
             }),
         }
     }
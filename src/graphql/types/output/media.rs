@@ -1,18 +1,19 @@
 use crate::{
-    db::models::{
-        media::Media,
-        media_payment::{MediaPayment, NewMediaPayment},
+    db::models::media::Media,
+    graphql::{
+        context::GQLContext,
+        policy::Action,
+        types::scalars::{BigInt, Msat},
     },
-    graphql::context::GQLContext,
-    lnd::invoice::{InvoiceParams, InvoiceUtils},
 };
 use base64;
 use chrono::NaiveDateTime;
-use infer::Infer;
-use juniper::Value;
-use juniper::{FieldError, FieldResult};
 use juniper_relay_connection::RelayConnectionNode;
-use std::fs::File;
+use rocket::http::ContentType;
+use std::{convert::TryFrom, path::Path};
+
+/// The number of characters of the description provided until a paid media is unlocked
+const DESCRIPTION_EXCERPT_LENGTH: usize = 140;
 
 /// To be deleted
 // #[derive(Clone, Serialize, Deserialize)]
@@ -29,75 +30,75 @@ pub struct MediaType {
     pub uuid: uuid::Uuid,
     pub title: String,
     pub description: Option<String>,
-    pub price: i32,
+    pub price_msat: i64,
+    price_currency: Option<String>,
+    price_amount: Option<i32>,
     pub published: bool,
     pub created_at: NaiveDateTime,
-    absolute_path: String,
+    checksum: Option<String>,
+    has_preview: bool,
+    has_thumbnail: bool,
+    original_filename: String,
+    filename: String,
+    mime_type: Option<String>,
+    size_bytes: Option<i64>,
+    width: Option<i32>,
+    height: Option<i32>,
+    duration: Option<f64>,
+    page_count: Option<i32>,
+    owner_uuid: Option<uuid::Uuid>,
+    free: bool,
+    // The download token minted once the media is paid
+    download_token: Option<String>,
 }
 
 impl From<Media> for MediaType {
     fn from(item: Media) -> Self {
+        let filename = item.filename().to_string();
+        let free = item.is_free();
+
         Self {
             uuid: item.uuid,
             title: item.title,
             description: item.description,
-            price: item.price,
+            price_msat: item.price_msat,
+            price_currency: item.price_currency,
+            price_amount: item.price_amount,
             published: item.published,
             created_at: item.created_at,
-            absolute_path: item.absolute_path,
+            checksum: item.checksum,
+            has_preview: item.preview_key.is_some(),
+            has_thumbnail: item.thumbnail_key.is_some(),
+            filename,
+            original_filename: item.original_filename,
+            mime_type: item.mime_type,
+            size_bytes: item.size_bytes,
+            width: item.width,
+            height: item.height,
+            duration: item.duration,
+            page_count: item.page_count,
+            owner_uuid: item.owner_uuid,
+            free,
+            download_token: None,
         }
     }
 }
 
-impl From<(Media, String)> for MediaType {
-    fn from(item: (Media, String)) -> Self {
-        let media = item.0;
-
+impl MediaType {
+    /// Provides a paid media along with the download token minted for its settled payment
+    pub fn unlocked(media: Media, download_token: String) -> Self {
         Self {
-            uuid: media.uuid,
-            title: media.title,
-            description: media.description,
-            price: media.price,
-            published: media.published,
-            created_at: media.created_at,
-            absolute_path: media.absolute_path,
+            download_token: Some(download_token),
+            ..Self::from(media)
         }
     }
-}
 
-impl MediaType {
-    // This method builds a json object with payment requirements details.
-    // The json object will be provided as an error's extension of graphql response
-    async fn _generate_invoiced_error(&self, context: &GQLContext, message: &str) -> FieldError {
-        let connection = context.get_db_connection();
-        let params = InvoiceParams::new(Some(self.price.into()), None, None);
-        let invoice =
-            InvoiceUtils::generate_invoice(context.get_lnd_client().clone(), params).await;
-        let uuid = self.uuid.clone();
-        let media_payment = connection
-            .run(move |c| MediaPayment::create(NewMediaPayment::from((invoice, uuid)), c))
-            .await;
-
-        match media_payment {
-            Ok(media_payment) => {
-                let request = media_payment.request.as_str();
-                let hash = media_payment.hash.as_str();
-
-                FieldError::new(
-                    format!("{} Use provided payment request.", message),
-                    graphql_value!({"state": "open",
-                         "payment_request": request, 
-                         "r_hash": hash}),
-                )
-            }
-            Err(_) => FieldError::new(
-                format!(
-                    "{}. An error happened while trying to generate payment request",
-                    message
-                ),
-                Value::null(),
-            ),
-        }
+    /// Free media and paid media are unlocked for everyone and the clients who paid them.
+    /// The users allowed to edit a media can see it as well.
+    fn is_unlocked(&self, context: &GQLContext) -> bool {
+        self.free
+            || self.download_token.is_some()
+            || context.is_allowed(Action::EditMedia, self.owner_uuid)
     }
 }
 
@@ -117,14 +118,40 @@ impl MediaType {
         &self.title
     }
 
-    #[graphql(description = "Description of media")]
-    fn description(&self) -> Option<&String> {
-        self.description.as_ref()
+    #[graphql(description = r#"
+        Description of media.
+        Only an excerpt is provided until a paid media is unlocked through the getMedia query.
+    "#)]
+    fn description(&self, context: &GQLContext) -> Option<String> {
+        let description = self.description.as_ref()?;
+
+        match self.is_unlocked(context) {
+            true => Some(description.clone()),
+            false => Some(excerpt(description, DESCRIPTION_EXCERPT_LENGTH)),
+        }
     }
 
-    #[graphql(description = "Price of media access in satoshis. If free is 0")]
+    #[graphql(
+        description = "Price of media access in satoshis. If free is 0",
+        deprecated = "Use `priceMsat`, the price in satoshis is rounded down to fit a 32 bits integer"
+    )]
     fn price(&self) -> i32 {
-        self.price
+        i32::try_from(self.price_msat / 1000).unwrap_or(i32::MAX)
+    }
+
+    #[graphql(description = "Price of media access in millisatoshis. If free is 0")]
+    fn price_msat(&self) -> Msat {
+        Msat(self.price_msat)
+    }
+
+    #[graphql(description = "The fiat currency the media is priced in, as an ISO 4217 code")]
+    fn price_currency(&self) -> Option<&String> {
+        self.price_currency.as_ref()
+    }
+
+    #[graphql(description = "The fiat price in the minor unit of its currency")]
+    fn price_amount(&self) -> Option<i32> {
+        self.price_amount
     }
 
     #[graphql(description = "Is the media published")]
@@ -138,36 +165,117 @@ impl MediaType {
     }
 
     #[graphql(description = "the public URL to a media")]
-    fn public_url<'a>(&self, _context: &'a GQLContext) -> FieldResult<String> {
-        let uri = format!("/file/{}", &self.uuid);
-        Ok(uri)
-    }
-    #[graphql(description = "The file type")]
-    fn file_type(&self) -> Option<&str> {
-        let info = Infer::new();
-        let kind = info.get_from_path(&self.absolute_path);
-
-        match kind {
-            Ok(result) => match result {
-                Some(t) => return Some(t.extension()),
-                None => return None,
-            },
-            Err(_) => return None,
+    fn public_url(&self) -> String {
+        format!("/file/{}", &self.uuid)
+    }
+
+    #[graphql(description = r#"
+        The URL to download the media, along with its download token for a paid media.
+        Paid media only provide it once unlocked through the getMedia query.
+    "#)]
+    fn download_url(&self) -> Option<String> {
+        match (&self.download_token, self.free) {
+            (Some(token), _) => Some(format!("/file/{}?token={}", &self.uuid, token)),
+            (None, true) => Some(format!("/file/{}", &self.uuid)),
+            (None, false) => None,
         }
     }
 
-    #[graphql(description = "The file size")]
-    fn file_size(&self) -> Option<i32> {
-        let file = File::open(&self.absolute_path);
+    #[graphql(description = "the URL to stream a media, e.g: in an audio or video player")]
+    fn stream_url(&self) -> String {
+        format!("/stream/{}", &self.uuid)
+    }
+
+    #[graphql(description = "the public URL to the free preview of a media, if any")]
+    fn preview_url(&self) -> Option<String> {
+        match self.has_preview {
+            true => Some(format!("/preview/{}", &self.uuid)),
+            false => None,
+        }
+    }
 
-        match file {
-            Ok(file) => {
-                let size = file.metadata().unwrap().len().to_string();
-                Some(size.parse::<i32>().unwrap_or(0))
-            }
-            Err(_) => None,
+    #[graphql(description = "the public URL to the thumbnail of a media, if any")]
+    fn thumbnail_url(&self) -> Option<String> {
+        match self.has_thumbnail {
+            true => Some(format!("/preview/{}/thumbnail", &self.uuid)),
+            false => None,
         }
     }
+
+    #[graphql(description = "The hex encoded SHA-256 of the file, to verify a download")]
+    fn checksum(&self) -> Option<&String> {
+        self.checksum.as_ref()
+    }
+
+    #[graphql(description = "The name of the uploaded file")]
+    fn original_filename(&self) -> &String {
+        &self.original_filename
+    }
+
+    #[graphql(description = "The name the file is downloaded as")]
+    fn filename(&self) -> &String {
+        &self.filename
+    }
+
+    #[graphql(description = "The file type, as its usual extension")]
+    fn file_type(&self) -> Option<String> {
+        let mime_type = ContentType::parse_flexible(self.mime_type.as_ref()?);
+        let extension = Path::new(&self.original_filename)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_lowercase());
+
+        // The extension of the uploaded file is kept unless it does not match the detected type
+        let is_consistent = extension.as_deref().and_then(ContentType::from_extension) == mime_type;
+        let known_extension = mime_type
+            .as_ref()
+            .and_then(|content_type| content_type.extension())
+            .map(|extension| extension.to_string().to_lowercase());
+
+        match (extension, known_extension) {
+            (Some(extension), _) if is_consistent => Some(extension),
+            (_, Some(known_extension)) => Some(known_extension),
+            (extension, None) => extension,
+        }
+    }
+
+    #[graphql(description = "The MIME type of the file")]
+    fn mime_type(&self) -> Option<&String> {
+        self.mime_type.as_ref()
+    }
+
+    #[graphql(description = "The file size, in bytes")]
+    fn file_size(&self) -> Option<BigInt> {
+        self.size_bytes.map(BigInt)
+    }
+
+    #[graphql(description = "The width of an image, in pixels")]
+    fn width(&self) -> Option<i32> {
+        self.width
+    }
+
+    #[graphql(description = "The height of an image, in pixels")]
+    fn height(&self) -> Option<i32> {
+        self.height
+    }
+
+    #[graphql(description = "The duration of an audio or video file, in seconds")]
+    fn duration(&self) -> Option<f64> {
+        self.duration
+    }
+
+    #[graphql(description = "The number of pages of a PDF document")]
+    fn page_count(&self) -> Option<i32> {
+        self.page_count
+    }
+}
+
+/// Provides the first characters of a text, ending with an ellipsis if it is truncated
+fn excerpt(text: &str, length: usize) -> String {
+    match text.char_indices().nth(length) {
+        Some((index, _)) => format!("{}…", text[..index].trim_end()),
+        None => text.to_string(),
+    }
 }
 
 /// Implements relay connection for Medias
@@ -1,3 +1,4 @@
+pub mod bundle;
 pub mod invoices;
 pub mod media;
 pub mod payment;
@@ -0,0 +1,69 @@
+use juniper::{
+    parser::{ParseError, ScalarToken, Token},
+    ParseScalarResult, ParseScalarValue, Value,
+};
+
+/// A 64 bits integer, e.g: the size of a file in bytes.
+/// Serialized as a string as GraphQL integers are limited to 32 bits.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BigInt(pub i64);
+
+#[juniper::graphql_scalar(
+    name = "BigInt",
+    description = "A 64 bits integer, serialized as a string"
+)]
+impl<S> GraphQLScalar for BigInt
+where
+    S: ScalarValue,
+{
+    fn resolve(&self) -> Value {
+        Value::scalar(self.0.to_string())
+    }
+
+    fn from_input_value(v: &InputValue) -> Option<BigInt> {
+        v.as_string_value()
+            .and_then(|value| value.parse::<i64>().ok())
+            .or_else(|| v.as_int_value().map(i64::from))
+            .map(BigInt)
+    }
+
+    fn from_str<'a>(value: ScalarToken<'a>) -> ParseScalarResult<'a, S> {
+        match value {
+            ScalarToken::String(value) => Ok(S::from(value.to_owned())),
+            ScalarToken::Int(value) => value
+                .parse::<i32>()
+                .map(S::from)
+                .or_else(|_| Ok(S::from(value.to_owned()))),
+            _ => Err(ParseError::UnexpectedToken(Token::Scalar(value))),
+        }
+    }
+}
+
+/// An amount in millisatoshis, e.g: the price of a media.
+/// Serialized as a string as GraphQL integers are limited to 32 bits.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Msat(pub i64);
+
+#[juniper::graphql_scalar(
+    name = "Msat",
+    description = "An amount in millisatoshis, serialized as a string"
+)]
+impl<S> GraphQLScalar for Msat
+where
+    S: ScalarValue,
+{
+    fn resolve(&self) -> Value {
+        Value::scalar(self.0.to_string())
+    }
+
+    fn from_input_value(v: &InputValue) -> Option<Msat> {
+        v.as_string_value()
+            .and_then(|value| value.parse::<i64>().ok())
+            .or_else(|| v.as_int_value().map(i64::from))
+            .map(Msat)
+    }
+
+    fn from_str<'a>(value: ScalarToken<'a>) -> ParseScalarResult<'a, S> {
+        <BigInt as ParseScalarValue<S>>::from_str(value)
+    }
+}
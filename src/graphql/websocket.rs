@@ -0,0 +1,298 @@
+use std::{
+    convert::TryFrom,
+    env, fmt,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    sync::Arc,
+};
+
+use futures::{future, SinkExt, StreamExt};
+use juniper::{DefaultScalarValue, Variables};
+use juniper_graphql_ws::{ArcSchema, ClientMessage, Connection, ConnectionConfig};
+use rocket::{
+    futures::future::BoxFuture,
+    tokio::net::{TcpListener, TcpStream},
+    Orbit, Rocket,
+};
+use tokio_tungstenite::tungstenite::{
+    handshake::server::{Request, Response},
+    http::{HeaderValue, StatusCode},
+    Message,
+};
+
+use super::context::GQLContext;
+use crate::{
+    app::build_schema,
+    cors::Cors,
+    db::{pool::DbPool, DbConnection},
+    lnd::{client::LndClient, subscriber::InvoiceEvents, throttle::InvoiceThrottle},
+    rates::client::RateClient,
+    storage::client::StorageClient,
+};
+
+/// The sub-protocol implemented by `juniper_graphql_ws`
+const GRAPHQL_WS_PROTOCOL: &str = "graphql-ws";
+
+/// Serves the GraphQL subscriptions over WebSocket once the server is launched.
+///
+/// Rocket does not handle connection upgrades, so the subscriptions are
+/// served on their own port (`GRAPHQL_WS_PORT`) with the `graphql-ws` protocol.
+/// The port is served without TLS, which shall be handled by a reverse proxy,
+/// and only accepts the browsers of the origin allowed by `CORS_ORIGIN_POLICY`.
+///
+/// The subscriptions are bound to the loopback address unless another one is set
+/// through `GRAPHQL_WS_ADDRESS`. Binding a public address requires `GRAPHQL_WS_PUBLIC`
+/// to be enabled, as the Rocket fairings and TLS configuration don't apply to the port.
+pub fn serve_subscriptions<'a>(rocket: &'a Rocket<Orbit>) -> BoxFuture<'a, ()> {
+    Box::pin(async move {
+        let address = match bind_address() {
+            Ok(address) => address,
+            Err(e) => {
+                error!("GraphQL subscriptions are not served: {}", e);
+                return;
+            }
+        };
+
+        let (database, lnd, storage, rates, invoice_events, invoice_throttle) = match (
+            rocket.state::<DbPool>(),
+            rocket.state::<LndClient>(),
+            rocket.state::<StorageClient>(),
+            rocket.state::<RateClient>(),
+            rocket.state::<InvoiceEvents>(),
+            rocket.state::<InvoiceThrottle>(),
+        ) {
+            (
+                Some(database),
+                Some(lnd),
+                Some(storage),
+                Some(rates),
+                Some(invoice_events),
+                Some(invoice_throttle),
+            ) => (
+                database.clone(),
+                lnd.clone(),
+                storage.clone(),
+                rates.clone(),
+                invoice_events.clone(),
+                invoice_throttle.clone(),
+            ),
+            _ => {
+                error!("GraphQL subscriptions require the database pool, lightning and storage backends, exchange rates, invoice events and throttle");
+                return;
+            }
+        };
+
+        let listener = match TcpListener::bind(address).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                error!("Unable to bind GraphQL subscriptions on {}: {}", address, e);
+                return;
+            }
+        };
+
+        info!("GraphQL subscriptions served on ws://{}", address);
+        if !address.ip().is_loopback() {
+            warn!("GraphQL subscriptions are served without TLS, use a reverse proxy to serve them through wss://");
+        }
+
+        let server = Subscriptions {
+            schema: Arc::new(build_schema()),
+            database,
+            lnd,
+            storage,
+            rates,
+            invoice_events,
+            invoice_throttle,
+        };
+
+        rocket::tokio::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((stream, peer)) => {
+                        rocket::tokio::spawn(server.clone().handle(stream, peer));
+                    }
+                    Err(e) => warn!("Unable to accept subscription connection: {}", e),
+                }
+            }
+        });
+    })
+}
+
+/// Provides the address the subscriptions are served on
+fn bind_address() -> Result<SocketAddr, String> {
+    let port = env::var("GRAPHQL_WS_PORT")
+        .ok()
+        .and_then(|port| port.parse::<u16>().ok())
+        .unwrap_or(8001);
+    let ip = match env::var("GRAPHQL_WS_ADDRESS") {
+        Ok(ip) => ip
+            .parse::<IpAddr>()
+            .map_err(|_| format!("`GRAPHQL_WS_ADDRESS` is not an IP address: {}", ip))?,
+        Err(_) => IpAddr::V4(Ipv4Addr::LOCALHOST),
+    };
+    let public = env::var("GRAPHQL_WS_PUBLIC")
+        .ok()
+        .and_then(|public| public.parse::<bool>().ok())
+        .unwrap_or(false);
+
+    check_bind_address(SocketAddr::new(ip, port), public)
+}
+
+/// Refuses the addresses other than loopback ones, unless explicitly allowed
+fn check_bind_address(address: SocketAddr, public: bool) -> Result<SocketAddr, String> {
+    match address.ip().is_loopback() || public {
+        true => Ok(address),
+        false => Err(format!(
+            "{} is not a loopback address, enable `GRAPHQL_WS_PUBLIC` to serve it",
+            address.ip()
+        )),
+    }
+}
+
+#[derive(Clone)]
+struct Subscriptions {
+    schema: Arc<crate::app::Schema>,
+    database: DbPool,
+    lnd: LndClient,
+    storage: StorageClient,
+    rates: RateClient,
+    invoice_events: InvoiceEvents,
+    invoice_throttle: InvoiceThrottle,
+}
+
+impl Subscriptions {
+    async fn handle(self, stream: TcpStream, peer: SocketAddr) {
+        let websocket = match tokio_tungstenite::accept_hdr_async(stream, negotiate).await {
+            Ok(websocket) => websocket,
+            Err(e) => {
+                warn!("WebSocket handshake failed: {}", e);
+                return;
+            }
+        };
+
+        // A connection is taken from the pool for each resolver call,
+        // so that pending subscriptions don't hold any
+        let context = GQLContext {
+            pool: DbConnection::Pool(self.database),
+            lnd: self.lnd,
+            storage: self.storage,
+            rates: self.rates,
+            files: None,
+            user: None,
+            server_config: None,
+            invoice_events: self.invoice_events,
+            invoice_throttle: self.invoice_throttle,
+            client_ip: Some(peer.ip()),
+        };
+
+        let init = move |_: Variables| async move {
+            Ok::<_, WebSocketError>(ConnectionConfig::new(context))
+        };
+
+        let (ws_tx, ws_rx) = websocket.split();
+        let (s_tx, s_rx) = Connection::new(ArcSchema(self.schema), init).split();
+
+        // Close frames end the connection, pings are answered by tungstenite itself
+        let ws_rx = ws_rx
+            .take_while(|message| future::ready(!matches!(message, Ok(Message::Close(_)))))
+            .filter(|message| {
+                future::ready(matches!(message, Ok(Message::Text(_) | Message::Binary(_))))
+            })
+            .map(|message| {
+                message
+                    .map(WebSocketMessage)
+                    .map_err(|e| WebSocketError(e.to_string()))
+            });
+
+        let s_rx = s_rx.map(|message| {
+            serde_json::to_string(&message)
+                .map(Message::Text)
+                .map_err(|e| WebSocketError(e.to_string()))
+        });
+
+        let result = future::select(
+            ws_rx.forward(s_tx.sink_map_err(|e| match e {})),
+            s_rx.forward(ws_tx.sink_map_err(|e| WebSocketError(e.to_string()))),
+        )
+        .await;
+
+        if let future::Either::Left((Err(e), _)) | future::Either::Right((Err(e), _)) = result {
+            warn!("GraphQL subscription connection closed: {}", e);
+        }
+    }
+}
+
+/// Rejects the browsers of origins not allowed by the CORS policy,
+/// then agrees on the `graphql-ws` protocol when the client requests it.
+/// Other clients don't provide any origin.
+// The signature is imposed by the tungstenite handshake callback
+#[allow(clippy::result_large_err)]
+fn negotiate(request: &Request, mut response: Response) -> Result<Response, ErrorResponse> {
+    let origin = request
+        .headers()
+        .get("Origin")
+        .map(|origin| origin.to_str().unwrap_or_default());
+
+    if let Some(origin) = origin {
+        if !Cors::allows_origin(origin) {
+            warn!("Rejected GraphQL subscription from origin {}", origin);
+            let mut rejection = ErrorResponse::new(Some("Origin not allowed".to_string()));
+            *rejection.status_mut() = StatusCode::FORBIDDEN;
+            return Err(rejection);
+        }
+    }
+
+    let requested = request
+        .headers()
+        .get_all("Sec-WebSocket-Protocol")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|protocol| protocol.trim() == GRAPHQL_WS_PROTOCOL);
+
+    if requested {
+        response.headers_mut().insert(
+            "Sec-WebSocket-Protocol",
+            HeaderValue::from_static(GRAPHQL_WS_PROTOCOL),
+        );
+    }
+
+    Ok(response)
+}
+
+type ErrorResponse = tokio_tungstenite::tungstenite::handshake::server::ErrorResponse;
+
+struct WebSocketMessage(Message);
+
+impl TryFrom<WebSocketMessage> for ClientMessage<DefaultScalarValue> {
+    type Error = serde_json::Error;
+
+    fn try_from(message: WebSocketMessage) -> Result<Self, Self::Error> {
+        serde_json::from_slice(&message.0.into_data())
+    }
+}
+
+#[derive(Debug)]
+struct WebSocketError(String);
+
+impl fmt::Display for WebSocketError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for WebSocketError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn public_addresses_require_an_explicit_opt_in() {
+        let loopback = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 8001);
+        let public = SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 8001);
+
+        assert_eq!(check_bind_address(loopback, false), Ok(loopback));
+        assert!(check_bind_address(public, false).is_err());
+        assert_eq!(check_bind_address(public, true), Ok(public));
+    }
+}
@@ -0,0 +1,21 @@
+use rocket::request::{FromRequest, Outcome};
+use rocket::Request;
+
+/// Provides the L402 credentials sent through the `Authorization` header.
+/// Credentials are verified by the route as they depend on the requested service.
+pub struct L402Authorization(pub Option<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for L402Authorization {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let authorization = request
+            .headers()
+            .get("Authorization")
+            .find(|value| value.starts_with("L402 ") || value.starts_with("LSAT "))
+            .map(|value| value.to_string());
+
+        Outcome::Success(L402Authorization(authorization))
+    }
+}
@@ -1,2 +1,5 @@
+pub mod l402;
 pub mod paymentrequestheader;
+pub mod paywall;
+pub mod tus;
 pub mod userguard;
@@ -1,4 +1,3 @@
-use diesel::result::Error;
 use rocket::{
     http::Status,
     request::{FromRequest, Outcome},
@@ -12,6 +11,9 @@ use crate::{
         models::api_payment::{ApiPayment, NewApiPayment},
         PostgresConn,
     },
+    errors::payment::PaymentError,
+    guards::l402::L402Authorization,
+    l402::{self, L402Service},
     lnd::{
         client::LndClient,
         invoice::{InvoiceParams, InvoiceUtils},
@@ -29,6 +31,16 @@ impl<'r> FromRequest<'r> for PaymentRequestHeader {
     type Error = Option<&'r str>;
 
     async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        // L402 credentials embed the proof of payment, no further check is required.
+        // Invalid credentials are answered with a new challenge.
+        if let Some(authorization) = request.guard::<L402Authorization>().await.succeeded() {
+            if let Some(authorization) = authorization.0 {
+                if l402::verify(&authorization, &L402Service::Api).is_ok() {
+                    return Outcome::Success(PaymentRequestHeader(None));
+                }
+            }
+        }
+
         let pool = request.guard::<PostgresConn>().await.succeeded();
         match pool {
             Some(conn) => {
@@ -37,7 +49,17 @@ impl<'r> FromRequest<'r> for PaymentRequestHeader {
                     Some(payment_request) => {
                         let api_payment = conn.find_api_payment(payment_request.to_string()).await;
                         match api_payment {
-                            Some(_) => outcome_from_payment_request(request, payment_request).await,
+                            Some(api_payment) => {
+                                // Rely on the state recorded in database once the invoice is settled or canceled
+                                match InvoiceUtils::final_state(&api_payment.state) {
+                                    Some(state) => {
+                                        outcome_from_invoice_state(state, payment_request)
+                                    }
+                                    None => {
+                                        outcome_from_payment_request(request, payment_request).await
+                                    }
+                                }
+                            }
                             None => Outcome::Failure((Status::PaymentRequired, None)),
                         }
                     }
@@ -66,13 +88,15 @@ impl<'r> FromRequest<'r> for PaymentRequestHeader {
 async fn request_new_api_payment(
     lnd_client: LndClient,
     db: PostgresConn,
-) -> Result<ApiPayment, Error> {
-    let client = lnd_client.0;
+) -> Result<ApiPayment, PaymentError> {
     let invoice =
-        InvoiceUtils::generate_invoice(client, InvoiceParams::new(None, None, None)).await;
+        InvoiceUtils::generate_invoice(&lnd_client, InvoiceParams::new(None, None, None)).await?;
+
+    let api_payment = db
+        .run(move |c| ApiPayment::create(NewApiPayment::from(invoice), c))
+        .await?;
 
-    db.run(move |c| ApiPayment::create(NewApiPayment::from(invoice), c))
-        .await
+    Ok(api_payment)
 }
 
 /// Creates an outcome from a payment request query to the lnd server
@@ -0,0 +1,39 @@
+use rocket::request::{FromRequest, Outcome};
+use rocket::Request;
+
+use super::l402::L402Authorization;
+
+/// Provides the credentials a client can send to access a paywalled resource:
+/// the `invoice`, `preimage` and `token` query parameters and the L402 credentials
+/// of the `Authorization` header.
+/// Credentials are verified by the route as they depend on the requested resource.
+pub struct PaywallCredentials {
+    pub invoice: Option<String>,
+    pub preimage: Option<String>,
+    pub token: Option<String>,
+    pub l402: Option<String>,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for PaywallCredentials {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let query = |name: &str| {
+            request
+                .query_value::<String>(name)
+                .and_then(|value| value.ok())
+        };
+        let l402 = match request.guard::<L402Authorization>().await {
+            Outcome::Success(authorization) => authorization.0,
+            _ => None,
+        };
+
+        Outcome::Success(PaywallCredentials {
+            invoice: query("invoice"),
+            preimage: query("preimage"),
+            token: query("token"),
+            l402,
+        })
+    }
+}
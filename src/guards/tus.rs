@@ -0,0 +1,56 @@
+use rocket::{
+    http::Status,
+    request::{FromRequest, Outcome},
+    Request,
+};
+
+/// The version of the tus protocol supported by the server
+pub const TUS_VERSION: &str = "1.0.0";
+
+/// The headers of a tus request.
+///
+/// The request fails with a `412` if the client does not use a supported
+/// version of the protocol and with a `400` if a length or offset is invalid.
+pub struct TusHeaders {
+    pub upload_length: Option<u64>,
+    pub upload_offset: Option<u64>,
+    pub upload_metadata: Option<String>,
+    pub content_type: Option<String>,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for TusHeaders {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let headers = request.headers();
+
+        if headers.get_one("Tus-Resumable") != Some(TUS_VERSION) {
+            return Outcome::Failure((Status::PreconditionFailed, ()));
+        }
+
+        let upload_length = match parse_number(headers.get_one("Upload-Length")) {
+            Ok(length) => length,
+            Err(_) => return Outcome::Failure((Status::BadRequest, ())),
+        };
+
+        let upload_offset = match parse_number(headers.get_one("Upload-Offset")) {
+            Ok(offset) => offset,
+            Err(_) => return Outcome::Failure((Status::BadRequest, ())),
+        };
+
+        Outcome::Success(TusHeaders {
+            upload_length,
+            upload_offset,
+            upload_metadata: headers.get_one("Upload-Metadata").map(str::to_string),
+            content_type: headers.get_one("Content-Type").map(str::to_string),
+        })
+    }
+}
+
+fn parse_number(value: Option<&str>) -> Result<Option<u64>, ()> {
+    match value {
+        Some(value) => value.trim().parse::<u64>().map(Some).map_err(|_| ()),
+        None => Ok(None),
+    }
+}
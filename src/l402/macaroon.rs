@@ -0,0 +1,333 @@
+use bitcoin_hashes::{hmac, sha256, Hash, HashEngine};
+
+use crate::errors::l402::L402Error;
+
+/// The key used by libmacaroons to derive the signing key from the root key
+const KEY_GENERATOR: &[u8] = b"macaroons-key-generator";
+
+const VERSION: u8 = 2;
+
+// Field types of the V2 binary format
+const FIELD_EOS: u8 = 0;
+const FIELD_LOCATION: u8 = 1;
+const FIELD_IDENTIFIER: u8 = 2;
+const FIELD_VID: u8 = 4;
+const FIELD_SIGNATURE: u8 = 6;
+
+/// A macaroon with first party caveats.
+///
+/// Macaroons are serialized with the libmacaroons V2 binary format and
+/// signed with a HMAC-SHA256 chain, so they are understood by the
+/// lightning HTTP clients that implement L402.
+#[derive(Debug, Clone)]
+pub struct Macaroon {
+    location: String,
+    identifier: Vec<u8>,
+    caveats: Vec<String>,
+    signature: [u8; 32],
+}
+
+impl Macaroon {
+    /// Mints a new macaroon signed with the provided root key
+    pub fn new(root_key: &[u8], location: &str, identifier: Vec<u8>) -> Self {
+        let signature = hmac_sha256(&Self::signing_key(root_key), &identifier);
+
+        Self {
+            location: location.to_string(),
+            identifier,
+            caveats: vec![],
+            signature,
+        }
+    }
+
+    /// Restricts the macaroon with a first party caveat
+    pub fn add_first_party_caveat(&mut self, caveat: String) {
+        self.signature = hmac_sha256(&self.signature, caveat.as_bytes());
+        self.caveats.push(caveat);
+    }
+
+    pub fn identifier(&self) -> &[u8] {
+        &self.identifier
+    }
+
+    pub fn caveats(&self) -> &[String] {
+        &self.caveats
+    }
+
+    /// Checks the macaroon has been minted with the provided root key
+    /// and that its caveats have not been tampered with.
+    pub fn verify_signature(&self, root_key: &[u8]) -> bool {
+        let mut signature = hmac_sha256(&Self::signing_key(root_key), &self.identifier);
+        for caveat in &self.caveats {
+            signature = hmac_sha256(&signature, caveat.as_bytes());
+        }
+
+        // Constant time comparison
+        signature
+            .iter()
+            .zip(self.signature.iter())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+    }
+
+    /// Serializes the macaroon as base64
+    pub fn serialize(&self) -> String {
+        let mut data = vec![VERSION];
+
+        write_field(&mut data, FIELD_LOCATION, self.location.as_bytes());
+        write_field(&mut data, FIELD_IDENTIFIER, &self.identifier);
+        data.push(FIELD_EOS);
+
+        for caveat in &self.caveats {
+            write_field(&mut data, FIELD_IDENTIFIER, caveat.as_bytes());
+            data.push(FIELD_EOS);
+        }
+        data.push(FIELD_EOS);
+
+        write_field(&mut data, FIELD_SIGNATURE, &self.signature);
+
+        base64::encode(data)
+    }
+
+    /// Deserializes a base64 encoded macaroon.
+    /// Both standard and URL safe alphabets are accepted.
+    pub fn deserialize(value: &str) -> Result<Self, L402Error> {
+        let data = base64::decode(value.trim())
+            .or_else(|_| base64::decode_config(value.trim(), base64::URL_SAFE))
+            .map_err(|e| L402Error::MalformedMacaroon(e.to_string()))?;
+
+        let mut reader = Reader {
+            data: &data,
+            position: 0,
+        };
+
+        if reader.byte()? != VERSION {
+            return Err(L402Error::MalformedMacaroon(
+                "unsupported version".to_string(),
+            ));
+        }
+
+        // Header section
+        let mut location = String::new();
+        let mut identifier = None;
+        loop {
+            match reader.field()? {
+                (FIELD_EOS, _) => break,
+                (FIELD_LOCATION, value) => location = reader.string(value)?,
+                (FIELD_IDENTIFIER, value) => identifier = Some(value.to_vec()),
+                (field, _) => return Err(unexpected_field(field)),
+            }
+        }
+
+        // Caveats section
+        let mut caveats = vec![];
+        loop {
+            let mut caveat = None;
+            loop {
+                match reader.field()? {
+                    (FIELD_EOS, _) => break,
+                    (FIELD_LOCATION, _) => {}
+                    (FIELD_IDENTIFIER, value) => caveat = Some(reader.string(value)?),
+                    // Third party caveats can't be discharged by the server
+                    (FIELD_VID, _) => {
+                        return Err(L402Error::MalformedMacaroon(
+                            "third party caveats are not supported".to_string(),
+                        ))
+                    }
+                    (field, _) => return Err(unexpected_field(field)),
+                }
+            }
+
+            match caveat {
+                Some(caveat) => caveats.push(caveat),
+                // An empty section ends the caveats
+                None => break,
+            }
+        }
+
+        let signature = match reader.field()? {
+            (FIELD_SIGNATURE, value) if value.len() == 32 => {
+                let mut signature = [0u8; 32];
+                signature.copy_from_slice(value);
+                signature
+            }
+            (field, _) => return Err(unexpected_field(field)),
+        };
+
+        Ok(Self {
+            location,
+            identifier: identifier
+                .ok_or_else(|| L402Error::MalformedMacaroon("missing identifier".to_string()))?,
+            caveats,
+            signature,
+        })
+    }
+
+    fn signing_key(root_key: &[u8]) -> [u8; 32] {
+        hmac_sha256(KEY_GENERATOR, root_key)
+    }
+}
+
+fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    let mut engine = hmac::HmacEngine::<sha256::Hash>::new(key);
+    engine.input(message);
+    hmac::Hmac::<sha256::Hash>::from_engine(engine).into_inner()
+}
+
+fn write_field(data: &mut Vec<u8>, field: u8, value: &[u8]) {
+    data.push(field);
+
+    // Length is encoded as an unsigned LEB128 varint
+    let mut length = value.len();
+    loop {
+        let byte = (length & 0x7f) as u8;
+        length >>= 7;
+        if length == 0 {
+            data.push(byte);
+            break;
+        }
+        data.push(byte | 0x80);
+    }
+
+    data.extend_from_slice(value);
+}
+
+fn unexpected_field(field: u8) -> L402Error {
+    L402Error::MalformedMacaroon(format!("unexpected field {}", field))
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn byte(&mut self) -> Result<u8, L402Error> {
+        let byte =
+            self.data.get(self.position).copied().ok_or_else(|| {
+                L402Error::MalformedMacaroon("unexpected end of data".to_string())
+            })?;
+        self.position += 1;
+        Ok(byte)
+    }
+
+    /// Reads a field type and its value.
+    /// The end of section marker has no value.
+    fn field(&mut self) -> Result<(u8, &'a [u8]), L402Error> {
+        let field = self.byte()?;
+        if field == FIELD_EOS {
+            return Ok((field, &[]));
+        }
+
+        let mut length = 0usize;
+        let mut shift = 0;
+        loop {
+            let byte = self.byte()?;
+            if shift > 28 {
+                return Err(L402Error::MalformedMacaroon(
+                    "field length overflow".to_string(),
+                ));
+            }
+            length |= ((byte & 0x7f) as usize) << shift;
+            if byte & 0x80 == 0 {
+                break;
+            }
+            shift += 7;
+        }
+
+        let end = self
+            .position
+            .checked_add(length)
+            .filter(|end| *end <= self.data.len())
+            .ok_or_else(|| L402Error::MalformedMacaroon("unexpected end of data".to_string()))?;
+
+        let value = &self.data[self.position..end];
+        self.position = end;
+        Ok((field, value))
+    }
+
+    fn string(&self, value: &[u8]) -> Result<String, L402Error> {
+        String::from_utf8(value.to_vec()).map_err(|e| L402Error::MalformedMacaroon(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The libmacaroons tutorial macaroon
+    const ROOT_KEY: &[u8] = b"this is our super secret key; only we should know it";
+
+    /// A V2 macaroon from the libmacaroons test suite, minted with `this is the key`
+    const V2_MACAROON: &str = "AgETaHR0cDovL2V4YW1wbGUub3JnLwIFa2V5aWQAAhRhY2NvdW50ID0gMzczNTkyODU1OQACDHVzZXIgPSBhbGljZQAABiBL6WfNHqDGsmuvakqU7psFsViG2guoXoxCqTyNDhJe_A";
+
+    #[test]
+    fn signs_like_libmacaroons() {
+        let mut macaroon = Macaroon::new(
+            ROOT_KEY,
+            "http://mybank/",
+            b"we used our secret key".to_vec(),
+        );
+        assert_eq!(
+            hex::encode(macaroon.signature),
+            "e3d9e02908526c4c0039ae15114115d97fdd68bf2ba379b342aaf0f617d0552f"
+        );
+
+        macaroon.add_first_party_caveat("account = 3735928559".to_string());
+        assert_eq!(
+            hex::encode(macaroon.signature),
+            "1efe4763f290dbce0c1d08477367e11f4eee456a64933cf662d79772dbb82128"
+        );
+    }
+
+    #[test]
+    fn deserializes_libmacaroons_v2() {
+        let macaroon = Macaroon::deserialize(V2_MACAROON).unwrap();
+
+        assert_eq!(macaroon.location, "http://example.org/");
+        assert_eq!(macaroon.identifier(), b"keyid");
+        assert_eq!(macaroon.caveats(), ["account = 3735928559", "user = alice"]);
+        assert!(macaroon.verify_signature(b"this is the key"));
+
+        // Serialized back to the same bytes, with the standard alphabet
+        assert_eq!(
+            base64::decode(macaroon.serialize()).unwrap(),
+            base64::decode_config(V2_MACAROON, base64::URL_SAFE_NO_PAD).unwrap()
+        );
+    }
+
+    #[test]
+    fn round_trips() {
+        let mut macaroon = Macaroon::new(ROOT_KEY, "lnfilestore", vec![0, 1, 2, 3]);
+        macaroon.add_first_party_caveat("services=media:0".to_string());
+        macaroon.add_first_party_caveat("media_valid_until=1700000000".to_string());
+
+        let deserialized = Macaroon::deserialize(&macaroon.serialize()).unwrap();
+
+        assert_eq!(deserialized.location, macaroon.location);
+        assert_eq!(deserialized.identifier(), macaroon.identifier());
+        assert_eq!(deserialized.caveats(), macaroon.caveats());
+        assert_eq!(deserialized.signature, macaroon.signature);
+        assert!(deserialized.verify_signature(ROOT_KEY));
+    }
+
+    #[test]
+    fn rejects_tampering() {
+        let mut macaroon = Macaroon::new(ROOT_KEY, "lnfilestore", vec![0, 1, 2, 3]);
+        macaroon.add_first_party_caveat("media_valid_until=1700000000".to_string());
+
+        assert!(!macaroon.verify_signature(b"another key"));
+
+        let mut tampered = macaroon.clone();
+        tampered.caveats[0] = "media_valid_until=4102444800".to_string();
+        assert!(!tampered.verify_signature(ROOT_KEY));
+
+        let mut stripped = macaroon.clone();
+        stripped.caveats.clear();
+        assert!(!stripped.verify_signature(ROOT_KEY));
+
+        let mut data = base64::decode(macaroon.serialize()).unwrap();
+        data.truncate(data.len() - 1);
+        assert!(Macaroon::deserialize(&base64::encode(data)).is_err());
+    }
+}
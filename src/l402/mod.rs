@@ -0,0 +1,295 @@
+pub mod macaroon;
+
+use std::env;
+
+use bitcoin_hashes::{sha256, Hash};
+use chrono::{NaiveDateTime, Utc};
+use rocket::http::Header;
+use uuid::Uuid;
+
+use self::macaroon::Macaroon;
+use crate::errors::l402::L402Error;
+
+/// The location stored in the minted macaroons
+const LOCATION: &str = "lnfilestore";
+
+/// Version of the macaroon identifier layout
+const IDENTIFIER_VERSION: u16 = 0;
+
+/// Default lifetime of an L402 token, in seconds
+const DEFAULT_L402_TOKEN_DURATION: i64 = 86400;
+
+/// The services that can be accessed through L402.
+/// Each service is bound to a capability that is checked
+/// along with the service when a token is verified.
+pub enum L402Service {
+    /// The paywalled GraphQL API served on `/payable`
+    Api,
+    /// A media served on `/file/<uuid>` and `/stream/<uuid>`
+    Media(Uuid),
+    /// A bundle served on `/bundle/<uuid>`
+    Bundle(Uuid),
+}
+
+impl L402Service {
+    /// The names of the services, which prefix their caveats
+    const NAMES: [&'static str; 3] = ["api", "media", "bundle"];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            L402Service::Api => "api",
+            L402Service::Media(_) => "media",
+            L402Service::Bundle(_) => "bundle",
+        }
+    }
+
+    fn capability(&self) -> String {
+        match self {
+            L402Service::Api => "graphql".to_string(),
+            L402Service::Media(uuid) | L402Service::Bundle(uuid) => uuid.to_string(),
+        }
+    }
+}
+
+/// Builds the `WWW-Authenticate` challenge for an invoice.
+///
+/// The challenge provides a macaroon bound to the invoice payment hash
+/// that will be accepted along with the invoice preimage once paid.
+/// The macaroon expires after `L402_TOKEN_DURATION` or at the provided date if sooner.
+pub fn challenge(
+    service: &L402Service,
+    payment_hash: &str,
+    payment_request: &str,
+    valid_until: Option<NaiveDateTime>,
+) -> Result<Header<'static>, L402Error> {
+    let payment_hash =
+        hex::decode(payment_hash).map_err(|e| L402Error::MalformedMacaroon(e.to_string()))?;
+
+    // version + payment hash + token id
+    let mut identifier = IDENTIFIER_VERSION.to_be_bytes().to_vec();
+    identifier.extend_from_slice(&payment_hash);
+    identifier.extend_from_slice(&sha256::Hash::hash(Uuid::new_v4().as_bytes()).into_inner());
+
+    let duration = env::var("L402_TOKEN_DURATION")
+        .ok()
+        .and_then(|duration| duration.parse::<i64>().ok())
+        .unwrap_or(DEFAULT_L402_TOKEN_DURATION);
+
+    let mut expiry = Utc::now().timestamp() + duration;
+    if let Some(valid_until) = valid_until {
+        expiry = std::cmp::min(expiry, valid_until.timestamp());
+    }
+
+    let mut macaroon = Macaroon::new(root_key()?.as_bytes(), LOCATION, identifier);
+    macaroon.add_first_party_caveat(format!("services={}:0", service.name()));
+    macaroon.add_first_party_caveat(format!(
+        "{}_capabilities={}",
+        service.name(),
+        service.capability()
+    ));
+    macaroon.add_first_party_caveat(format!("{}_valid_until={}", service.name(), expiry));
+
+    Ok(Header::new(
+        "WWW-Authenticate",
+        format!(
+            r#"L402 macaroon="{}", invoice="{}""#,
+            macaroon.serialize(),
+            payment_request
+        ),
+    ))
+}
+
+/// Checks an `Authorization: L402 <macaroon>:<preimage>` header value
+/// grants access to the provided service.
+///
+/// The former `LSAT` scheme is accepted as well.
+pub fn verify(authorization: &str, service: &L402Service) -> Result<(), L402Error> {
+    let credentials = authorization
+        .trim()
+        .strip_prefix("L402 ")
+        .or_else(|| authorization.trim().strip_prefix("LSAT "))
+        .ok_or_else(|| L402Error::MalformedAuthorization("unknown scheme".to_string()))?;
+
+    let (macaroons, preimage) = credentials
+        .trim()
+        .rsplit_once(':')
+        .ok_or_else(|| L402Error::MalformedAuthorization("missing preimage".to_string()))?;
+
+    // Only the first macaroon is relevant, no discharge macaroon is expected
+    let macaroon = Macaroon::deserialize(macaroons.split(',').next().unwrap_or_default())?;
+
+    if !macaroon.verify_signature(root_key()?.as_bytes()) {
+        return Err(L402Error::InvalidSignature);
+    }
+
+    let identifier = macaroon.identifier();
+    if identifier.len() != 66 || identifier[0..2] != IDENTIFIER_VERSION.to_be_bytes() {
+        return Err(L402Error::MalformedMacaroon(
+            "unknown identifier".to_string(),
+        ));
+    }
+
+    let preimage = hex::decode(preimage.trim())
+        .map_err(|e| L402Error::MalformedAuthorization(e.to_string()))?;
+    if sha256::Hash::hash(&preimage).into_inner()[..] != identifier[2..34] {
+        return Err(L402Error::InvalidPreimage);
+    }
+
+    for caveat in macaroon.caveats() {
+        if !is_caveat_satisfied(caveat, service) {
+            return Err(L402Error::CaveatNotSatisfied(caveat.clone()));
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks a caveat against the requested service.
+/// Caveats of the other services are skipped, unknown caveats are not satisfied.
+fn is_caveat_satisfied(caveat: &str, service: &L402Service) -> bool {
+    let (condition, value) = match caveat.split_once('=') {
+        Some((condition, value)) => (condition.trim(), value.trim()),
+        None => return false,
+    };
+
+    if condition == "services" {
+        return value
+            .split(',')
+            .filter_map(|service| service.trim().split(':').next())
+            .any(|name| name == service.name());
+    }
+
+    match condition.split_once('_') {
+        // Caveats for other services don't restrict the requested one
+        Some((name, _)) if name != service.name() => L402Service::NAMES.contains(&name),
+        Some((_, "capabilities")) => value
+            .split(',')
+            .any(|capability| capability.trim() == service.capability()),
+        Some((_, "valid_until")) => match value.parse::<i64>() {
+            Ok(valid_until) => Utc::now().timestamp() < valid_until,
+            Err(_) => false,
+        },
+        _ => false,
+    }
+}
+
+/// The key macaroons are minted with.
+/// A dedicated key is required so rotating the JWT secret doesn't affect the L402 tokens.
+fn root_key() -> Result<String, L402Error> {
+    env::var("L402_ROOT_KEY")
+        .ok()
+        .filter(|key| !key.is_empty())
+        .ok_or(L402Error::MissingRootKey)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PREIMAGE: [u8; 32] = [7; 32];
+
+    /// Mints a challenge for the test preimage and returns its macaroon
+    fn mint(service: &L402Service, valid_until: Option<NaiveDateTime>) -> String {
+        env::set_var("L402_ROOT_KEY", "test root key");
+
+        let payment_hash = sha256::Hash::hash(&PREIMAGE).into_inner();
+        let header = challenge(service, &hex::encode(payment_hash), "lnbc1", valid_until).unwrap();
+
+        header.value().split('"').nth(1).unwrap().to_string()
+    }
+
+    #[test]
+    fn verifies_minted_tokens() {
+        let uuid = Uuid::new_v4();
+        let macaroon = mint(&L402Service::Media(uuid), None);
+
+        let authorization = format!("L402 {}:{}", macaroon, hex::encode(PREIMAGE));
+        assert!(verify(&authorization, &L402Service::Media(uuid)).is_ok());
+
+        let authorization = format!("LSAT {}:{}", macaroon, hex::encode(PREIMAGE));
+        assert!(verify(&authorization, &L402Service::Media(uuid)).is_ok());
+    }
+
+    #[test]
+    fn rejects_wrong_preimage() {
+        let uuid = Uuid::new_v4();
+        let macaroon = mint(&L402Service::Media(uuid), None);
+
+        let authorization = format!("L402 {}:{}", macaroon, hex::encode([8; 32]));
+        assert!(matches!(
+            verify(&authorization, &L402Service::Media(uuid)),
+            Err(L402Error::InvalidPreimage)
+        ));
+    }
+
+    #[test]
+    fn rejects_other_services() {
+        let uuid = Uuid::new_v4();
+        let macaroon = mint(&L402Service::Media(uuid), None);
+        let authorization = format!("L402 {}:{}", macaroon, hex::encode(PREIMAGE));
+
+        assert!(matches!(
+            verify(&authorization, &L402Service::Media(Uuid::new_v4())),
+            Err(L402Error::CaveatNotSatisfied(_))
+        ));
+        assert!(matches!(
+            verify(&authorization, &L402Service::Bundle(uuid)),
+            Err(L402Error::CaveatNotSatisfied(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_expired_tokens() {
+        let uuid = Uuid::new_v4();
+        let expired = Utc::now().naive_utc() - chrono::Duration::seconds(1);
+        let macaroon = mint(&L402Service::Bundle(uuid), Some(expired));
+
+        let authorization = format!("L402 {}:{}", macaroon, hex::encode(PREIMAGE));
+        match verify(&authorization, &L402Service::Bundle(uuid)) {
+            Err(L402Error::CaveatNotSatisfied(caveat)) => {
+                assert!(caveat.starts_with("bundle_valid_until="))
+            }
+            other => panic!("expected an unsatisfied caveat, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn rejects_caveats_added_by_clients() {
+        let uuid = Uuid::new_v4();
+        let mut macaroon = Macaroon::deserialize(&mint(&L402Service::Media(uuid), None)).unwrap();
+        macaroon.add_first_party_caveat("media_valid_until=4102444800".to_string());
+
+        // Caveats added by clients only restrict the token further, unknown ones are refused
+        let authorization = format!("L402 {}:{}", macaroon.serialize(), hex::encode(PREIMAGE));
+        assert!(verify(&authorization, &L402Service::Media(uuid)).is_ok());
+
+        let mut macaroon = Macaroon::deserialize(&mint(&L402Service::Media(uuid), None)).unwrap();
+        macaroon.add_first_party_caveat("unknown=caveat".to_string());
+        let authorization = format!("L402 {}:{}", macaroon.serialize(), hex::encode(PREIMAGE));
+        assert!(matches!(
+            verify(&authorization, &L402Service::Media(uuid)),
+            Err(L402Error::CaveatNotSatisfied(_))
+        ));
+    }
+
+    #[test]
+    fn only_skips_the_caveats_of_known_services() {
+        let uuid = Uuid::new_v4();
+        let service = L402Service::Media(uuid);
+
+        assert!(is_caveat_satisfied("bundle_valid_until=0", &service));
+        assert!(is_caveat_satisfied("api_capabilities=graphql", &service));
+        assert!(!is_caveat_satisfied(
+            "unknown_valid_until=4102444800",
+            &service
+        ));
+
+        let mut macaroon = Macaroon::deserialize(&mint(&service, None)).unwrap();
+        macaroon.add_first_party_caveat("unknown_capabilities=anything".to_string());
+        let authorization = format!("L402 {}:{}", macaroon.serialize(), hex::encode(PREIMAGE));
+        assert!(matches!(
+            verify(&authorization, &service),
+            Err(L402Error::CaveatNotSatisfied(caveat)) if caveat.starts_with("unknown_")
+        ));
+    }
+}
@@ -0,0 +1,34 @@
+use futures::stream::BoxStream;
+use tonic_lnd::rpc::Invoice;
+
+use super::invoice::{InvoiceParams, LndInvoice};
+use crate::errors::lightning::LightningError;
+
+/// A stream of invoice updates as emitted by the lightning backend.
+pub type InvoiceStream = BoxStream<'static, Result<Invoice, LightningError>>;
+
+/// Provides the set of lightning network operations the paywall relies on.
+///
+/// Every component that needs to talk to a lightning node goes through
+/// this trait, which allows us to swap the LND gRPC client with
+/// any other implementation, e.g: the in-memory mock node used
+/// to run the paywall offline.
+#[rocket::async_trait]
+pub trait LightningBackend: Send + Sync {
+    /// Registers a new invoice on the node
+    async fn add_invoice(&self, params: InvoiceParams) -> Result<LndInvoice, LightningError>;
+
+    /// Looks up an invoice from its payment request.
+    /// Returns `None` if the node does not know the invoice.
+    async fn lookup_invoice(
+        &self,
+        payment_request: &str,
+    ) -> Result<Option<Invoice>, LightningError>;
+
+    /// Subscribes to the invoices updates of the node.
+    /// Invoices settled after the provided `settle_index` are replayed first.
+    async fn subscribe_invoices(&self, settle_index: u64) -> Result<InvoiceStream, LightningError>;
+
+    /// Ensures the backend is reachable and the credentials are valid
+    async fn health_check(&self) -> Result<(), LightningError>;
+}
@@ -1,4 +1,4 @@
-use std::env;
+use std::sync::Arc;
 
 use rocket::{
     http::Status,
@@ -7,50 +7,32 @@ use rocket::{
 };
 use tonic_lnd::rpc::invoice::InvoiceState;
 
-use super::invoice::InvoiceUtils;
-extern crate dotenv;
+use super::{backend::LightningBackend, invoice::InvoiceUtils};
 
-pub struct LndClient(pub tonic_lnd::Client);
+/// A handle to the lightning backend, shared by the routes,
+/// the GraphQL resolvers and the invoices subscriber.
+#[derive(Clone)]
+pub struct LndClient(pub Arc<dyn LightningBackend>);
+
+impl<B: LightningBackend + 'static> From<B> for LndClient {
+    fn from(backend: B) -> Self {
+        LndClient(Arc::new(backend))
+    }
+}
 
 /*
-The below implementation allows us to start the Lnd client instance that will
+The below implementation allows us to retrieve the Lnd client instance that will
 be later used in a request process by being injected in context object
 */
 #[rocket::async_trait]
 impl<'r> FromRequest<'r> for LndClient {
     type Error = ();
 
-    async fn from_request(_request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
-        let address = env::var("LND_ADDRESS");
-
-        if address.is_err() {
-            println!("missing address for lnd");
-            return Outcome::Failure((Status::ServiceUnavailable, ()));
-        }
-
-        let address = address.unwrap();
-
-        let cert_file = env::var("LND_CERTFILE_PATH");
-
-        if cert_file.is_err() {
-            println!("cert failure for lnd");
-            return Outcome::Failure((Status::ServiceUnavailable, ()));
-        }
-
-        let macaroon_file = env::var("LND_MACAROON_PATH");
-
-        if macaroon_file.is_err() {
-            println!("macaroon failure");
-            return Outcome::Failure((Status::ServiceUnavailable, ()));
-        }
-
-        let client = tonic_lnd::connect(address, cert_file.unwrap(), macaroon_file.unwrap()).await;
-
-        match client {
-            Ok(result) => Outcome::Success(LndClient(result)),
-            Err(e) => {
-                println!("Error while connecting to LND server");
-                println!("{}", e);
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match request.rocket().state::<LndClient>() {
+            Some(client) => Outcome::Success(client.clone()),
+            None => {
+                error!("No lightning backend is managed by the server");
                 Outcome::Failure((Status::ServiceUnavailable, ()))
             }
         }
@@ -59,10 +41,10 @@ impl<'r> FromRequest<'r> for LndClient {
 
 impl LndClient {
     pub async fn get_invoice_status(
-        self,
-        payment_hash: String,
+        &self,
+        payment_request: String,
     ) -> Result<InvoiceState, &'static str> {
-        match InvoiceUtils::get_invoice_state_from_payment_request(&self.0, payment_hash).await {
+        match InvoiceUtils::get_invoice_state_from_payment_request(self, payment_request).await {
             Ok(invoice_result) => match invoice_result {
                 Some(invoice) => Ok(invoice.state()),
                 None => Err("No invoice found"),
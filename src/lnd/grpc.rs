@@ -0,0 +1,281 @@
+#![allow(deprecated)]
+use std::{future::Future, time::Duration};
+
+use futures::StreamExt;
+use lightning_invoice::SignedRawInvoice;
+use rocket::tokio::{
+    sync::{Mutex, RwLock},
+    time::sleep,
+};
+use tonic::Code;
+use tonic_lnd::rpc::{Invoice, InvoiceSubscription, ListInvoiceRequest, PaymentHash};
+
+use super::{
+    backend::{InvoiceStream, LightningBackend},
+    invoice::{InvoiceParams, LndInvoice},
+};
+use crate::errors::lightning::LightningError;
+
+/// Credentials used to reach the LND node
+#[derive(Clone, Debug)]
+pub struct GrpcConfig {
+    pub address: String,
+    pub cert_file: String,
+    pub macaroon_file: String,
+    pub max_reconnect_attempts: u32,
+}
+
+/// Lightning backend relying on a LND node reached through gRPC.
+///
+/// A single client is shared by every request. The client is
+/// replaced with a fresh connection whenever the gRPC channel
+/// is considered as dropped.
+pub struct GrpcBackend {
+    config: GrpcConfig,
+    connection: RwLock<Connection>,
+    // Ensures a single reconnection happens at a time
+    reconnecting: Mutex<()>,
+}
+
+/// The shared client, along with the count of connections opened before it
+#[derive(Clone)]
+struct Connection {
+    client: tonic_lnd::Client,
+    generation: u64,
+}
+
+impl GrpcBackend {
+    /// Connects to the LND node with the provided credentials
+    pub async fn connect(config: GrpcConfig) -> Result<Self, LightningError> {
+        let client = Self::open(&config).await?;
+
+        Ok(Self {
+            config,
+            connection: RwLock::new(Connection {
+                client,
+                generation: 0,
+            }),
+            reconnecting: Mutex::new(()),
+        })
+    }
+
+    async fn open(config: &GrpcConfig) -> Result<tonic_lnd::Client, LightningError> {
+        tonic_lnd::connect(
+            config.address.clone(),
+            config.cert_file.clone(),
+            config.macaroon_file.clone(),
+        )
+        .await
+        .map_err(|e| LightningError::ConnectionError(e.to_string()))
+    }
+
+    async fn connection(&self) -> Connection {
+        self.connection.read().await.clone()
+    }
+
+    /// Replaces the shared client of the provided generation with a new connection.
+    /// Attempts are spaced with an exponential backoff.
+    ///
+    /// Callers queue while a reconnection is in progress,
+    /// the client is only replaced once if it is still the failing one.
+    async fn reconnect(&self, generation: u64) -> Result<(), LightningError> {
+        let _guard = self.reconnecting.lock().await;
+        if self.connection.read().await.generation != generation {
+            return Ok(());
+        }
+
+        let mut delay = Duration::from_millis(500);
+        let mut attempt = 0;
+
+        loop {
+            attempt += 1;
+
+            match Self::open(&self.config).await {
+                Ok(client) => {
+                    *self.connection.write().await = Connection {
+                        client,
+                        generation: generation + 1,
+                    };
+                    info!("Reconnected to LND node after {} attempt(s)", attempt);
+                    return Ok(());
+                }
+                Err(e) => {
+                    warn!("LND reconnection attempt {} failed: {}", attempt, e);
+
+                    if attempt >= self.config.max_reconnect_attempts {
+                        return Err(e);
+                    }
+
+                    sleep(delay).await;
+                    delay = std::cmp::min(delay * 2, Duration::from_secs(30));
+                }
+            }
+        }
+    }
+
+    /// Performs a call with the shared client.
+    /// If the call fails because the channel dropped, the client
+    /// is reconnected and the call is attempted a second time.
+    async fn call<T, F, Fut>(&self, f: F) -> Result<T, tonic::Status>
+    where
+        F: Fn(tonic_lnd::Client) -> Fut,
+        Fut: Future<Output = Result<T, tonic::Status>>,
+    {
+        let connection = self.connection().await;
+
+        match f(connection.client).await {
+            Err(status) if Self::is_connection_error(&status) => {
+                if let Err(e) = self.reconnect(connection.generation).await {
+                    return Err(tonic::Status::unavailable(e.to_string()));
+                }
+                f(self.connection().await.client).await
+            }
+            result => result,
+        }
+    }
+
+    fn is_connection_error(status: &tonic::Status) -> bool {
+        matches!(status.code(), Code::Unavailable | Code::Cancelled)
+            || (status.code() == Code::Unknown && status.message().contains("transport error"))
+    }
+}
+
+#[rocket::async_trait]
+impl LightningBackend for GrpcBackend {
+    async fn add_invoice(&self, params: InvoiceParams) -> Result<LndInvoice, LightningError> {
+        let invoice = Invoice {
+            memo: params.memo,
+            value_msat: params.value_msat,
+            expiry: params.expiry,
+            ..Invoice::default()
+        };
+
+        let result = self
+            .call(|mut client| {
+                let invoice = invoice.clone();
+                async move { client.add_invoice(invoice).await }
+            })
+            .await
+            .map_err(|status| LightningError::InvoiceError(status.message().to_string()))?
+            .into_inner();
+
+        // Retrieve the payment hash based on r_hash returned from the AddInvoiceResponse
+        let payment_hash = PaymentHash {
+            r_hash: result.r_hash.clone(),
+            r_hash_str: hex::encode(result.r_hash.clone()), // provided as request by the Struct but not used and deprecated
+        };
+
+        // Get the Invoice detail so we can return the payment_request
+        let invoice = self
+            .call(|mut client| {
+                let payment_hash = payment_hash.clone();
+                async move { client.lookup_invoice(payment_hash).await }
+            })
+            .await
+            .map_err(|status| LightningError::InvoiceError(status.message().to_string()))?
+            .into_inner();
+
+        Ok(LndInvoice::new(invoice, hex::encode(result.r_hash)))
+    }
+
+    //    Gets the invoice from a payment request string.
+    //    It consists as a two steps method.
+    async fn lookup_invoice(
+        &self,
+        payment_request: &str,
+    ) -> Result<Option<Invoice>, LightningError> {
+        // Parse the payment request
+        let invoice = payment_request
+            .parse::<SignedRawInvoice>()
+            .map_err(|e| LightningError::MalformedPaymentRequest(e.to_string()))?;
+
+        // Get the payment hash
+        let p_hash = match invoice.payment_hash() {
+            Some(p_hash) => p_hash,
+            None => {
+                return Err(LightningError::MalformedPaymentRequest(
+                    "missing payment hash".to_string(),
+                ))
+            }
+        };
+
+        /*
+            The below instruction might seems a bit odd.
+            the expected r_hash here is not the Invoice r_hash
+            but rather the r_hash of the payment request which is
+            denominated in the SignedRawInvoice as the payment_hash.
+        */
+        let payment_hash = PaymentHash {
+            r_hash: p_hash.0.to_vec(),
+            ..PaymentHash::default()
+        };
+
+        let response = self
+            .call(|mut client| {
+                let payment_hash = payment_hash.clone();
+                async move { client.lookup_invoice(payment_hash).await }
+            })
+            .await;
+
+        match response {
+            Ok(response) => Ok(Some(response.into_inner())),
+            Err(status) => {
+                if status.code() == Code::Unknown
+                    && (status.message() == "there are no existing invoices"
+                        || status.message() == "unable to locate invoice")
+                {
+                    Ok(None)
+                } else {
+                    Err(LightningError::InvoiceError(status.message().to_string()))
+                }
+            }
+        }
+    }
+
+    async fn subscribe_invoices(&self, settle_index: u64) -> Result<InvoiceStream, LightningError> {
+        let subscription = InvoiceSubscription {
+            settle_index,
+            ..InvoiceSubscription::default()
+        };
+
+        let stream = self
+            .call(|mut client| {
+                let subscription = subscription.clone();
+                async move { client.subscribe_invoices(subscription).await }
+            })
+            .await
+            .map_err(|status| LightningError::ConnectionError(status.message().to_string()))?
+            .into_inner();
+
+        Ok(stream
+            .map(|item| {
+                item.map_err(|status| LightningError::ConnectionError(status.message().to_string()))
+            })
+            .boxed())
+    }
+
+    // Lists a single invoice as the invoice macaroon
+    // does not grant access to the node info.
+    async fn health_check(&self) -> Result<(), LightningError> {
+        let request = ListInvoiceRequest {
+            num_max_invoices: 1,
+            reversed: true,
+            ..ListInvoiceRequest::default()
+        };
+
+        let result = self
+            .call(|mut client| {
+                let request = request.clone();
+                async move { client.list_invoices(request).await }
+            })
+            .await;
+
+        match result {
+            Ok(_) => Ok(()),
+            Err(status) if GrpcBackend::is_connection_error(&status) => Err(
+                LightningError::ConnectionError(status.message().to_string()),
+            ),
+            Err(status) => Err(LightningError::InvoiceError(status.message().to_string())),
+        }
+    }
+}
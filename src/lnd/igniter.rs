@@ -0,0 +1,115 @@
+use std::{env, sync::Arc, time::Duration};
+
+use rocket::{futures::future::BoxFuture, tokio::time::interval, Build, Orbit, Rocket};
+
+use super::{
+    backend::LightningBackend,
+    client::LndClient,
+    grpc::{GrpcBackend, GrpcConfig},
+    mock::MockBackend,
+};
+use crate::routes::mock::{cancel_invoice, expire_invoice, settle_invoice};
+
+// Creates the lightning backend shared by every request on ignite of the server.
+// If `LND_BACKEND` env var is set to `mock`, an in-memory lightning node
+// is managed by the server instead of connecting to the LND node.
+// The mock node can then be driven through the `/mock/invoice/*` routes.
+pub async fn setup_lightning_backend(
+    rocket: Rocket<Build>,
+) -> Result<Rocket<Build>, Rocket<Build>> {
+    let backend = env::var("LND_BACKEND").unwrap_or("lnd".to_string());
+
+    match backend.as_str() {
+        "mock" => {
+            warn!("Using the in-memory mock lightning backend. Payments are not real.");
+            let mock = Arc::new(MockBackend::new());
+            let client = LndClient(mock.clone() as Arc<dyn LightningBackend>);
+
+            Ok(rocket
+                .manage(client)
+                .manage(mock)
+                .mount("/", routes![settle_invoice, cancel_invoice, expire_invoice]))
+        }
+        _ => {
+            let config = match grpc_config() {
+                Ok(config) => config,
+                Err(variable) => {
+                    error!(
+                        "Missing `{}` environment variable to connect to the LND node",
+                        variable
+                    );
+                    return Err(rocket);
+                }
+            };
+
+            let address = config.address.clone();
+
+            let backend = match GrpcBackend::connect(config).await {
+                Ok(backend) => backend,
+                Err(e) => {
+                    error!("Unable to connect to the LND node at {}: {}", address, e);
+                    error!("Ensure the `LND_CERTFILE_PATH` and `LND_MACAROON_PATH` are valid");
+                    return Err(rocket);
+                }
+            };
+
+            // A channel can be opened with wrong credentials,
+            // so we ensure the node accepts our requests
+            if let Err(e) = backend.health_check().await {
+                error!("The LND node at {} rejected the server: {}", address, e);
+                error!("Ensure the macaroon grants read/write access on invoices");
+                return Err(rocket);
+            }
+
+            info!("Connected to the LND node at {}", address);
+
+            Ok(rocket.manage(LndClient::from(backend)))
+        }
+    }
+}
+
+// Periodically checks the lightning backend health once the server is launched.
+// The check interval - in seconds - can be set through `LND_HEALTH_CHECK_INTERVAL`.
+pub fn monitor_lightning_backend<'a>(rocket: &'a Rocket<Orbit>) -> BoxFuture<'a, ()> {
+    Box::pin(async move {
+        let client = match rocket.state::<LndClient>() {
+            Some(client) => client.clone(),
+            None => return,
+        };
+
+        let seconds = env::var("LND_HEALTH_CHECK_INTERVAL")
+            .unwrap_or("30".to_string())
+            .parse::<u64>()
+            .unwrap_or(30);
+
+        rocket::tokio::spawn(async move {
+            let mut ticker = interval(Duration::from_secs(seconds));
+
+            loop {
+                ticker.tick().await;
+
+                if let Err(e) = client.0.health_check().await {
+                    error!("Lightning backend health check failed: {}", e);
+                }
+            }
+        });
+    })
+}
+
+fn grpc_config() -> Result<GrpcConfig, &'static str> {
+    let address = env::var("LND_ADDRESS").map_err(|_| "LND_ADDRESS")?;
+    let cert_file = env::var("LND_CERTFILE_PATH").map_err(|_| "LND_CERTFILE_PATH")?;
+    let macaroon_file = env::var("LND_MACAROON_PATH").map_err(|_| "LND_MACAROON_PATH")?;
+
+    let max_reconnect_attempts = env::var("LND_RECONNECT_MAX_ATTEMPTS")
+        .unwrap_or("5".to_string())
+        .parse::<u32>()
+        .unwrap_or(5);
+
+    Ok(GrpcConfig {
+        address,
+        cert_file,
+        macaroon_file,
+        max_reconnect_attempts,
+    })
+}
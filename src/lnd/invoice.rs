@@ -1,34 +1,76 @@
-#![allow(deprecated)]
-use chrono::{Duration, NaiveDateTime, Utc};
+use bitcoin_hashes::{sha256, Hash};
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use serde::Serialize;
 use std::env;
-use tonic::transport::Channel;
-use tonic::{Code, Status};
 use tonic_lnd::rpc::invoice::InvoiceState;
-use tonic_lnd::rpc::lightning_client::LightningClient;
-use tonic_lnd::rpc::{Invoice, PaymentHash};
-use tonic_lnd::tonic::codegen::InterceptedService;
-use tonic_lnd::MacaroonInterceptor;
+use tonic_lnd::rpc::Invoice;
 
-use lightning_invoice::*;
+use super::client::LndClient;
+use crate::db::models::download_token::ClientSecret;
+use crate::errors::lightning::LightningError;
 extern crate dotenv;
 
+/// Default value - in millisatoshis - of an invoice
+const DEFAULT_INVOICE_VALUE_MSAT: i64 = 100_000;
+
+/// Default lifetime - in seconds - of an invoice
+const DEFAULT_INVOICE_EXPIRY: i64 = 600;
+
 pub struct InvoiceParams {
-    pub value: i64,
+    pub value_msat: i64,
     pub memo: String,
     pub expiry: i64,
 }
 
 impl InvoiceParams {
-    pub fn new(value: Option<i64>, memo: Option<String>, expiry: Option<i64>) -> Self {
-        let default_value = env::var("DEFAULT_INVOICE_VALUE").unwrap_or("100".to_string());
-        let default_expiry = env::var("DEFAULT_INVOICE_EXPIRY").unwrap_or("600".to_string());
+    /// Provides the parameters of an invoice, the value being in millisatoshis.
+    /// Missing parameters are read from the environment.
+    pub fn new(value_msat: Option<i64>, memo: Option<String>, expiry: Option<i64>) -> Self {
         let default_memo = env::var("DEFAULT_INVOICE_MEMO").unwrap_or("API Payment".to_string());
 
         Self {
-            value: value.unwrap_or_else(|| default_value.parse::<i64>().unwrap()),
+            value_msat: value_msat.unwrap_or_else(default_value_msat),
             memo: memo.unwrap_or_else(|| default_memo),
-            expiry: expiry.unwrap_or_else(|| default_expiry.parse::<i64>().unwrap()),
+            expiry: expiry.unwrap_or_else(|| {
+                env_or_default("DEFAULT_INVOICE_EXPIRY", DEFAULT_INVOICE_EXPIRY)
+            }),
+        }
+    }
+}
+
+/// The default value of an invoice, in millisatoshis.
+/// The former `DEFAULT_INVOICE_VALUE` in satoshis is still read if no value in millisatoshis is set.
+fn default_value_msat() -> i64 {
+    match (
+        env::var("DEFAULT_INVOICE_VALUE_MSAT"),
+        env::var("DEFAULT_INVOICE_VALUE"),
+    ) {
+        (Err(_), Ok(_)) => {
+            warn!(
+                "`DEFAULT_INVOICE_VALUE` is deprecated, use `DEFAULT_INVOICE_VALUE_MSAT` instead"
+            );
+            env_or_default("DEFAULT_INVOICE_VALUE", DEFAULT_INVOICE_VALUE_MSAT / 1000)
+                .saturating_mul(1000)
         }
+        _ => env_or_default("DEFAULT_INVOICE_VALUE_MSAT", DEFAULT_INVOICE_VALUE_MSAT),
+    }
+}
+
+/// Reads a positive integer from the environment.
+/// An invalid value is reported and replaced by the default one.
+fn env_or_default(key: &str, default: i64) -> i64 {
+    match env::var(key) {
+        Ok(value) => match value.trim().parse::<i64>() {
+            Ok(value) if value > 0 => value,
+            _ => {
+                warn!(
+                    "Invalid `{}` environment variable, using {} instead",
+                    key, default
+                );
+                default
+            }
+        },
+        Err(_) => default,
     }
 }
 
@@ -41,7 +83,7 @@ impl InvoiceParams {
 pub struct LndInvoice {
     pub memo: String,
     pub payment_request: String,
-    pub value: i64,
+    pub value_msat: i64,
     pub r_hash: String,
     pub expires_at: NaiveDateTime,
     pub state: InvoiceState,
@@ -60,7 +102,7 @@ impl LndInvoice {
         Self {
             payment_request: invoice.payment_request,
             memo: invoice.memo,
-            value: invoice.value as i64,
+            value_msat: invoice.value_msat,
             r_hash: r_hash,
             expires_at: expires_at.naive_utc(),
             state: state,
@@ -68,6 +110,40 @@ impl LndInvoice {
     }
 }
 
+/// The invoice to be paid to access a resource
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct InvoiceDetails {
+    pub payment_request: String,
+    pub hash: String,
+    /// The amount of the invoice in millisatoshis
+    pub amount: Option<i64>,
+    pub expires_at: DateTime<Utc>,
+    /// The secret proving the invoice has been requested by the client, only
+    /// provided along with a new invoice
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_secret: Option<String>,
+}
+
+impl InvoiceDetails {
+    pub fn new(payment_request: &str, hash: &str, expires_at: NaiveDateTime) -> Self {
+        Self {
+            payment_request: payment_request.to_string(),
+            hash: hash.to_string(),
+            amount: InvoiceUtils::amount_msat(payment_request),
+            expires_at: DateTime::from_utc(expires_at, Utc),
+            client_secret: None,
+        }
+    }
+
+    /// Provides the secret returned to the client requesting the invoice
+    pub fn with_client_secret(self, secret: ClientSecret) -> Self {
+        Self {
+            client_secret: Some(secret.into_string()),
+            ..self
+        }
+    }
+}
+
 /**
  * Provides with the utilities method required to build the Lightning Network
  * Paywall on top of the Juniper GraphQL API.
@@ -76,77 +152,128 @@ pub struct InvoiceUtils {}
 
 impl InvoiceUtils {
     /**
-       Generate an invoice through lnd
+       Generate an invoice through the lightning backend
     */
     pub async fn generate_invoice(
-        mut lnd_client: LightningClient<InterceptedService<Channel, MacaroonInterceptor>>,
+        lnd_client: &LndClient,
         params: InvoiceParams,
-    ) -> LndInvoice {
-        let add_invoice_response = lnd_client.add_invoice(tonic_lnd::rpc::Invoice {
-            memo: params.memo,
-            value: params.value,
-            expiry: params.expiry,
-            ..tonic_lnd::rpc::Invoice::default()
-        });
+    ) -> Result<LndInvoice, LightningError> {
+        lnd_client.0.add_invoice(params).await
+    }
 
-        let result = add_invoice_response.await.unwrap().into_inner();
+    /// Provides the label used to store an invoice state in database
+    pub fn state_label(state: InvoiceState) -> &'static str {
+        match state {
+            InvoiceState::Open => "open",
+            InvoiceState::Settled => "settled",
+            InvoiceState::Canceled => "canceled",
+            InvoiceState::Accepted => "accepted",
+        }
+    }
+
+    /// Provides the final invoice state recorded in database, if any.
+    /// Open and accepted invoices can still change, so their state
+    /// must be checked against the lightning backend.
+    pub fn final_state(label: &Option<String>) -> Option<InvoiceState> {
+        match label.as_deref() {
+            Some("settled") => Some(InvoiceState::Settled),
+            Some("canceled") => Some(InvoiceState::Canceled),
+            _ => None,
+        }
+    }
+
+    /// Provides the state of an invoice which payment is recorded with the provided state label.
+    /// A valid preimage proves the payment, so the invoice is settled whatever the recorded state.
+    /// Otherwise only the final states are provided, the others must be checked
+    /// against the lightning backend.
+    pub fn resolve_state(label: &Option<String>, proven: bool) -> Option<InvoiceState> {
+        match proven {
+            true => Some(InvoiceState::Settled),
+            false => Self::final_state(label),
+        }
+    }
 
-        // Retrieve the payment hash based on r_hash returned from the AddInvoiceResponse
-        let payment_hash = tonic_lnd::rpc::PaymentHash {
-            r_hash: result.r_hash.clone(),
-            r_hash_str: hex::encode(result.r_hash.clone()), // provided as request by the Struct but not used and deprecated
-        };
+    /// Checks the provided hex encoded preimage is the one of the payment hash.
+    /// As the preimage is only revealed to the payer once the invoice is
+    /// settled, it provides a proof of payment.
+    pub fn is_preimage_valid(preimage: &str, payment_hash: &str) -> bool {
+        match hex::decode(preimage.trim()) {
+            Ok(preimage) => {
+                sha256::Hash::hash(&preimage).to_string() == payment_hash.to_lowercase()
+            }
+            Err(_) => false,
+        }
+    }
 
-        // Get the Invoice detail so we can return the payment_request
-        let invoice = lnd_client
-            .lookup_invoice(payment_hash)
-            .await
-            .unwrap()
-            .into_inner();
+    /// Provides the amount - in millisatoshis - requested by a payment request,
+    /// if it can be decoded and requests a specific amount
+    pub fn amount_msat(payment_request: &str) -> Option<i64> {
+        let invoice = payment_request.parse::<lightning_invoice::Invoice>().ok()?;
 
-        LndInvoice::new(invoice, hex::encode(result.r_hash))
+        invoice.amount_milli_satoshis().map(|amount| amount as i64)
     }
 
     //    Gets the invoice state from a payment request string.
-    //    It consists as a two steps method.
     pub async fn get_invoice_state_from_payment_request<'a>(
-        lnd_client: &LightningClient<InterceptedService<Channel, MacaroonInterceptor>>,
+        lnd_client: &LndClient,
         payment_request: String,
-    ) -> Result<Option<Invoice>, Status> {
-        let mut client = lnd_client.clone();
+    ) -> Result<Option<Invoice>, LightningError> {
+        lnd_client.0.lookup_invoice(payment_request.as_str()).await
+    }
+}
 
-        // Parse the payment request
-        let invoice = payment_request
-            .as_str()
-            .parse::<SignedRawInvoice>()
-            .unwrap();
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        // Get the payment hash
-        let p_hash = invoice.payment_hash().unwrap();
-
-        /*
-            The below instruction might seems a bit odd.
-            the expected r_hash here is not the Invoice r_hash
-            but rather the r_hash of the payment request which is
-            denominated in the SignedRawInvoice as the payment_hash.
-        */
-        let request = tonic::Request::new(PaymentHash {
-            r_hash: p_hash.0.to_vec(),
-            ..PaymentHash::default()
-        });
-
-        match client.lookup_invoice(request).await {
-            Ok(response) => Ok(Some(response.into_inner())),
-            Err(status) => {
-                if status.code() == Code::Unknown
-                    && (status.message() == "there are no existing invoices"
-                        || status.message() == "unable to locate invoice")
-                {
-                    Ok(None)
-                } else {
-                    Err(status)
-                }
-            }
+    const PREIMAGE: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+    const PAYMENT_HASH: &str = "66687aadf862bd776c8fc18b8e9f8e20089714856ee233b3902a591d0d5f2925";
+
+    #[test]
+    fn the_preimage_of_the_payment_hash_is_valid() {
+        assert!(InvoiceUtils::is_preimage_valid(PREIMAGE, PAYMENT_HASH));
+        assert!(InvoiceUtils::is_preimage_valid(
+            &format!(" {} ", PREIMAGE),
+            &PAYMENT_HASH.to_uppercase()
+        ));
+    }
+
+    #[test]
+    fn other_preimages_are_invalid() {
+        let other = "0101010101010101010101010101010101010101010101010101010101010101";
+
+        assert!(!InvoiceUtils::is_preimage_valid(other, PAYMENT_HASH));
+        // the payment hash itself is not a proof of payment
+        assert!(!InvoiceUtils::is_preimage_valid(PAYMENT_HASH, PAYMENT_HASH));
+        assert!(!InvoiceUtils::is_preimage_valid("", PAYMENT_HASH));
+        assert!(!InvoiceUtils::is_preimage_valid("not hex", PAYMENT_HASH));
+    }
+
+    #[test]
+    fn proven_payments_are_settled_whatever_their_recorded_state() {
+        for label in [
+            None,
+            Some("open"),
+            Some("accepted"),
+            Some("canceled"),
+            Some("settled"),
+        ] {
+            let label = label.map(String::from);
+            assert_eq!(
+                InvoiceUtils::resolve_state(&label, true),
+                Some(InvoiceState::Settled)
+            );
         }
     }
+
+    #[test]
+    fn unproven_payments_are_only_resolved_in_a_final_state() {
+        let state = |label: &str| InvoiceUtils::resolve_state(&Some(label.to_string()), false);
+
+        assert_eq!(state("canceled"), Some(InvoiceState::Canceled));
+        assert_eq!(state("settled"), Some(InvoiceState::Settled));
+        assert_eq!(state("open"), None);
+        assert_eq!(state("accepted"), None);
+        assert_eq!(InvoiceUtils::resolve_state(&None, false), None);
+    }
 }
@@ -0,0 +1,384 @@
+#![allow(deprecated)]
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use bitcoin_hashes::{sha256, Hash};
+use futures::StreamExt;
+use lightning::ln::PaymentSecret;
+use lightning_invoice::{Currency, InvoiceBuilder};
+use rocket::tokio::sync::broadcast;
+use secp256k1::{Secp256k1, SecretKey};
+use tonic_lnd::rpc::{invoice::InvoiceState, Invoice};
+
+use super::{
+    backend::{InvoiceStream, LightningBackend},
+    invoice::{InvoiceParams, LndInvoice},
+};
+use crate::errors::lightning::LightningError;
+
+/// The node key used to sign the mock invoices.
+const MOCK_NODE_KEY: [u8; 32] = [0x01; 32];
+
+/// An in-memory lightning node.
+///
+/// The mock issues real BOLT11 payment requests signed with a static key
+/// and derives preimages from a counter so that the produced invoices
+/// are predictable. Invoices stay open until they are explicitly
+/// settled, canceled or expired through the control methods.
+pub struct MockBackend {
+    invoices: Mutex<MockInvoices>,
+    updates: broadcast::Sender<Invoice>,
+}
+
+#[derive(Default)]
+struct MockInvoices {
+    index: u64,
+    settle_index: u64,
+    // Invoices indexed by their hex encoded payment hash
+    by_hash: HashMap<String, Invoice>,
+}
+
+impl Default for MockBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockBackend {
+    pub fn new() -> Self {
+        let (updates, _) = broadcast::channel(64);
+
+        Self {
+            invoices: Mutex::new(MockInvoices::default()),
+            updates,
+        }
+    }
+
+    /// Marks an open invoice as paid
+    pub fn settle_invoice(&self, r_hash: &str) -> Result<Invoice, LightningError> {
+        let mut invoices = self.invoices.lock().unwrap();
+        let settle_index = invoices.settle_index + 1;
+
+        let invoice = Self::find_open_invoice(&mut invoices, r_hash)?;
+        invoice.set_state(InvoiceState::Settled);
+        invoice.settled = true;
+        invoice.settle_index = settle_index;
+        invoice.settle_date = now();
+        invoice.amt_paid_sat = invoice.value;
+        invoice.amt_paid_msat = invoice.value_msat;
+
+        let invoice = invoice.clone();
+        invoices.settle_index = settle_index;
+        let _ = self.updates.send(invoice.clone());
+        Ok(invoice)
+    }
+
+    /// Cancels an open invoice
+    pub fn cancel_invoice(&self, r_hash: &str) -> Result<Invoice, LightningError> {
+        let mut invoices = self.invoices.lock().unwrap();
+
+        let invoice = Self::find_open_invoice(&mut invoices, r_hash)?;
+        invoice.set_state(InvoiceState::Canceled);
+
+        let invoice = invoice.clone();
+        let _ = self.updates.send(invoice.clone());
+        Ok(invoice)
+    }
+
+    /// Marks an open invoice as expired.
+    /// As LND does, an expired invoice ends in the canceled state.
+    pub fn expire_invoice(&self, r_hash: &str) -> Result<Invoice, LightningError> {
+        let mut invoices = self.invoices.lock().unwrap();
+
+        let invoice = Self::find_open_invoice(&mut invoices, r_hash)?;
+        invoice.creation_date = now() - invoice.expiry - 1;
+        invoice.set_state(InvoiceState::Canceled);
+
+        let invoice = invoice.clone();
+        let _ = self.updates.send(invoice.clone());
+        Ok(invoice)
+    }
+
+    fn find_open_invoice<'a>(
+        invoices: &'a mut MockInvoices,
+        r_hash: &str,
+    ) -> Result<&'a mut Invoice, LightningError> {
+        match invoices.by_hash.get_mut(r_hash) {
+            Some(invoice) => match invoice.state() {
+                InvoiceState::Open | InvoiceState::Accepted => Ok(invoice),
+                _ => Err(LightningError::InvoiceError(
+                    "invoice already settled or canceled".to_string(),
+                )),
+            },
+            None => Err(LightningError::InvoiceError(
+                "unable to locate invoice".to_string(),
+            )),
+        }
+    }
+
+    fn sign_payment_request(
+        params: &InvoiceParams,
+        payment_hash: sha256::Hash,
+        timestamp: i64,
+    ) -> Result<String, LightningError> {
+        let secp = Secp256k1::new();
+        let key = SecretKey::from_slice(&MOCK_NODE_KEY).unwrap();
+
+        InvoiceBuilder::new(Currency::Regtest)
+            .description(params.memo.clone())
+            .payment_hash(payment_hash)
+            .payment_secret(PaymentSecret(payment_hash.into_inner()))
+            .timestamp(UNIX_EPOCH + Duration::from_secs(timestamp as u64))
+            .min_final_cltv_expiry(144)
+            .amount_milli_satoshis(params.value_msat as u64)
+            .expiry_time(Duration::from_secs(params.expiry as u64))
+            .build_signed(|hash| secp.sign_ecdsa_recoverable(hash, &key))
+            .map(|invoice| invoice.to_string())
+            .map_err(|e| LightningError::InvoiceError(e.to_string()))
+    }
+}
+
+#[rocket::async_trait]
+impl LightningBackend for MockBackend {
+    async fn add_invoice(&self, params: InvoiceParams) -> Result<LndInvoice, LightningError> {
+        let mut invoices = self.invoices.lock().unwrap();
+        invoices.index += 1;
+        let index = invoices.index;
+
+        let preimage = sha256::Hash::hash(format!("mock-preimage-{}", index).as_bytes());
+        let payment_hash = sha256::Hash::hash(&preimage.into_inner());
+        let r_hash = hex::encode(payment_hash.into_inner());
+        let creation_date = now();
+
+        let payment_request = Self::sign_payment_request(&params, payment_hash, creation_date)?;
+
+        let mut invoice = Invoice {
+            memo: params.memo,
+            r_preimage: preimage.into_inner().to_vec(),
+            r_hash: payment_hash.into_inner().to_vec(),
+            value: params.value_msat / 1000,
+            value_msat: params.value_msat,
+            creation_date,
+            payment_request,
+            expiry: params.expiry,
+            add_index: index,
+            ..Invoice::default()
+        };
+        invoice.set_state(InvoiceState::Open);
+
+        invoices.by_hash.insert(r_hash.clone(), invoice.clone());
+        let _ = self.updates.send(invoice.clone());
+
+        Ok(LndInvoice::new(invoice, r_hash))
+    }
+
+    async fn lookup_invoice(
+        &self,
+        payment_request: &str,
+    ) -> Result<Option<Invoice>, LightningError> {
+        let mut invoices = self.invoices.lock().unwrap();
+
+        let invoice = invoices
+            .by_hash
+            .values_mut()
+            .find(|invoice| invoice.payment_request == payment_request);
+
+        match invoice {
+            Some(invoice) => {
+                // Open invoices past their expiry are canceled, as LND does.
+                if invoice.state() == InvoiceState::Open
+                    && invoice.creation_date + invoice.expiry < now()
+                {
+                    invoice.set_state(InvoiceState::Canceled);
+                }
+                Ok(Some(invoice.clone()))
+            }
+            None => Ok(None),
+        }
+    }
+
+    async fn subscribe_invoices(&self, settle_index: u64) -> Result<InvoiceStream, LightningError> {
+        let receiver = self.updates.subscribe();
+
+        let mut replayed = match settle_index {
+            0 => vec![],
+            settle_index => self
+                .invoices
+                .lock()
+                .unwrap()
+                .by_hash
+                .values()
+                .filter(|invoice| invoice.settle_index > settle_index)
+                .cloned()
+                .collect::<Vec<Invoice>>(),
+        };
+        replayed.sort_by_key(|invoice| invoice.settle_index);
+
+        let stream = futures::stream::unfold(receiver, |mut receiver| async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(invoice) => return Some((Ok(invoice), receiver)),
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        });
+
+        Ok(futures::stream::iter(replayed.into_iter().map(Ok))
+            .chain(stream)
+            .boxed())
+    }
+
+    async fn health_check(&self) -> Result<(), LightningError> {
+        Ok(())
+    }
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::lnd::{client::LndClient, invoice::InvoiceUtils};
+
+    fn params(value_msat: i64) -> InvoiceParams {
+        InvoiceParams::new(
+            Some(value_msat),
+            Some("mock invoice".to_string()),
+            Some(3600),
+        )
+    }
+
+    /// The client used by the server, along with the mock to control its invoices
+    fn mock_client() -> (Arc<MockBackend>, LndClient) {
+        let mock = Arc::new(MockBackend::new());
+        (mock.clone(), LndClient(mock))
+    }
+
+    #[rocket::async_test]
+    async fn invoices_are_created_open() {
+        let (_, client) = mock_client();
+
+        let invoice = InvoiceUtils::generate_invoice(&client, params(21_000))
+            .await
+            .unwrap();
+        assert_eq!(invoice.state, InvoiceState::Open);
+        assert_eq!(invoice.value_msat, 21_000);
+        assert_eq!(invoice.memo, "mock invoice");
+        assert_eq!(
+            InvoiceUtils::amount_msat(&invoice.payment_request),
+            Some(21_000)
+        );
+
+        let found = client
+            .0
+            .lookup_invoice(&invoice.payment_request)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(found.state(), InvoiceState::Open);
+        assert_eq!(hex::encode(&found.r_hash), invoice.r_hash);
+        assert!(!found.settled);
+    }
+
+    #[rocket::async_test]
+    async fn settled_invoices_reveal_their_preimage() {
+        let (mock, client) = mock_client();
+        let invoice = InvoiceUtils::generate_invoice(&client, params(21_000))
+            .await
+            .unwrap();
+
+        let settled = mock.settle_invoice(&invoice.r_hash).unwrap();
+        assert_eq!(settled.state(), InvoiceState::Settled);
+        assert_eq!(settled.settle_index, 1);
+        assert_eq!(settled.amt_paid_msat, 21_000);
+        assert!(InvoiceUtils::is_preimage_valid(
+            &hex::encode(&settled.r_preimage),
+            &invoice.r_hash
+        ));
+
+        assert_eq!(
+            client
+                .get_invoice_status(invoice.payment_request.clone())
+                .await,
+            Ok(InvoiceState::Settled)
+        );
+
+        // A settled invoice can't be settled or canceled anymore
+        assert!(mock.settle_invoice(&invoice.r_hash).is_err());
+        assert!(mock.cancel_invoice(&invoice.r_hash).is_err());
+
+        let next = InvoiceUtils::generate_invoice(&client, params(1_000))
+            .await
+            .unwrap();
+        assert_eq!(mock.settle_invoice(&next.r_hash).unwrap().settle_index, 2);
+    }
+
+    #[rocket::async_test]
+    async fn canceled_and_expired_invoices_are_canceled() {
+        let (mock, client) = mock_client();
+        let canceled = InvoiceUtils::generate_invoice(&client, params(1_000))
+            .await
+            .unwrap();
+        let expired = InvoiceUtils::generate_invoice(&client, params(1_000))
+            .await
+            .unwrap();
+
+        mock.cancel_invoice(&canceled.r_hash).unwrap();
+        mock.expire_invoice(&expired.r_hash).unwrap();
+
+        for invoice in [canceled, expired] {
+            assert_eq!(
+                client.get_invoice_status(invoice.payment_request).await,
+                Ok(InvoiceState::Canceled)
+            );
+            assert!(mock.settle_invoice(&invoice.r_hash).is_err());
+        }
+    }
+
+    #[rocket::async_test]
+    async fn unknown_invoices_are_not_found() {
+        let (mock, client) = mock_client();
+
+        assert!(client.0.lookup_invoice("lnbcrt1").await.unwrap().is_none());
+        assert!(mock.settle_invoice(&"00".repeat(32)).is_err());
+    }
+
+    #[rocket::async_test]
+    async fn subscriptions_replay_the_invoices_settled_since_the_provided_index() {
+        let (mock, client) = mock_client();
+
+        let mut hashes = vec![];
+        for _ in 0..3 {
+            let invoice = InvoiceUtils::generate_invoice(&client, params(1_000))
+                .await
+                .unwrap();
+            hashes.push(invoice.r_hash);
+        }
+        mock.settle_invoice(&hashes[0]).unwrap();
+        mock.settle_invoice(&hashes[1]).unwrap();
+
+        let mut updates = client.0.subscribe_invoices(1).await.unwrap();
+
+        // The invoices settled after the index are replayed first
+        let replayed = updates.next().await.unwrap().unwrap();
+        assert_eq!(hex::encode(&replayed.r_hash), hashes[1]);
+        assert_eq!(replayed.settle_index, 2);
+
+        // Then the updates are pushed as they happen
+        mock.settle_invoice(&hashes[2]).unwrap();
+        let update = updates.next().await.unwrap().unwrap();
+        assert_eq!(hex::encode(&update.r_hash), hashes[2]);
+        assert_eq!(update.state(), InvoiceState::Settled);
+    }
+}
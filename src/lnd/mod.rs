@@ -1,2 +1,9 @@
+pub mod backend;
 pub mod client;
+pub mod grpc;
+pub mod igniter;
 pub mod invoice;
+pub mod mock;
+pub mod paywall;
+pub mod subscriber;
+pub mod throttle;
@@ -0,0 +1,252 @@
+use diesel::{PgConnection, QueryResult};
+use tonic_lnd::rpc::invoice::InvoiceState;
+
+use super::{
+    client::LndClient,
+    invoice::InvoiceUtils,
+    subscriber::{InvoiceEvent, InvoiceEvents},
+};
+use crate::{
+    db::{
+        models::{
+            bundle_payment::BundlePayment, download_token::DownloadToken,
+            media_payment::MediaPayment,
+        },
+        DbConnection,
+    },
+    errors::paywall::PaywallError,
+};
+
+/// A payment recorded for an invoice of the paywall
+pub trait PaywallPayment: Clone + Send + 'static {
+    fn request(&self) -> &str;
+    fn hash(&self) -> &str;
+    /// The label of the invoice state recorded in database, if any
+    fn state(&self) -> &Option<String>;
+    fn is_expired(&self) -> bool;
+    fn is_requested_by(&self, client_secret: Option<&str>) -> bool;
+    fn record_settlement(&self, connection: &PgConnection) -> QueryResult<usize>;
+    /// The download token minted for a client that proved the payment
+    fn download_token(&self) -> DownloadToken;
+}
+
+impl PaywallPayment for MediaPayment {
+    fn request(&self) -> &str {
+        &self.request
+    }
+
+    fn hash(&self) -> &str {
+        &self.hash
+    }
+
+    fn state(&self) -> &Option<String> {
+        &self.state
+    }
+
+    fn is_expired(&self) -> bool {
+        MediaPayment::is_expired(self)
+    }
+
+    fn is_requested_by(&self, client_secret: Option<&str>) -> bool {
+        MediaPayment::is_requested_by(self, client_secret)
+    }
+
+    fn record_settlement(&self, connection: &PgConnection) -> QueryResult<usize> {
+        MediaPayment::record_settlement(self, connection)
+    }
+
+    fn download_token(&self) -> DownloadToken {
+        DownloadToken::new(self)
+    }
+}
+
+impl PaywallPayment for BundlePayment {
+    fn request(&self) -> &str {
+        &self.request
+    }
+
+    fn hash(&self) -> &str {
+        &self.hash
+    }
+
+    fn state(&self) -> &Option<String> {
+        &self.state
+    }
+
+    fn is_expired(&self) -> bool {
+        BundlePayment::is_expired(self)
+    }
+
+    fn is_requested_by(&self, client_secret: Option<&str>) -> bool {
+        BundlePayment::is_requested_by(self, client_secret)
+    }
+
+    fn record_settlement(&self, connection: &PgConnection) -> QueryResult<usize> {
+        BundlePayment::record_settlement(self, connection)
+    }
+
+    fn download_token(&self) -> DownloadToken {
+        DownloadToken::for_bundle(self)
+    }
+}
+
+/// The status of a payment, as seen by the paywall
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PaymentStatus {
+    /// The payment is proven by the invoice preimage
+    /// or by the secret returned to the client that requested the invoice
+    Unlocked,
+    /// The invoice is settled but the client proved neither
+    /// the payment nor that it requested the invoice
+    ProofRequired,
+    /// The invoice is paid but held by the lightning backend until it is settled
+    Held,
+    /// The invoice is waiting to be paid
+    Open,
+    /// The invoice is canceled, a new one is required
+    Canceled,
+    /// The validity of the payment is over, a new one is required
+    Expired,
+}
+
+/// Resolves the status of the payments behind the paywall,
+/// for the GraphQL queries and the REST routes alike.
+pub struct Paywall<'a> {
+    db: &'a DbConnection,
+    lnd: &'a LndClient,
+    events: &'a InvoiceEvents,
+}
+
+impl<'a> Paywall<'a> {
+    pub fn new(db: &'a DbConnection, lnd: &'a LndClient, events: &'a InvoiceEvents) -> Self {
+        Self { db, lnd, events }
+    }
+
+    /// Provides the status of a payment.
+    ///
+    /// A valid preimage proves the payment: its settlement is recorded and the
+    /// subscriptions are notified of it. Otherwise the settled and canceled states
+    /// recorded in database are relied on, and the others are requested to the lightning backend.
+    pub async fn resolve<P: PaywallPayment>(
+        &self,
+        payment: &P,
+        preimage: Option<&str>,
+        client_secret: Option<&str>,
+    ) -> Result<PaymentStatus, PaywallError> {
+        if payment.is_expired() {
+            return Ok(PaymentStatus::Expired);
+        }
+
+        let proven = match preimage {
+            Some(preimage) if InvoiceUtils::is_preimage_valid(preimage, payment.hash()) => true,
+            Some(_) => return Err(PaywallError::InvalidPreimage),
+            None => false,
+        };
+
+        if proven {
+            self.record_settlement(payment).await;
+        }
+
+        let state = match InvoiceUtils::resolve_state(payment.state(), proven) {
+            Some(state) => state,
+            None => InvoiceUtils::get_invoice_state_from_payment_request(
+                self.lnd,
+                payment.request().to_string(),
+            )
+            .await?
+            .ok_or(PaywallError::InvoiceNotFound)?
+            .state(),
+        };
+
+        Ok(status_of(payment, state, proven, client_secret))
+    }
+
+    async fn record_settlement<P: PaywallPayment>(&self, payment: &P) {
+        let settled = payment.clone();
+
+        match self.db.run(move |c| settled.record_settlement(c)).await {
+            // The settlement was already known, so were the subscriptions
+            Ok(0) => {}
+            Ok(_) => self.events.publish(InvoiceEvent {
+                hash: payment.hash().to_string(),
+                state: InvoiceState::Settled,
+            }),
+            Err(e) => error!("Unable to record payment settlement: {}", e),
+        }
+    }
+}
+
+/// Provides the status of a payment which invoice is in the provided state
+fn status_of<P: PaywallPayment>(
+    payment: &P,
+    state: InvoiceState,
+    proven: bool,
+    client_secret: Option<&str>,
+) -> PaymentStatus {
+    match state {
+        InvoiceState::Settled if proven || payment.is_requested_by(client_secret) => {
+            PaymentStatus::Unlocked
+        }
+        InvoiceState::Settled => PaymentStatus::ProofRequired,
+        InvoiceState::Accepted => PaymentStatus::Held,
+        InvoiceState::Open => PaymentStatus::Open,
+        InvoiceState::Canceled => PaymentStatus::Canceled,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::db::models::download_token::ClientSecret;
+
+    fn payment(secret: &ClientSecret) -> MediaPayment {
+        MediaPayment {
+            uuid: Uuid::new_v4(),
+            request: "lnbcrt1".to_string(),
+            state: None,
+            hash: "66687aadf862bd776c8fc18b8e9f8e20089714856ee233b3902a591d0d5f2925".to_string(),
+            media_uuid: Uuid::new_v4(),
+            expires_at: (Utc::now() + Duration::hours(1)).naive_utc(),
+            valid_until: None,
+            settled_at: None,
+            price_currency: None,
+            exchange_rate: None,
+            client_secret_hash: Some(secret.hash()),
+        }
+    }
+
+    #[test]
+    fn settled_payments_are_unlocked_for_the_requester_or_on_proof() {
+        let secret = ClientSecret::generate();
+        let payment = payment(&secret);
+        let provided = secret.into_string();
+
+        let status = |proven, client_secret| {
+            status_of(&payment, InvoiceState::Settled, proven, client_secret)
+        };
+
+        assert_eq!(status(true, None), PaymentStatus::Unlocked);
+        assert_eq!(
+            status(false, Some(provided.as_str())),
+            PaymentStatus::Unlocked
+        );
+        assert_eq!(status(false, Some("other")), PaymentStatus::ProofRequired);
+        assert_eq!(status(false, None), PaymentStatus::ProofRequired);
+    }
+
+    #[test]
+    fn pending_payments_are_not_unlocked() {
+        let secret = ClientSecret::generate();
+        let payment = payment(&secret);
+        let provided = secret.into_string();
+
+        let status = |state| status_of(&payment, state, false, Some(provided.as_str()));
+
+        assert_eq!(status(InvoiceState::Accepted), PaymentStatus::Held);
+        assert_eq!(status(InvoiceState::Open), PaymentStatus::Open);
+        assert_eq!(status(InvoiceState::Canceled), PaymentStatus::Canceled);
+    }
+}
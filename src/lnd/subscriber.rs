@@ -0,0 +1,189 @@
+use std::time::Duration;
+
+use chrono::NaiveDateTime;
+use diesel::Connection;
+use futures::StreamExt;
+use rocket::{
+    futures::future::BoxFuture,
+    tokio::{sync::broadcast, time::sleep},
+    Orbit, Rocket,
+};
+use tonic_lnd::rpc::{invoice::InvoiceState, Invoice};
+
+use super::{client::LndClient, invoice::InvoiceUtils};
+use crate::db::{
+    models::{
+        api_payment::ApiPayment, bundle_payment::BundlePayment,
+        invoice_subscription::InvoiceSubscription, media_payment::MediaPayment,
+    },
+    pool::DbPool,
+};
+
+/// A settled or canceled invoice, as recorded on its payment
+#[derive(Clone, Debug)]
+pub struct InvoiceEvent {
+    pub hash: String,
+    pub state: InvoiceState,
+}
+
+/// Broadcasts the recorded invoice states to the GraphQL subscriptions.
+///
+/// The sender is managed by the server, so cloning it only
+/// clones a handle to the same channel.
+#[derive(Clone)]
+pub struct InvoiceEvents(broadcast::Sender<InvoiceEvent>);
+
+impl Default for InvoiceEvents {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InvoiceEvents {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(256);
+        Self(sender)
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<InvoiceEvent> {
+        self.0.subscribe()
+    }
+
+    pub fn publish(&self, event: InvoiceEvent) {
+        // Sending only fails when no subscription is listening
+        let _ = self.0.send(event);
+    }
+}
+
+// Subscribes to the invoices stream of the lightning backend once the server is launched.
+// Settled and canceled invoices have their state recorded on the matching payment
+// so the paywall can rely on the database rather than requesting the node.
+// The subscription resumes from the last recorded settlement, so that the invoices
+// settled while the server was down are recorded as well.
+pub fn watch_invoices<'a>(rocket: &'a Rocket<Orbit>) -> BoxFuture<'a, ()> {
+    Box::pin(async move {
+        let client = match rocket.state::<LndClient>() {
+            Some(client) => client.clone(),
+            None => return,
+        };
+
+        let db = match rocket.state::<DbPool>() {
+            Some(db) => db.clone(),
+            None => {
+                error!("Unable to get the database pool for the invoices subscription");
+                return;
+            }
+        };
+
+        let events = match rocket.state::<InvoiceEvents>() {
+            Some(events) => events.clone(),
+            None => InvoiceEvents::new(),
+        };
+
+        rocket::tokio::spawn(subscribe(client, db, events));
+    })
+}
+
+async fn subscribe(client: LndClient, db: DbPool, events: InvoiceEvents) {
+    let mut settle_index = None;
+    let mut delay = Duration::from_secs(1);
+
+    loop {
+        let from = match settle_index {
+            Some(index) => index,
+            None => match db.run(InvoiceSubscription::settle_index).await {
+                Ok(index) => *settle_index.insert(index.max(0) as u64),
+                Err(e) => {
+                    error!("Unable to get the last recorded settlement: {}", e);
+                    sleep(delay).await;
+                    delay = std::cmp::min(delay * 2, Duration::from_secs(60));
+                    continue;
+                }
+            },
+        };
+
+        match client.0.subscribe_invoices(from).await {
+            Ok(mut stream) => {
+                info!("Subscribed to the lightning backend invoices");
+                delay = Duration::from_secs(1);
+
+                while let Some(update) = stream.next().await {
+                    match update {
+                        Ok(invoice) => {
+                            let index = invoice.settle_index;
+                            if !record_invoice_state(&db, &events, invoice).await {
+                                // Later settlements would move the recorded index past this one,
+                                // we resubscribe from the last recorded one to get it replayed
+                                warn!("Invoices subscription restarted to replay an unrecorded invoice");
+                                break;
+                            }
+                            settle_index = settle_index.max(Some(index));
+                        }
+                        Err(e) => {
+                            warn!("Invoices subscription interrupted: {}", e);
+                            break;
+                        }
+                    }
+                }
+            }
+            Err(e) => error!(
+                "Unable to subscribe to the lightning backend invoices: {}",
+                e
+            ),
+        }
+
+        // The stream ended, we resubscribe from the last known settlement
+        sleep(delay).await;
+        delay = std::cmp::min(delay * 2, Duration::from_secs(60));
+    }
+}
+
+/// Stores the state of a settled or canceled invoice onto its payment
+/// along with the settle index the subscription shall resume from,
+/// and notifies the subscriptions once recorded.
+/// Returns whether the invoice has been processed.
+async fn record_invoice_state(db: &DbPool, events: &InvoiceEvents, invoice: Invoice) -> bool {
+    let state = invoice.state();
+
+    let settled_at = match state {
+        InvoiceState::Settled => Some(NaiveDateTime::from_timestamp(invoice.settle_date, 0)),
+        InvoiceState::Canceled => None,
+        // Other states are transient
+        _ => return true,
+    };
+    let settle_index = invoice.settle_index as i64;
+
+    let hash = hex::encode(&invoice.r_hash);
+    let label = InvoiceUtils::state_label(state).to_string();
+
+    let payment_hash = hash.clone();
+    let result = db
+        .run(move |c| {
+            c.transaction(|| {
+                MediaPayment::update_state_by_hash(hash.clone(), label.clone(), settled_at, c)?;
+                BundlePayment::update_state_by_hash(hash.clone(), label.clone(), settled_at, c)?;
+                ApiPayment::update_state_by_hash(hash, label, settled_at, c)?;
+
+                if settle_index > 0 {
+                    InvoiceSubscription::record_settle_index(settle_index, c)?;
+                }
+
+                Ok(())
+            })
+        })
+        .await;
+
+    match result {
+        Ok(()) => {
+            events.publish(InvoiceEvent {
+                hash: payment_hash,
+                state,
+            });
+            true
+        }
+        Err(e) => {
+            error!("Unable to record invoice state: {}", e);
+            false
+        }
+    }
+}
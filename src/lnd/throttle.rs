@@ -0,0 +1,109 @@
+use std::{
+    collections::HashMap,
+    env,
+    net::IpAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// The default number of invoices a client may request per minute, set through `INVOICE_RATE_LIMIT`
+const DEFAULT_RATE_LIMIT: u32 = 10;
+
+const WINDOW: Duration = Duration::from_secs(60);
+
+/// The start of the current window of each client, along with the invoices it requested since
+type Windows = HashMap<Option<IpAddr>, (Instant, u32)>;
+
+/// Limits the number of invoices generated for each client.
+///
+/// Anonymous clients get a new invoice whenever they don't provide one, so each
+/// of them is allowed a number of invoices per minute to keep the lightning backend
+/// and the database from being flooded. Clients are identified by their IP address,
+/// the ones without address share the same limit.
+///
+/// The counters are managed by the server, so cloning it only clones a handle to them.
+#[derive(Clone)]
+pub struct InvoiceThrottle {
+    limit: u32,
+    clients: Arc<Mutex<Windows>>,
+}
+
+impl Default for InvoiceThrottle {
+    fn default() -> Self {
+        Self::new(DEFAULT_RATE_LIMIT)
+    }
+}
+
+impl InvoiceThrottle {
+    pub fn new(limit: u32) -> Self {
+        Self {
+            limit,
+            clients: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Builds the throttle with the limit set through `INVOICE_RATE_LIMIT`
+    pub fn from_env() -> Self {
+        let limit = env::var("INVOICE_RATE_LIMIT")
+            .ok()
+            .and_then(|limit| limit.parse::<u32>().ok())
+            .filter(|limit| *limit > 0)
+            .unwrap_or(DEFAULT_RATE_LIMIT);
+
+        Self::new(limit)
+    }
+
+    /// Counts an invoice for the client, unless it reached its limit for the current minute
+    pub fn allow(&self, client: Option<IpAddr>) -> bool {
+        self.allow_at(client, Instant::now())
+    }
+
+    fn allow_at(&self, client: Option<IpAddr>, now: Instant) -> bool {
+        let mut clients = match self.clients.lock() {
+            Ok(clients) => clients,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        // The clients which window is over are forgotten
+        clients.retain(|_, (start, _)| now.duration_since(*start) < WINDOW);
+
+        let (_, count) = clients.entry(client).or_insert((now, 0));
+        if *count >= self.limit {
+            return false;
+        }
+
+        *count += 1;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use super::*;
+
+    #[test]
+    fn clients_are_limited_on_their_own() {
+        let throttle = InvoiceThrottle::new(2);
+        let client = Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)));
+        let other = Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)));
+        let now = Instant::now();
+
+        assert!(throttle.allow_at(client, now));
+        assert!(throttle.allow_at(client, now));
+        assert!(!throttle.allow_at(client, now));
+        assert!(throttle.allow_at(other, now));
+    }
+
+    #[test]
+    fn limits_are_reset_every_window() {
+        let throttle = InvoiceThrottle::new(1);
+        let client = Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)));
+        let now = Instant::now();
+
+        assert!(throttle.allow_at(client, now));
+        assert!(!throttle.allow_at(client, now + Duration::from_secs(30)));
+        assert!(throttle.allow_at(client, now + WINDOW));
+    }
+}
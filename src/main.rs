@@ -21,30 +21,55 @@ mod catchers;
 mod cors;
 mod db;
 mod errors;
+mod extractor;
 mod forms;
 mod graphql;
 mod guards;
+mod l402;
 mod lnd;
+mod rates;
 mod responders;
 mod routes;
+mod storage;
+mod uploads;
 
 use crate::db::PostgresConn;
-use app::Schema;
-use db::igniter::{run_db_migrations, seed_db};
+use app::build_schema;
+use db::igniter::{run_db_migrations, seed_db, setup_db_pool};
 use dotenv::dotenv;
-use juniper::EmptySubscription;
+use extractor::backfill::backfill_file_metadata;
+use graphql::websocket::serve_subscriptions;
+use lnd::igniter::{monitor_lightning_backend, setup_lightning_backend};
+use lnd::subscriber::{watch_invoices, InvoiceEvents};
+use lnd::throttle::InvoiceThrottle;
+use rates::igniter::setup_rate_provider;
 use rocket::Rocket;
 use rocket::{fairing::AdHoc, Route};
-use routes::{auth::login, file::get_file, utils::graphiql, utils::static_index};
+use routes::{
+    auth::login,
+    bundle::get_bundle,
+    file::{get_file, stream_file},
+    preview::{get_preview, get_thumbnail},
+    tus::{
+        create_upload, delete_upload, get_upload_offset, patch_upload, tus_options,
+        tus_upload_options,
+    },
+    utils::graphiql,
+    utils::static_index,
+};
 use std::env;
+use storage::{collector::collect_garbage, igniter::setup_storage_backend};
+use uploads::{collector::remove_expired_uploads, igniter::setup_partial_uploads};
 
 use app::{
     auth_options_handler, graphql_options_handler, payable_post_graphql_handler,
     post_graphql_handler, upload,
 };
-use catchers::payment_required::payment_required;
+use catchers::{
+    errors::{bad_request, forbidden, internal_error, not_found, unauthorized},
+    payment_required::payment_required,
+};
 use cors::Cors;
-use graphql::{context::GQLContext, mutation::Mutation, query::Query};
 
 itconfig::config! {
     DATABASE_URL: String,
@@ -68,14 +93,57 @@ async fn main() -> Result<(), rocket::Error> {
             run_db_migrations,
         ))
         .attach(AdHoc::try_on_ignite("Database seed", seed_db))
+        .attach(AdHoc::try_on_ignite("Database pool", setup_db_pool))
+        .attach(AdHoc::try_on_ignite(
+            "Lightning backend",
+            setup_lightning_backend,
+        ))
+        .attach(AdHoc::try_on_ignite(
+            "Storage backend",
+            setup_storage_backend,
+        ))
+        .attach(AdHoc::try_on_ignite("Exchange rates", setup_rate_provider))
+        .attach(AdHoc::try_on_ignite(
+            "Resumable uploads",
+            setup_partial_uploads,
+        ))
+        .attach(AdHoc::on_liftoff(
+            "Lightning health check",
+            monitor_lightning_backend,
+        ))
+        .attach(AdHoc::on_liftoff("Invoices subscription", watch_invoices))
+        .attach(AdHoc::on_liftoff(
+            "Storage garbage collection",
+            collect_garbage,
+        ))
+        .attach(AdHoc::on_liftoff(
+            "Media metadata backfill",
+            backfill_file_metadata,
+        ))
+        .attach(AdHoc::on_liftoff(
+            "Expired uploads removal",
+            remove_expired_uploads,
+        ))
+        .attach(AdHoc::on_liftoff(
+            "GraphQL subscriptions",
+            serve_subscriptions,
+        ))
         .manage(Cors)
+        .manage(InvoiceEvents::new())
+        .manage(InvoiceThrottle::from_env())
         // .configure(figment)
-        .register("/", catchers![payment_required])
-        .manage(Schema::new(
-            Query,
-            Mutation,
-            EmptySubscription::<GQLContext>::new(),
-        ))
+        .register(
+            "/",
+            catchers![
+                bad_request,
+                unauthorized,
+                payment_required,
+                forbidden,
+                not_found,
+                internal_error
+            ],
+        )
+        .manage(build_schema())
         .mount("/", routes_builder())
         .launch()
         .await
@@ -92,7 +160,17 @@ fn routes_builder() -> Vec<Route> {
         payable_post_graphql_handler,
         upload,
         login,
-        get_file
+        get_file,
+        stream_file,
+        get_bundle,
+        get_preview,
+        get_thumbnail,
+        tus_options,
+        tus_upload_options,
+        create_upload,
+        get_upload_offset,
+        patch_upload,
+        delete_upload
     ];
 
     let enable_dev_tools = env::var("ENABLE_DEV_TOOLS").unwrap_or("false".to_string());
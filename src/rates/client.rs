@@ -0,0 +1,208 @@
+use std::sync::Arc;
+
+use rocket::{
+    http::Status,
+    request::{FromRequest, Outcome},
+    Request,
+};
+
+use super::provider::RateProvider;
+use crate::{db::models::media::Media, errors::rate::RateError};
+
+const MSATS_PER_BITCOIN: f64 = 100_000_000_000.0;
+
+/// The amount of millisatoshis a media is sold for, quoted when its invoice is generated
+#[derive(Debug, Clone)]
+pub struct Quote {
+    pub value_msat: i64,
+    /// The currency the media is priced in and the rate its price was converted with,
+    /// if the media is priced in a fiat currency
+    pub currency: Option<String>,
+    pub rate: Option<f64>,
+}
+
+/// A handle to the exchange rate provider converting fiat prices into millisatoshis.
+#[derive(Clone)]
+pub struct RateClient(pub Arc<dyn RateProvider>);
+
+impl<P: RateProvider + 'static> From<P> for RateClient {
+    fn from(provider: P) -> Self {
+        RateClient(Arc::new(provider))
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for RateClient {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match request.rocket().state::<RateClient>() {
+            Some(client) => Outcome::Success(client.clone()),
+            None => {
+                error!("No exchange rate provider is managed by the server");
+                Outcome::Failure((Status::ServiceUnavailable, ()))
+            }
+        }
+    }
+}
+
+impl RateClient {
+    /// Checks a rate is available for a currency and normalizes its code, e.g: `eur` into `EUR`
+    pub async fn check_currency(&self, currency: &str) -> Result<String, RateError> {
+        let code = currency.trim().to_uppercase();
+
+        if code.len() != 3 || !code.chars().all(|c| c.is_ascii_alphabetic()) {
+            return Err(RateError::UnsupportedCurrency(currency.to_string()));
+        }
+
+        self.0.rate(&code).await?;
+
+        Ok(code)
+    }
+
+    /// Checks the price of a media: the price in millisatoshis, if provided, can't be negative
+    /// and the fiat amount is required along with a currency a rate is available for.
+    /// Returns the normalized currency, if any.
+    pub async fn check_price(
+        &self,
+        price_msat: Option<i64>,
+        currency: Option<&str>,
+        amount: Option<i32>,
+    ) -> Result<Option<String>, RateError> {
+        if matches!(price_msat, Some(price) if price < 0) {
+            return Err(RateError::InvalidPrice(
+                "the price can't be negative".to_string(),
+            ));
+        }
+
+        match (currency, amount) {
+            (None, None) => Ok(None),
+            (None, Some(_)) => Err(RateError::InvalidPrice(
+                "a currency is required along with the amount".to_string(),
+            )),
+            (Some(_), None) => Err(RateError::InvalidPrice(
+                "an amount is required along with the currency".to_string(),
+            )),
+            (Some(_), Some(amount)) if amount < 0 => Err(RateError::InvalidPrice(
+                "the amount can't be negative".to_string(),
+            )),
+            (Some(currency), Some(_)) => self.check_currency(currency).await.map(Some),
+        }
+    }
+
+    /// Quotes the price of a media in millisatoshis.
+    /// A media priced in a fiat currency is converted at the current rate.
+    pub async fn quote(&self, media: &Media) -> Result<Quote, RateError> {
+        match (&media.price_currency, media.price_amount) {
+            (Some(currency), Some(amount)) => {
+                // The currency was supported when the media was priced,
+                // so a missing rate is a misconfiguration of the server
+                let rate = self.0.rate(currency).await.map_err(|error| match error {
+                    RateError::UnsupportedCurrency(currency) => RateError::MissingRate(currency),
+                    error => error,
+                })?;
+
+                Ok(Quote {
+                    value_msat: to_msat(amount, minor_unit_exponent(currency), rate),
+                    currency: Some(currency.clone()),
+                    rate: Some(rate),
+                })
+            }
+            _ => Ok(Quote {
+                value_msat: media.price_msat,
+                currency: None,
+                rate: None,
+            }),
+        }
+    }
+}
+
+/// The number of decimals of the minor unit of a currency, as defined by ISO 4217,
+/// e.g: `2` for the cents of `EUR` and `0` for `JPY`
+pub fn minor_unit_exponent(currency: &str) -> i32 {
+    match currency {
+        "BIF" | "CLP" | "DJF" | "GNF" | "ISK" | "JPY" | "KMF" | "KRW" | "PYG" | "RWF" | "UGX"
+        | "UYI" | "VND" | "VUV" | "XAF" | "XOF" | "XPF" => 0,
+        "BHD" | "IQD" | "JOD" | "KWD" | "LYD" | "OMR" | "TND" => 3,
+        "CLF" | "UYW" => 4,
+        _ => 2,
+    }
+}
+
+/// Converts an amount in the minor unit of a currency into millisatoshis, rounded up
+/// so a media is never sold below its price
+fn to_msat(amount: i32, exponent: i32, rate: f64) -> i64 {
+    (amount as f64 / 10f64.powi(exponent) / rate * MSATS_PER_BITCOIN).ceil() as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rates::fixed::StaticRates;
+
+    #[test]
+    fn amounts_are_converted_in_the_minor_unit_of_their_currency() {
+        // 2.50 EUR at 25000 EUR per bitcoin
+        assert_eq!(
+            to_msat(250, minor_unit_exponent("EUR"), 25_000.0),
+            10_000_000
+        );
+        // 250 JPY at 2500000 JPY per bitcoin
+        assert_eq!(
+            to_msat(250, minor_unit_exponent("JPY"), 2_500_000.0),
+            10_000_000
+        );
+        // 0.250 KWD at 2500 KWD per bitcoin
+        assert_eq!(
+            to_msat(250, minor_unit_exponent("KWD"), 2_500.0),
+            10_000_000
+        );
+    }
+
+    #[test]
+    fn amounts_are_rounded_up() {
+        assert_eq!(to_msat(1, 2, 30_000.0), 33_334);
+        assert_eq!(to_msat(0, 2, 30_000.0), 0);
+    }
+
+    #[rocket::async_test]
+    async fn prices_are_checked() {
+        let rates = RateClient::from(StaticRates::parse("EUR=25000").unwrap());
+
+        assert_eq!(
+            rates.check_price(Some(1000), None, None).await.unwrap(),
+            None
+        );
+        assert_eq!(
+            rates
+                .check_price(None, Some(" eur "), Some(250))
+                .await
+                .unwrap(),
+            Some("EUR".to_string())
+        );
+        assert!(matches!(
+            rates.check_price(Some(-1), None, None).await,
+            Err(RateError::InvalidPrice(_))
+        ));
+        assert!(matches!(
+            rates.check_price(None, Some("EUR"), None).await,
+            Err(RateError::InvalidPrice(_))
+        ));
+        assert!(matches!(
+            rates.check_price(None, None, Some(250)).await,
+            Err(RateError::InvalidPrice(_))
+        ));
+        assert!(matches!(
+            rates.check_price(None, Some("EUR"), Some(-250)).await,
+            Err(RateError::InvalidPrice(_))
+        ));
+        assert!(matches!(
+            rates.check_price(None, Some("USD"), Some(250)).await,
+            Err(RateError::UnsupportedCurrency(_))
+        ));
+        assert!(matches!(
+            rates.check_price(None, Some("EURO"), Some(250)).await,
+            Err(RateError::UnsupportedCurrency(_))
+        ));
+    }
+}
@@ -0,0 +1,86 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use reqwest::Client;
+
+use super::provider::RateProvider;
+use crate::errors::rate::RateError;
+
+/// Market rates provided by the CoinGecko API.
+/// Rates are kept for `ttl` so that listings and invoices don't query the API each time.
+pub struct CoinGeckoRates {
+    client: Client,
+    endpoint: String,
+    ttl: Duration,
+    cache: Mutex<HashMap<String, (f64, Instant)>>,
+}
+
+impl CoinGeckoRates {
+    pub fn new(endpoint: String, ttl: Duration) -> Self {
+        Self {
+            client: Client::new(),
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            ttl,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn cached(&self, currency: &str) -> Option<f64> {
+        let cache = self.cache.lock().ok()?;
+
+        match cache.get(currency) {
+            Some((rate, fetched_at)) if fetched_at.elapsed() < self.ttl => Some(*rate),
+            _ => None,
+        }
+    }
+
+    async fn fetch(&self, currency: &str) -> Result<f64, RateError> {
+        let url = format!(
+            "{}/simple/price?ids=bitcoin&vs_currencies={}",
+            self.endpoint,
+            currency.to_lowercase()
+        );
+
+        let response = self
+            .client
+            .get(&url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| RateError::ProviderError(e.to_string()))?;
+        let body = response
+            .bytes()
+            .await
+            .map_err(|e| RateError::ProviderError(e.to_string()))?;
+
+        // e.g: { "bitcoin": { "eur": 25000.0 } }
+        let prices = serde_json::from_slice::<HashMap<String, HashMap<String, f64>>>(&body)?;
+
+        prices
+            .get("bitcoin")
+            .and_then(|rates| rates.get(&currency.to_lowercase()))
+            .copied()
+            .filter(|rate| rate.is_finite() && *rate > 0.0)
+            .ok_or_else(|| RateError::UnsupportedCurrency(currency.to_string()))
+    }
+}
+
+#[rocket::async_trait]
+impl RateProvider for CoinGeckoRates {
+    async fn rate(&self, currency: &str) -> Result<f64, RateError> {
+        if let Some(rate) = self.cached(currency) {
+            return Ok(rate);
+        }
+
+        let rate = self.fetch(currency).await?;
+
+        if let Ok(mut cache) = self.cache.lock() {
+            cache.insert(currency.to_string(), (rate, Instant::now()));
+        }
+
+        Ok(rate)
+    }
+}
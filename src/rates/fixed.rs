@@ -0,0 +1,116 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use rocket::tokio::fs;
+
+use super::provider::RateProvider;
+use crate::errors::rate::RateError;
+
+/// Rates set once through the configuration, e.g: `EUR=25000,USD=27000`.
+pub struct StaticRates {
+    rates: HashMap<String, f64>,
+}
+
+impl StaticRates {
+    /// Parses a comma separated list of currencies and rates.
+    /// Returns the invalid entry if any.
+    pub fn parse(rates: &str) -> Result<Self, String> {
+        let mut parsed = HashMap::new();
+
+        for entry in rates
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+        {
+            let rate = entry
+                .split_once('=')
+                .and_then(|(currency, rate)| {
+                    rate.trim()
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|rate| rate.is_finite() && *rate > 0.0)
+                        .map(|rate| (currency.trim().to_uppercase(), rate))
+                })
+                .ok_or_else(|| entry.to_string())?;
+
+            parsed.insert(rate.0, rate.1);
+        }
+
+        Ok(Self { rates: parsed })
+    }
+}
+
+#[rocket::async_trait]
+impl RateProvider for StaticRates {
+    async fn rate(&self, currency: &str) -> Result<f64, RateError> {
+        self.rates
+            .get(currency)
+            .copied()
+            .ok_or_else(|| RateError::UnsupportedCurrency(currency.to_string()))
+    }
+}
+
+/// Rates read from a JSON file, e.g: `{ "EUR": 25000, "USD": 27000 }`.
+/// The file is read for each conversion, so rates can be updated without a restart.
+pub struct FileRates {
+    path: PathBuf,
+}
+
+impl FileRates {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+}
+
+#[rocket::async_trait]
+impl RateProvider for FileRates {
+    async fn rate(&self, currency: &str) -> Result<f64, RateError> {
+        let content = fs::read(&self.path).await?;
+        let rates = serde_json::from_slice::<HashMap<String, f64>>(&content)?;
+
+        rates
+            .iter()
+            .find(|(code, _)| code.eq_ignore_ascii_case(currency))
+            .map(|(_, rate)| *rate)
+            .filter(|rate| rate.is_finite() && *rate > 0.0)
+            .ok_or_else(|| RateError::UnsupportedCurrency(currency.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[rocket::async_test]
+    async fn rates_are_parsed() {
+        let rates = StaticRates::parse(" eur=25000, USD = 27000.5,").unwrap();
+
+        assert_eq!(rates.rate("EUR").await.unwrap(), 25000.0);
+        assert_eq!(rates.rate("USD").await.unwrap(), 27000.5);
+        assert!(matches!(
+            rates.rate("JPY").await,
+            Err(RateError::UnsupportedCurrency(_))
+        ));
+        assert!(StaticRates::parse("").unwrap().rates.is_empty());
+    }
+
+    #[test]
+    fn invalid_rates_are_returned() {
+        assert_eq!(
+            StaticRates::parse("EUR=25000,USD").err(),
+            Some("USD".to_string())
+        );
+        assert_eq!(
+            StaticRates::parse("EUR=abc").err(),
+            Some("EUR=abc".to_string())
+        );
+        assert_eq!(StaticRates::parse("EUR=0").err(), Some("EUR=0".to_string()));
+        assert_eq!(
+            StaticRates::parse("EUR=-1").err(),
+            Some("EUR=-1".to_string())
+        );
+        assert_eq!(
+            StaticRates::parse("EUR=inf").err(),
+            Some("EUR=inf".to_string())
+        );
+    }
+}
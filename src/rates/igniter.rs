@@ -0,0 +1,62 @@
+use std::{env, path::PathBuf, time::Duration};
+
+use rocket::{Build, Rocket};
+
+use super::{
+    client::RateClient,
+    coingecko::CoinGeckoRates,
+    fixed::{FileRates, StaticRates},
+};
+
+/// Default URL of the CoinGecko API
+const DEFAULT_COINGECKO_URL: &str = "https://api.coingecko.com/api/v3";
+
+/// Default delay - in seconds - a market rate is kept before it is fetched again
+const DEFAULT_RATES_TTL: u64 = 60;
+
+// Creates the exchange rate provider used to price media in a fiat currency on ignite of the server.
+// Rates are set through the `EXCHANGE_RATES` env var unless `EXCHANGE_RATES_PROVIDER` is set to:
+//  - `file`, to read them from the JSON file set through `EXCHANGE_RATES_FILE`,
+//  - `coingecko`, to follow the market rates of the CoinGecko API.
+pub async fn setup_rate_provider(rocket: Rocket<Build>) -> Result<Rocket<Build>, Rocket<Build>> {
+    let provider = env::var("EXCHANGE_RATES_PROVIDER").unwrap_or("static".to_string());
+
+    let client = match provider.as_str() {
+        "file" => {
+            match env::var("EXCHANGE_RATES_FILE") {
+                Ok(path) => {
+                    info!("Reading exchange rates from {}", path);
+                    RateClient::from(FileRates::new(PathBuf::from(path)))
+                }
+                Err(_) => {
+                    error!("Missing `EXCHANGE_RATES_FILE` environment variable to read the exchange rates");
+                    return Err(rocket);
+                }
+            }
+        }
+        "coingecko" => {
+            let endpoint =
+                env::var("COINGECKO_API_URL").unwrap_or(DEFAULT_COINGECKO_URL.to_string());
+            let ttl = env::var("EXCHANGE_RATES_TTL")
+                .ok()
+                .and_then(|seconds| seconds.parse::<u64>().ok())
+                .unwrap_or(DEFAULT_RATES_TTL);
+
+            info!("Following the exchange rates of {}", endpoint);
+            RateClient::from(CoinGeckoRates::new(endpoint, Duration::from_secs(ttl)))
+        }
+        _ => {
+            let rates = env::var("EXCHANGE_RATES").unwrap_or_default();
+
+            match StaticRates::parse(&rates) {
+                Ok(rates) => RateClient::from(rates),
+                Err(entry) => {
+                    error!("Invalid exchange rate `{}` in `EXCHANGE_RATES`", entry);
+                    return Err(rocket);
+                }
+            }
+        }
+    };
+
+    Ok(rocket.manage(client))
+}
@@ -0,0 +1,5 @@
+pub mod client;
+pub mod coingecko;
+pub mod fixed;
+pub mod igniter;
+pub mod provider;
@@ -0,0 +1,11 @@
+use crate::errors::rate::RateError;
+
+/// Provides the exchange rates used to price media in a fiat currency.
+///
+/// A rate is the price of one bitcoin in the currency, e.g: `25000.0` for `EUR`,
+/// so that the amount of satoshis of an invoice follows the market.
+#[rocket::async_trait]
+pub trait RateProvider: Send + Sync {
+    /// The current price of one bitcoin in the currency, provided as an ISO 4217 code
+    async fn rate(&self, currency: &str) -> Result<f64, RateError>;
+}
@@ -0,0 +1,95 @@
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use rocket::http::Header;
+
+/// The characters left as is in an extended parameter value (RFC 5987 `attr-char`)
+const ATTR_CHAR: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'!')
+    .remove(b'#')
+    .remove(b'$')
+    .remove(b'&')
+    .remove(b'+')
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'^')
+    .remove(b'_')
+    .remove(b'`')
+    .remove(b'|')
+    .remove(b'~');
+
+/// How the file should be presented by the client
+pub enum Disposition {
+    Attachment,
+    Inline,
+}
+
+impl Disposition {
+    /// Builds the `Content-Disposition` header of a file (RFC 6266).
+    /// The UTF-8 encoded `filename*` parameter keeps non-ASCII names,
+    /// `filename` is an ASCII fallback for the clients which don't support it.
+    pub fn header(&self, filename: &str) -> Header<'static> {
+        let disposition = match self {
+            Disposition::Attachment => "attachment",
+            Disposition::Inline => "inline",
+        };
+
+        let fallback = filename
+            .chars()
+            .map(|c| match c {
+                '"' | '\\' => '_',
+                ' '..='~' => c,
+                _ => '_',
+            })
+            .collect::<String>();
+
+        Header::new(
+            "Content-Disposition",
+            format!(
+                r#"{}; filename="{}"; filename*=UTF-8''{}"#,
+                disposition,
+                fallback,
+                utf8_percent_encode(filename, ATTR_CHAR)
+            ),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value(disposition: Disposition, filename: &str) -> String {
+        disposition.header(filename).value().to_string()
+    }
+
+    #[test]
+    fn ascii_names_are_kept() {
+        assert_eq!(
+            value(Disposition::Attachment, "track 01.mp3"),
+            r#"attachment; filename="track 01.mp3"; filename*=UTF-8''track%2001.mp3"#
+        );
+        assert_eq!(
+            value(Disposition::Inline, "a!#$&+-.^_`|~b"),
+            r#"inline; filename="a!#$&+-.^_`|~b"; filename*=UTF-8''a!#$&+-.^_`|~b"#
+        );
+    }
+
+    #[test]
+    fn non_ascii_names_are_encoded_with_an_ascii_fallback() {
+        assert_eq!(
+            value(Disposition::Attachment, "résumé €.pdf"),
+            r#"attachment; filename="r_sum_ _.pdf"; filename*=UTF-8''r%C3%A9sum%C3%A9%20%E2%82%AC.pdf"#
+        );
+        assert_eq!(
+            value(Disposition::Attachment, "音楽.flac"),
+            r#"attachment; filename="__.flac"; filename*=UTF-8''%E9%9F%B3%E6%A5%BD.flac"#
+        );
+    }
+
+    #[test]
+    fn quotes_are_escaped() {
+        assert_eq!(
+            value(Disposition::Attachment, r#"say "hi"\bye.txt"#),
+            r#"attachment; filename="say _hi__bye.txt"; filename*=UTF-8''say%20%22hi%22%5Cbye.txt"#
+        );
+    }
+}
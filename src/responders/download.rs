@@ -1,12 +1,426 @@
-use rocket::fs::NamedFile;
-use rocket::http::Header;
-use rocket::Responder;
+use std::path::Path;
 
-type InnerResponder = NamedFile;
+use chrono::{DateTime, Utc};
+use futures::{StreamExt, TryStreamExt};
+use infer::Infer;
+use rocket::http::{ContentType, Header, Status};
+use rocket::response::{self, Responder, Response};
+use rocket::Request;
+use tokio_util::io::StreamReader;
 
-#[derive(Responder)]
-#[response(status = 200)]
+use crate::db::models::media::Media;
+use crate::errors::storage::StorageError;
+use crate::storage::client::StorageClient;
+
+/// Serves a file with support for conditional and range requests.
+///
+/// The responder answers with:
+///  - `200` with the whole file,
+///  - `206` with the requested byte range if a single satisfiable `Range` is provided,
+///  - `304` if the client copy is still fresh (`If-None-Match`, `If-Modified-Since`),
+///  - `416` if the requested range cannot be satisfied.
+///
+/// A `Range` is ignored if the `If-Range` validator does not match the current file.
+/// Only the served bytes are read from the storage.
 pub struct DownloadResponder {
-    pub inner: InnerResponder,
-    pub disposition: Header<'static>,
+    storage: StorageClient,
+    key: String,
+    length: u64,
+    last_modified: DateTime<Utc>,
+    content_type: ContentType,
+    disposition: Header<'static>,
+}
+
+/// A byte range with inclusive bounds
+struct ByteRange {
+    start: u64,
+    end: u64,
+}
+
+impl DownloadResponder {
+    /// Opens the stored file to be served
+    pub async fn open(
+        storage: &StorageClient,
+        key: &str,
+        filename: &str,
+        disposition: Header<'static>,
+    ) -> Result<Self, StorageError> {
+        let metadata = storage.0.metadata(key).await?;
+
+        // The type is detected from the file content,
+        // the extension is only used as a fallback.
+        let head = storage.read_head(key, 8192).await?;
+        let mime_type = Infer::new().get(&head).map(|kind| kind.mime_type());
+        let content_type = Self::content_type_of(mime_type, filename);
+
+        Ok(Self {
+            storage: storage.clone(),
+            key: key.to_string(),
+            length: metadata.size,
+            last_modified: metadata.last_modified,
+            content_type,
+            disposition,
+        })
+    }
+
+    /// Prepares the file of a media to be served from its recorded size and type,
+    /// the storage is only read once the response is sent.
+    /// Media uploaded before these were recorded are opened from the storage.
+    pub async fn open_media(
+        storage: &StorageClient,
+        media: &Media,
+        disposition: Header<'static>,
+    ) -> Result<Self, StorageError> {
+        let size = match media.size_bytes {
+            Some(size) => size as u64,
+            None => {
+                return Self::open(
+                    storage,
+                    &media.storage_key,
+                    &media.original_filename,
+                    disposition,
+                )
+                .await
+            }
+        };
+
+        Ok(Self {
+            storage: storage.clone(),
+            key: media.storage_key.clone(),
+            length: size,
+            // Stored files are never modified once uploaded
+            last_modified: DateTime::from_utc(media.created_at, Utc),
+            content_type: Self::content_type_of(
+                media.mime_type.as_deref(),
+                &media.original_filename,
+            ),
+            disposition,
+        })
+    }
+
+    /// The content type of a file from its detected MIME type, or from its extension.
+    /// The type of the extension is preferred when both match as it carries the charset of texts.
+    fn content_type_of(mime_type: Option<&str>, filename: &str) -> ContentType {
+        let detected = mime_type.and_then(ContentType::parse_flexible);
+        let from_extension = Path::new(filename)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(|extension| ContentType::from_extension(&extension.to_lowercase()));
+
+        match (detected, from_extension) {
+            (Some(detected), Some(from_extension)) if detected == from_extension => from_extension,
+            (Some(detected), _) => detected,
+            (None, from_extension) => from_extension.unwrap_or(ContentType::Binary),
+        }
+    }
+
+    /// A strong validator derived from the file size and modification date
+    fn etag(&self) -> String {
+        format!(
+            "\"{:x}-{:x}\"",
+            self.length,
+            self.last_modified.timestamp_nanos()
+        )
+    }
+
+    fn http_date(date: &DateTime<Utc>) -> String {
+        date.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+    }
+
+    fn parse_http_date(value: &str) -> Option<DateTime<Utc>> {
+        DateTime::parse_from_rfc2822(value)
+            .ok()
+            .map(|date| date.with_timezone(&Utc))
+    }
+
+    /// Checks if the client copy of the file is still fresh
+    fn is_not_modified(&self, request: &Request<'_>) -> bool {
+        let etag = self.etag();
+
+        // If-None-Match takes precedence over If-Modified-Since
+        if let Some(if_none_match) = request.headers().get_one("If-None-Match") {
+            return if_none_match
+                .split(',')
+                .map(|tag| tag.trim().trim_start_matches("W/"))
+                .any(|tag| tag == "*" || tag == etag);
+        }
+
+        match request
+            .headers()
+            .get_one("If-Modified-Since")
+            .and_then(Self::parse_http_date)
+        {
+            Some(since) => self.last_modified.timestamp() <= since.timestamp(),
+            None => false,
+        }
+    }
+
+    /// Checks if a range can be served based on the `If-Range` validator
+    fn is_range_valid(&self, request: &Request<'_>) -> bool {
+        match request.headers().get_one("If-Range") {
+            Some(validator) if validator.trim().starts_with('"') => validator.trim() == self.etag(),
+            Some(validator) => match Self::parse_http_date(validator.trim()) {
+                Some(date) => date.timestamp() == self.last_modified.timestamp(),
+                None => false,
+            },
+            None => true,
+        }
+    }
+
+    /// Parses a `Range` header value.
+    ///
+    /// Returns `None` if the header is not a single `bytes` range, in which case the
+    /// whole file is served, and `Some(Err(()))` if the range cannot be satisfied.
+    fn parse_range(value: &str, length: u64) -> Option<Result<ByteRange, ()>> {
+        let ranges = value.trim().strip_prefix("bytes=")?;
+
+        // Multiple ranges are not supported, the whole file is served instead
+        if ranges.contains(',') {
+            return None;
+        }
+
+        let (start, end) = ranges.trim().split_once('-')?;
+        let (start, end) = (start.trim(), end.trim());
+
+        let range = match (start.is_empty(), end.is_empty()) {
+            // bytes=-500 : the last 500 bytes
+            (true, false) => {
+                let suffix = end.parse::<u64>().ok()?;
+                if suffix == 0 || length == 0 {
+                    return Some(Err(()));
+                }
+                ByteRange {
+                    start: length.saturating_sub(suffix),
+                    end: length - 1,
+                }
+            }
+            // bytes=500- : from the 500th byte to the end
+            (false, true) => ByteRange {
+                start: start.parse::<u64>().ok()?,
+                end: length.saturating_sub(1),
+            },
+            // bytes=500-999
+            (false, false) => {
+                let start = start.parse::<u64>().ok()?;
+                let end = end.parse::<u64>().ok()?;
+                if end < start {
+                    return None;
+                }
+                ByteRange {
+                    start,
+                    end: std::cmp::min(end, length.saturating_sub(1)),
+                }
+            }
+            (true, true) => return None,
+        };
+
+        match range.start < length {
+            true => Some(Ok(range)),
+            false => Some(Err(())),
+        }
+    }
+}
+
+impl<'r> Responder<'r, 'static> for DownloadResponder {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let mut response = Response::build();
+        response
+            .header(self.content_type.clone())
+            .header(self.disposition.clone())
+            .raw_header("Accept-Ranges", "bytes")
+            .raw_header("ETag", self.etag())
+            .raw_header("Last-Modified", Self::http_date(&self.last_modified));
+
+        if self.is_not_modified(request) {
+            return response.status(Status::NotModified).ok();
+        }
+
+        let range = match request.headers().get_one("Range") {
+            Some(range) if self.is_range_valid(request) => Self::parse_range(range, self.length),
+            _ => None,
+        };
+
+        let (status, range) = match range {
+            Some(Ok(range)) => (Status::PartialContent, range),
+            Some(Err(_)) => {
+                return response
+                    .status(Status::RangeNotSatisfiable)
+                    .raw_header("Content-Range", format!("bytes */{}", self.length))
+                    .ok();
+            }
+            None => (
+                Status::Ok,
+                ByteRange {
+                    start: 0,
+                    end: self.length.saturating_sub(1),
+                },
+            ),
+        };
+
+        let size = match self.length {
+            0 => 0,
+            _ => range.end - range.start + 1,
+        };
+
+        if status == Status::PartialContent {
+            response.raw_header(
+                "Content-Range",
+                format!("bytes {}-{}/{}", range.start, range.end, self.length),
+            );
+        }
+
+        // The content is only requested once the response body is polled
+        let (storage, key) = (self.storage, self.key);
+        let chunks = futures::stream::once(async move {
+            match size {
+                0 => Ok(futures::stream::empty().boxed()),
+                _ => storage
+                    .0
+                    .read(&key, Some(range.start..=range.end))
+                    .await
+                    .map_err(|e| std::io::Error::other(e.to_string())),
+            }
+        })
+        .try_flatten();
+        let body = StreamReader::new(chunks);
+
+        response
+            .status(status)
+            .raw_header("Content-Length", size.to_string())
+            .streamed_body(body)
+            .ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use chrono::TimeZone;
+    use rocket::local::blocking::Client;
+
+    use super::*;
+    use crate::storage::local::LocalBackend;
+
+    fn responder() -> DownloadResponder {
+        DownloadResponder {
+            storage: StorageClient::from(LocalBackend::new(env::temp_dir())),
+            key: "sha256/file".to_string(),
+            length: 1000,
+            last_modified: Utc.ymd(2022, 8, 6).and_hms(13, 33, 25),
+            content_type: ContentType::Binary,
+            disposition: Header::new("Content-Disposition", "attachment"),
+        }
+    }
+
+    fn client() -> Client {
+        Client::untracked(rocket::build()).unwrap()
+    }
+
+    fn range(value: &str, length: u64) -> Option<Result<(u64, u64), ()>> {
+        DownloadResponder::parse_range(value, length)
+            .map(|range| range.map(|range| (range.start, range.end)))
+    }
+
+    #[test]
+    fn ranges_are_parsed() {
+        assert_eq!(range("bytes=0-99", 1000), Some(Ok((0, 99))));
+        assert_eq!(range(" bytes= 500 - 999 ", 1000), Some(Ok((500, 999))));
+        // Open-ended ranges are served up to the end of the file
+        assert_eq!(range("bytes=500-", 1000), Some(Ok((500, 999))));
+        assert_eq!(range("bytes=900-1999", 1000), Some(Ok((900, 999))));
+        // Suffix ranges provide the last bytes of the file
+        assert_eq!(range("bytes=-100", 1000), Some(Ok((900, 999))));
+        assert_eq!(range("bytes=-5000", 1000), Some(Ok((0, 999))));
+    }
+
+    #[test]
+    fn out_of_bounds_ranges_are_not_satisfiable() {
+        assert_eq!(range("bytes=1000-", 1000), Some(Err(())));
+        assert_eq!(range("bytes=1000-1999", 1000), Some(Err(())));
+        assert_eq!(range("bytes=-0", 1000), Some(Err(())));
+        assert_eq!(range("bytes=0-", 0), Some(Err(())));
+        assert_eq!(range("bytes=-100", 0), Some(Err(())));
+    }
+
+    #[test]
+    fn other_ranges_serve_the_whole_file() {
+        // Multiple ranges are not supported
+        assert_eq!(range("bytes=0-99,200-299", 1000), None);
+        assert_eq!(range("bytes=99-0", 1000), None);
+        assert_eq!(range("bytes=-", 1000), None);
+        assert_eq!(range("bytes=a-b", 1000), None);
+        assert_eq!(range("items=0-99", 1000), None);
+    }
+
+    #[test]
+    fn fresh_copies_are_not_modified() {
+        let responder = responder();
+        let client = client();
+        let is_not_modified = |name: &'static str, value: String| {
+            let request = client.get("/").header(Header::new(name, value));
+            responder.is_not_modified(request.inner())
+        };
+
+        assert!(!responder.is_not_modified(client.get("/").inner()));
+
+        let etag = responder.etag();
+        assert!(is_not_modified("If-None-Match", etag.clone()));
+        assert!(is_not_modified("If-None-Match", format!("W/{}", etag)));
+        assert!(is_not_modified(
+            "If-None-Match",
+            format!("\"other\", {}", etag)
+        ));
+        assert!(is_not_modified("If-None-Match", "*".to_string()));
+        assert!(!is_not_modified("If-None-Match", "\"other\"".to_string()));
+
+        let last_modified = DownloadResponder::http_date(&responder.last_modified);
+        assert!(is_not_modified("If-Modified-Since", last_modified));
+        assert!(is_not_modified(
+            "If-Modified-Since",
+            "Sun, 07 Aug 2022 00:00:00 GMT".to_string()
+        ));
+        assert!(!is_not_modified(
+            "If-Modified-Since",
+            "Fri, 05 Aug 2022 00:00:00 GMT".to_string()
+        ));
+        assert!(!is_not_modified(
+            "If-Modified-Since",
+            "yesterday".to_string()
+        ));
+    }
+
+    #[test]
+    fn if_none_match_takes_precedence_over_if_modified_since() {
+        let responder = responder();
+        let client = client();
+        let request = client
+            .get("/")
+            .header(Header::new("If-None-Match", "\"other\""))
+            .header(Header::new(
+                "If-Modified-Since",
+                DownloadResponder::http_date(&responder.last_modified),
+            ));
+
+        assert!(!responder.is_not_modified(request.inner()));
+    }
+
+    #[test]
+    fn ranges_are_only_served_for_the_current_file() {
+        let responder = responder();
+        let client = client();
+        let is_range_valid = |value: String| {
+            let request = client.get("/").header(Header::new("If-Range", value));
+            responder.is_range_valid(request.inner())
+        };
+
+        assert!(responder.is_range_valid(client.get("/").inner()));
+        assert!(is_range_valid(responder.etag()));
+        assert!(!is_range_valid("\"other\"".to_string()));
+        assert!(is_range_valid(DownloadResponder::http_date(
+            &responder.last_modified
+        )));
+        assert!(!is_range_valid("Sun, 07 Aug 2022 00:00:00 GMT".to_string()));
+        assert!(!is_range_valid("yesterday".to_string()));
+    }
 }
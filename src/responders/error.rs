@@ -0,0 +1,201 @@
+use rocket::http::{ContentType, Header, Status};
+use rocket::response::{self, Responder, Response};
+use rocket::Request;
+use serde::Serialize;
+use std::io::Cursor;
+
+use crate::lnd::invoice::InvoiceDetails;
+
+/// The machine-readable code of an error replied by the REST routes.
+/// Each code is replied with its own HTTP status.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    BadRequest,
+    Unauthenticated,
+    /// The provided login, download token, preimage or L402 credentials are not valid
+    InvalidCredentials,
+    /// The invoice is settled, the download token minted for it is expected
+    DownloadTokenRequired,
+    PaymentRequired,
+    /// The invoice is paid but held by the node, it is neither settled nor canceled yet
+    InvoiceNotSettled,
+    Forbidden,
+    NotFound,
+    LnUnavailable,
+    RateUnavailable,
+    InternalError,
+}
+
+impl ErrorCode {
+    pub fn status(self) -> Status {
+        match self {
+            ErrorCode::BadRequest => Status::BadRequest,
+            ErrorCode::Unauthenticated
+            | ErrorCode::InvalidCredentials
+            | ErrorCode::DownloadTokenRequired => Status::Unauthorized,
+            ErrorCode::PaymentRequired | ErrorCode::InvoiceNotSettled => Status::PaymentRequired,
+            ErrorCode::Forbidden => Status::Forbidden,
+            ErrorCode::NotFound => Status::NotFound,
+            ErrorCode::LnUnavailable | ErrorCode::RateUnavailable => Status::ServiceUnavailable,
+            ErrorCode::InternalError => Status::InternalServerError,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ErrorBody {
+    code: ErrorCode,
+    message: String,
+    #[serde(flatten)]
+    invoice: Option<InvoiceDetails>,
+}
+
+/// A JSON error replied by the REST routes and catchers, e.g:
+/// `{"code": "PAYMENT_REQUIRED", "message": "...", "payment_request": "...", ...}`.
+///
+/// The invoice fields are only provided along with payment requirements.
+pub struct ErrorResponder {
+    body: ErrorBody,
+    headers: Vec<Header<'static>>,
+}
+
+impl ErrorResponder {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            body: ErrorBody {
+                code,
+                message: message.into(),
+                invoice: None,
+            },
+            headers: vec![],
+        }
+    }
+
+    /// Replies with the invoice to be paid to access a resource
+    pub fn payment_required(invoice: InvoiceDetails) -> Self {
+        let mut error = Self::new(
+            ErrorCode::PaymentRequired,
+            "The payment request shall be paid to access the resource",
+        );
+        error.body.invoice = Some(invoice);
+        error
+    }
+
+    pub fn with_header(mut self, header: Header<'static>) -> Self {
+        self.headers.push(header);
+        self
+    }
+}
+
+impl<'r> Responder<'r, 'static> for ErrorResponder {
+    fn respond_to(self, _: &'r Request<'_>) -> response::Result<'static> {
+        let body = match serde_json::to_string(&self.body) {
+            Ok(body) => body,
+            Err(e) => {
+                error!("Unable to serialize error response: {}", e);
+                return Err(Status::InternalServerError);
+            }
+        };
+
+        let mut response = Response::build();
+        response.status(self.body.code.status());
+        response.header(ContentType::JSON);
+
+        for header in self.headers {
+            response.header_adjoin(header);
+        }
+
+        response.sized_body(body.len(), Cursor::new(body)).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDateTime;
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    fn body(error: &ErrorResponder) -> Value {
+        serde_json::to_value(&error.body).unwrap()
+    }
+
+    #[test]
+    fn every_code_is_replied_with_its_status() {
+        let codes = [
+            (ErrorCode::BadRequest, "BAD_REQUEST", Status::BadRequest),
+            (
+                ErrorCode::Unauthenticated,
+                "UNAUTHENTICATED",
+                Status::Unauthorized,
+            ),
+            (
+                ErrorCode::InvalidCredentials,
+                "INVALID_CREDENTIALS",
+                Status::Unauthorized,
+            ),
+            (
+                ErrorCode::DownloadTokenRequired,
+                "DOWNLOAD_TOKEN_REQUIRED",
+                Status::Unauthorized,
+            ),
+            (
+                ErrorCode::PaymentRequired,
+                "PAYMENT_REQUIRED",
+                Status::PaymentRequired,
+            ),
+            (
+                ErrorCode::InvoiceNotSettled,
+                "INVOICE_NOT_SETTLED",
+                Status::PaymentRequired,
+            ),
+            (ErrorCode::Forbidden, "FORBIDDEN", Status::Forbidden),
+            (ErrorCode::NotFound, "NOT_FOUND", Status::NotFound),
+            (
+                ErrorCode::LnUnavailable,
+                "LN_UNAVAILABLE",
+                Status::ServiceUnavailable,
+            ),
+            (
+                ErrorCode::RateUnavailable,
+                "RATE_UNAVAILABLE",
+                Status::ServiceUnavailable,
+            ),
+            (
+                ErrorCode::InternalError,
+                "INTERNAL_ERROR",
+                Status::InternalServerError,
+            ),
+        ];
+
+        for (code, name, status) in codes {
+            assert_eq!(code.status(), status);
+            assert_eq!(
+                body(&ErrorResponder::new(code, "message")),
+                json!({ "code": name, "message": "message" })
+            );
+        }
+    }
+
+    #[test]
+    fn payment_requirements_provide_the_invoice() {
+        let error = ErrorResponder::payment_required(InvoiceDetails::new(
+            "lnbcrt1",
+            "66687aadf862bd776c8fc18b8e9f8e20089714856ee233b3902a591d0d5f2925",
+            NaiveDateTime::from_timestamp(1_700_000_000, 0),
+        ));
+
+        assert_eq!(
+            body(&error),
+            json!({
+                "code": "PAYMENT_REQUIRED",
+                "message": "The payment request shall be paid to access the resource",
+                "payment_request": "lnbcrt1",
+                "hash": "66687aadf862bd776c8fc18b8e9f8e20089714856ee233b3902a591d0d5f2925",
+                "amount": null,
+                "expires_at": "2023-11-14T22:13:20Z",
+            })
+        );
+    }
+}
@@ -1 +1,5 @@
+pub mod disposition;
 pub mod download;
+pub mod error;
+pub mod tus;
+pub mod zip;
@@ -0,0 +1,41 @@
+use rocket::http::{Header, Status};
+use rocket::response::{self, Responder, Response};
+use rocket::Request;
+
+use crate::guards::tus::TUS_VERSION;
+
+/// An empty response to a tus request, which always provides
+/// the version of the protocol used by the server.
+pub struct TusResponder {
+    status: Status,
+    headers: Vec<Header<'static>>,
+}
+
+impl TusResponder {
+    pub fn new(status: Status) -> Self {
+        Self {
+            status,
+            headers: vec![],
+        }
+    }
+
+    pub fn with_header(mut self, name: &'static str, value: impl Into<String>) -> Self {
+        self.headers.push(Header::new(name, value.into()));
+        self
+    }
+}
+
+impl<'r> Responder<'r, 'static> for TusResponder {
+    fn respond_to(self, _: &'r Request<'_>) -> response::Result<'static> {
+        let mut response = Response::build();
+        response
+            .status(self.status)
+            .raw_header("Tus-Resumable", TUS_VERSION);
+
+        for header in self.headers {
+            response.header(header);
+        }
+
+        response.ok()
+    }
+}
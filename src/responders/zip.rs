@@ -0,0 +1,493 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+use chrono::{DateTime, Datelike, Timelike, Utc};
+use crc32fast::Hasher;
+use futures::StreamExt;
+use rocket::http::{ContentType, Status};
+use rocket::response::{self, Responder, Response};
+use rocket::tokio::io::{self, AsyncWrite, AsyncWriteExt};
+use rocket::Request;
+
+use crate::errors::storage::StorageError;
+use crate::responders::disposition::Disposition;
+use crate::storage::client::StorageClient;
+
+/// Size of the buffer between the archive writer and the response body
+const PIPE_CAPACITY: usize = 64 * 1024;
+
+/// Data descriptor after each file (bit 3) and UTF-8 names (bit 11)
+const ENTRY_FLAGS: u16 = 0x0808;
+
+/// Version needed to extract an entry, 4.5 when it uses the ZIP64 extensions
+const VERSION: u16 = 20;
+const VERSION_ZIP64: u16 = 45;
+
+/// The value of a field whose actual value is provided by a ZIP64 record
+const ZIP64_MARKER: u32 = u32::MAX;
+
+/// Serves several stored files as a single zip archive.
+///
+/// Files are stored without compression and streamed one after the other,
+/// so the archive is never built in memory nor on disk and its length
+/// is known before the first byte is sent. The ZIP64 extensions are used
+/// for the files, offsets and entry counts that exceed the zip limits.
+pub struct ZipResponder {
+    storage: StorageClient,
+    filename: String,
+    entries: Vec<ZipEntry>,
+}
+
+/// A file of the archive
+struct ZipEntry {
+    key: String,
+    name: String,
+    size: u64,
+    last_modified: DateTime<Utc>,
+}
+
+impl ZipEntry {
+    /// Files of 4 GiB or more have their sizes in ZIP64 fields
+    fn is_large(&self) -> bool {
+        self.size >= ZIP64_MARKER as u64
+    }
+}
+
+impl ZipResponder {
+    /// Opens the stored files to be served, provided as their key and filename.
+    /// Filenames are made unique within the archive.
+    pub async fn open(
+        storage: &StorageClient,
+        filename: &str,
+        files: Vec<(String, String)>,
+    ) -> Result<Self, StorageError> {
+        let mut names = HashSet::new();
+        let mut entries = Vec::with_capacity(files.len());
+
+        for (key, name) in files {
+            let metadata = storage.0.metadata(&key).await?;
+
+            entries.push(ZipEntry {
+                key,
+                name: Self::unique_name(&name, &mut names),
+                size: metadata.size,
+                last_modified: metadata.last_modified,
+            });
+        }
+
+        Ok(Self {
+            storage: storage.clone(),
+            filename: filename.to_string(),
+            entries,
+        })
+    }
+
+    /// Makes the name of an entry unique by numbering the duplicates,
+    /// e.g: `track.mp3`, `track (1).mp3`.
+    fn unique_name(name: &str, names: &mut HashSet<String>) -> String {
+        let name = name.replace(['/', '\\'], "_");
+        let path = Path::new(&name);
+        let stem = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or("file")
+            .to_string();
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| format!(".{}", extension))
+            .unwrap_or_default();
+
+        let mut candidate = name.clone();
+        let mut count = 1;
+        while !names.insert(candidate.to_lowercase()) {
+            candidate = format!("{} ({}){}", stem, count, extension);
+            count += 1;
+        }
+
+        candidate
+    }
+
+    /// The length of the archive.
+    /// The records are built without the checksums, which don't change their length.
+    fn length(&self) -> u64 {
+        let mut offset = 0;
+        let mut directory = 0;
+
+        for entry in &self.entries {
+            directory += Self::central_header(entry, offset, 0).len() as u64;
+            offset += Self::local_header(entry).len() as u64
+                + entry.size
+                + Self::data_descriptor(entry, 0).len() as u64;
+        }
+
+        offset + directory + Self::end_records(self.entries.len(), directory, offset).len() as u64
+    }
+
+    /// Writes the archive, reading each file from the storage
+    async fn write<W: AsyncWrite + Unpin>(
+        storage: StorageClient,
+        entries: Vec<ZipEntry>,
+        mut writer: W,
+    ) -> io::Result<()> {
+        let mut offset: u64 = 0;
+        let mut directory = Vec::new();
+
+        for entry in &entries {
+            let header = Self::local_header(entry);
+            writer.write_all(&header).await?;
+
+            let mut hasher = Hasher::new();
+            if entry.size > 0 {
+                let mut chunks = storage
+                    .0
+                    .read(&entry.key, None)
+                    .await
+                    .map_err(|e| io::Error::other(e.to_string()))?;
+
+                while let Some(chunk) = chunks.next().await {
+                    let chunk = chunk?;
+                    hasher.update(&chunk);
+                    writer.write_all(&chunk).await?;
+                }
+            }
+            let crc = hasher.finalize();
+
+            let descriptor = Self::data_descriptor(entry, crc);
+            writer.write_all(&descriptor).await?;
+
+            directory.extend_from_slice(&Self::central_header(entry, offset, crc));
+            offset += header.len() as u64 + entry.size + descriptor.len() as u64;
+        }
+
+        let end = Self::end_records(entries.len(), directory.len() as u64, offset);
+
+        writer.write_all(&directory).await?;
+        writer.write_all(&end).await?;
+        writer.shutdown().await
+    }
+
+    /// The local file header of an entry.
+    /// The checksum and sizes are provided by the data descriptor.
+    fn local_header(entry: &ZipEntry) -> Vec<u8> {
+        let (time, date) = Self::dos_date_time(&entry.last_modified);
+        let name = entry.name.as_bytes();
+        let version = match entry.is_large() {
+            true => VERSION_ZIP64,
+            false => VERSION,
+        };
+
+        let mut header = Vec::with_capacity(30 + name.len() + 20);
+        header.extend_from_slice(&0x04034b50u32.to_le_bytes());
+        header.extend_from_slice(&version.to_le_bytes()); // version needed to extract
+        header.extend_from_slice(&ENTRY_FLAGS.to_le_bytes());
+        header.extend_from_slice(&0u16.to_le_bytes()); // stored, no compression
+        header.extend_from_slice(&time.to_le_bytes());
+        header.extend_from_slice(&date.to_le_bytes());
+        header.extend_from_slice(&0u32.to_le_bytes()); // checksum
+        if entry.is_large() {
+            header.extend_from_slice(&ZIP64_MARKER.to_le_bytes());
+            header.extend_from_slice(&ZIP64_MARKER.to_le_bytes());
+            header.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            header.extend_from_slice(&20u16.to_le_bytes()); // extra field length
+            header.extend_from_slice(name);
+            // ZIP64 extra field, the sizes are provided by the data descriptor
+            header.extend_from_slice(&0x0001u16.to_le_bytes());
+            header.extend_from_slice(&16u16.to_le_bytes());
+            header.extend_from_slice(&[0u8; 16]);
+        } else {
+            header.extend_from_slice(&[0u8; 8]); // sizes
+            header.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            header.extend_from_slice(&0u16.to_le_bytes()); // extra field length
+            header.extend_from_slice(name);
+        }
+
+        header
+    }
+
+    /// The data descriptor following the content of an entry.
+    /// Sizes are written on 8 bytes when the local header has a ZIP64 extra field.
+    fn data_descriptor(entry: &ZipEntry, crc: u32) -> Vec<u8> {
+        let mut descriptor = Vec::with_capacity(24);
+        descriptor.extend_from_slice(&0x08074b50u32.to_le_bytes());
+        descriptor.extend_from_slice(&crc.to_le_bytes());
+        if entry.is_large() {
+            descriptor.extend_from_slice(&entry.size.to_le_bytes());
+            descriptor.extend_from_slice(&entry.size.to_le_bytes());
+        } else {
+            descriptor.extend_from_slice(&(entry.size as u32).to_le_bytes());
+            descriptor.extend_from_slice(&(entry.size as u32).to_le_bytes());
+        }
+
+        descriptor
+    }
+
+    /// The central directory header of an entry written at `offset`.
+    /// Sizes and offsets that don't fit in 4 bytes are provided by a ZIP64 extra field.
+    fn central_header(entry: &ZipEntry, offset: u64, crc: u32) -> Vec<u8> {
+        let (time, date) = Self::dos_date_time(&entry.last_modified);
+        let name = entry.name.as_bytes();
+
+        let mut extra = Vec::new();
+        let size = match entry.is_large() {
+            true => {
+                extra.extend_from_slice(&entry.size.to_le_bytes());
+                extra.extend_from_slice(&entry.size.to_le_bytes());
+                ZIP64_MARKER
+            }
+            false => entry.size as u32,
+        };
+        let local_offset = match offset >= ZIP64_MARKER as u64 {
+            true => {
+                extra.extend_from_slice(&offset.to_le_bytes());
+                ZIP64_MARKER
+            }
+            false => offset as u32,
+        };
+        if !extra.is_empty() {
+            let data = extra;
+            extra = Vec::with_capacity(4 + data.len());
+            extra.extend_from_slice(&0x0001u16.to_le_bytes());
+            extra.extend_from_slice(&(data.len() as u16).to_le_bytes());
+            extra.extend_from_slice(&data);
+        }
+        let version = match extra.is_empty() {
+            true => VERSION,
+            false => VERSION_ZIP64,
+        };
+
+        let mut header = Vec::with_capacity(46 + name.len() + extra.len());
+        header.extend_from_slice(&0x02014b50u32.to_le_bytes());
+        header.extend_from_slice(&version.to_le_bytes()); // version made by
+        header.extend_from_slice(&version.to_le_bytes()); // version needed to extract
+        header.extend_from_slice(&ENTRY_FLAGS.to_le_bytes());
+        header.extend_from_slice(&0u16.to_le_bytes()); // stored, no compression
+        header.extend_from_slice(&time.to_le_bytes());
+        header.extend_from_slice(&date.to_le_bytes());
+        header.extend_from_slice(&crc.to_le_bytes());
+        header.extend_from_slice(&size.to_le_bytes());
+        header.extend_from_slice(&size.to_le_bytes());
+        header.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        header.extend_from_slice(&(extra.len() as u16).to_le_bytes());
+        // comment, disk number, internal and external attributes
+        header.extend_from_slice(&[0u8; 10]);
+        header.extend_from_slice(&local_offset.to_le_bytes());
+        header.extend_from_slice(name);
+        header.extend_from_slice(&extra);
+
+        header
+    }
+
+    /// The records ending the archive, for a central directory of `size` bytes
+    /// written at `offset`. The ZIP64 end of central directory record and its locator
+    /// precede the end of central directory record when one of its fields overflows.
+    fn end_records(count: usize, size: u64, offset: u64) -> Vec<u8> {
+        let is_zip64 = count >= u16::MAX as usize
+            || size >= ZIP64_MARKER as u64
+            || offset >= ZIP64_MARKER as u64;
+
+        let mut end = Vec::with_capacity(56 + 20 + 22);
+        if is_zip64 {
+            end.extend_from_slice(&0x06064b50u32.to_le_bytes());
+            end.extend_from_slice(&44u64.to_le_bytes()); // size of the remaining record
+            end.extend_from_slice(&VERSION_ZIP64.to_le_bytes()); // version made by
+            end.extend_from_slice(&VERSION_ZIP64.to_le_bytes()); // version needed to extract
+            end.extend_from_slice(&[0u8; 8]); // disk numbers
+            end.extend_from_slice(&(count as u64).to_le_bytes());
+            end.extend_from_slice(&(count as u64).to_le_bytes());
+            end.extend_from_slice(&size.to_le_bytes());
+            end.extend_from_slice(&offset.to_le_bytes());
+
+            // locator of the ZIP64 record, written right after the central directory
+            end.extend_from_slice(&0x07064b50u32.to_le_bytes());
+            end.extend_from_slice(&0u32.to_le_bytes()); // disk number
+            end.extend_from_slice(&(offset + size).to_le_bytes());
+            end.extend_from_slice(&1u32.to_le_bytes()); // total number of disks
+        }
+
+        let count = std::cmp::min(count, u16::MAX as usize) as u16;
+        let size = std::cmp::min(size, ZIP64_MARKER as u64) as u32;
+        let offset = std::cmp::min(offset, ZIP64_MARKER as u64) as u32;
+
+        end.extend_from_slice(&0x06054b50u32.to_le_bytes());
+        end.extend_from_slice(&[0u8; 4]); // disk numbers
+        end.extend_from_slice(&count.to_le_bytes());
+        end.extend_from_slice(&count.to_le_bytes());
+        end.extend_from_slice(&size.to_le_bytes());
+        end.extend_from_slice(&offset.to_le_bytes());
+        end.extend_from_slice(&0u16.to_le_bytes()); // comment length
+
+        end
+    }
+
+    /// The MS-DOS time and date of a file, as expected by the zip format
+    fn dos_date_time(date: &DateTime<Utc>) -> (u16, u16) {
+        let time = (date.hour() << 11) | (date.minute() << 5) | (date.second() / 2);
+        let year = std::cmp::max(date.year() - 1980, 0) as u32;
+        let day = (year << 9) | (date.month() << 5) | date.day();
+
+        (time as u16, day as u16)
+    }
+}
+
+impl<'r> Responder<'r, 'static> for ZipResponder {
+    fn respond_to(self, _: &'r Request<'_>) -> response::Result<'static> {
+        let length = self.length();
+        let disposition = Disposition::Attachment.header(&self.filename);
+
+        // The archive is written in the background as the client reads it
+        let (writer, reader) = io::duplex(PIPE_CAPACITY);
+        let (storage, entries) = (self.storage, self.entries);
+        rocket::tokio::spawn(async move {
+            if let Err(e) = Self::write(storage, entries, writer).await {
+                warn!("Archive download interrupted: {}", e);
+            }
+        });
+
+        Response::build()
+            .status(Status::Ok)
+            .header(ContentType::ZIP)
+            .header(disposition)
+            .raw_header("Content-Length", length.to_string())
+            .streamed_body(reader)
+            .ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryInto;
+
+    use bytes::Bytes;
+    use rocket::tokio::fs;
+
+    use super::*;
+    use crate::storage::local::LocalBackend;
+
+    fn entry(name: &str, size: u64) -> ZipEntry {
+        ZipEntry {
+            key: name.to_string(),
+            name: name.to_string(),
+            size,
+            last_modified: Utc::now(),
+        }
+    }
+
+    fn u16_at(data: &[u8], position: usize) -> u16 {
+        u16::from_le_bytes([data[position], data[position + 1]])
+    }
+
+    fn u32_at(data: &[u8], position: usize) -> u32 {
+        u32::from_le_bytes(data[position..position + 4].try_into().unwrap())
+    }
+
+    fn u64_at(data: &[u8], position: usize) -> u64 {
+        u64::from_le_bytes(data[position..position + 8].try_into().unwrap())
+    }
+
+    #[rocket::async_test]
+    async fn writes_the_announced_length() {
+        let root = std::env::temp_dir().join(format!("lnfs-zip-{}", uuid::Uuid::new_v4()));
+        let storage = StorageClient::from(LocalBackend::new(root.clone()));
+        let first = storage
+            .store(Bytes::from_static(b"first file"))
+            .await
+            .unwrap();
+        let second = storage.store(Bytes::new()).await.unwrap();
+
+        let files = vec![
+            (first.key, "track.mp3".to_string()),
+            (second.key, "track.mp3".to_string()),
+        ];
+        let responder = ZipResponder::open(&storage, "bundle.zip", files)
+            .await
+            .unwrap();
+        let length = responder.length();
+
+        let mut archive = Vec::new();
+        ZipResponder::write(storage, responder.entries, &mut archive)
+            .await
+            .unwrap();
+        fs::remove_dir_all(root).await.unwrap();
+
+        assert_eq!(archive.len() as u64, length);
+        assert_eq!(u32_at(&archive, 0), 0x04034b50);
+
+        // End of central directory record, without ZIP64 records
+        let end = archive.len() - 22;
+        assert_eq!(u32_at(&archive, end), 0x06054b50);
+        assert_eq!(u16_at(&archive, end + 10), 2);
+        let directory = u32_at(&archive, end + 16) as usize;
+        assert_eq!(u32_at(&archive, directory), 0x02014b50);
+        assert_eq!(&archive[directory + 46..directory + 46 + 9], b"track.mp3");
+        assert_eq!(
+            u32_at(&archive, directory + 16),
+            crc32fast::hash(b"first file")
+        );
+    }
+
+    #[test]
+    fn large_files_use_zip64_fields() {
+        let large = entry("large.bin", 5 * 1024 * 1024 * 1024);
+
+        let header = ZipResponder::local_header(&large);
+        assert_eq!(u16_at(&header, 4), VERSION_ZIP64);
+        assert_eq!(u32_at(&header, 18), ZIP64_MARKER);
+        assert_eq!(u32_at(&header, 22), ZIP64_MARKER);
+        assert_eq!(u16_at(&header, 28), 20);
+        assert_eq!(u16_at(&header, 30 + 9), 0x0001);
+
+        let descriptor = ZipResponder::data_descriptor(&large, 1);
+        assert_eq!(descriptor.len(), 24);
+        assert_eq!(u64_at(&descriptor, 8), large.size);
+        assert_eq!(u64_at(&descriptor, 16), large.size);
+
+        let header = ZipResponder::central_header(&large, 0, 1);
+        assert_eq!(u32_at(&header, 20), ZIP64_MARKER);
+        assert_eq!(u32_at(&header, 42), 0);
+        let extra = 46 + 9;
+        assert_eq!(u16_at(&header, extra), 0x0001);
+        assert_eq!(u16_at(&header, extra + 2), 16);
+        assert_eq!(u64_at(&header, extra + 4), large.size);
+        assert_eq!(u64_at(&header, extra + 12), large.size);
+    }
+
+    #[test]
+    fn far_offsets_use_zip64_fields() {
+        let small = entry("small.bin", 10);
+        let offset = 5 * 1024 * 1024 * 1024;
+
+        assert_eq!(ZipResponder::local_header(&small).len(), 30 + 9);
+        assert_eq!(ZipResponder::data_descriptor(&small, 1).len(), 16);
+
+        let header = ZipResponder::central_header(&small, offset, 1);
+        assert_eq!(u32_at(&header, 20), 10);
+        assert_eq!(u32_at(&header, 42), ZIP64_MARKER);
+        let extra = 46 + 9;
+        assert_eq!(u16_at(&header, extra + 2), 8);
+        assert_eq!(u64_at(&header, extra + 4), offset);
+
+        assert_eq!(ZipResponder::central_header(&small, 1024, 1).len(), 46 + 9);
+    }
+
+    #[test]
+    fn large_archives_end_with_zip64_records() {
+        assert_eq!(ZipResponder::end_records(2, 100, 1000).len(), 22);
+
+        let (count, size, offset) = (70_000, 5_000_000, 5 * 1024 * 1024 * 1024);
+        let end = ZipResponder::end_records(count, size, offset);
+        assert_eq!(end.len(), 56 + 20 + 22);
+
+        assert_eq!(u32_at(&end, 0), 0x06064b50);
+        assert_eq!(u64_at(&end, 24), count as u64);
+        assert_eq!(u64_at(&end, 40), size);
+        assert_eq!(u64_at(&end, 48), offset);
+
+        assert_eq!(u32_at(&end, 56), 0x07064b50);
+        assert_eq!(u64_at(&end, 64), offset + size);
+
+        assert_eq!(u32_at(&end, 76), 0x06054b50);
+        assert_eq!(u16_at(&end, 86), u16::MAX);
+        assert_eq!(u32_at(&end, 92), ZIP64_MARKER);
+    }
+}
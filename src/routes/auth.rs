@@ -1,4 +1,3 @@
-use chrono::{DateTime, Utc};
 use rocket::{
     form::{Form, Strict},
     http::{Cookie, CookieJar, SameSite, Status}, time::OffsetDateTime,
@@ -6,7 +5,9 @@ use rocket::{
 
 use crate::{
     db::{models::user_token::UserToken, PostgresConn},
+    errors::authentication::AuthenticationError,
     forms::login_user::LoginUser,
+    responders::error::{ErrorCode, ErrorResponder},
 };
 
 use std::env;
@@ -17,37 +18,56 @@ pub async fn login(
     db: PostgresConn,
     cookies: &CookieJar<'_>,
     user_form: Form<Strict<LoginUser>>,
-) -> rocket::http::Status {
+) -> Result<Status, ErrorResponder> {
     let login_user = user_form.into_inner().into_inner();
 
     let session = login_user.login(db).await;
 
     match session {
         Ok(session) => {
-            let expiration = OffsetDateTime::from_unix_timestamp_nanos(session.1.expires_at.timestamp_nanos().into());
-            
+            let expiration = OffsetDateTime::from_unix_timestamp_nanos(
+                session.1.expires_at.timestamp_nanos().into(),
+            )
+            .map_err(|_| session_error())?;
+
             let scope = session.0.role;
             let scope_cookie = Cookie::build("scope", scope.to_string())
                 .same_site(same_site_cookie())
-                .expires(expiration.unwrap())
+                .expires(expiration)
                 .secure(secure_cookie())
                 .finish();
 
-            let token = UserToken::generate_token(session.1).unwrap();
+            let token = UserToken::generate_token(session.1).map_err(|e| {
+                error!("Unable to generate session token: {}", e);
+                session_error()
+            })?;
             let session_cookie = Cookie::build("session", token)
                 .same_site(same_site_cookie())
-                .expires(expiration.unwrap())
+                .expires(expiration)
                 .secure(secure_cookie())
                 .finish();
 
             cookies.add(scope_cookie);
             cookies.add(session_cookie);
-            Status::Ok
+            Ok(Status::Ok)
+        }
+        Err(AuthenticationError::UserNotFound(_) | AuthenticationError::PasswordMismatch(_)) => {
+            Err(ErrorResponder::new(
+                ErrorCode::InvalidCredentials,
+                "Invalid username or password",
+            ))
+        }
+        Err(e) => {
+            error!("Unable to open session: {:?}", e);
+            Err(session_error())
         }
-        Err(_) => Status::ExpectationFailed,
     }
 }
 
+fn session_error() -> ErrorResponder {
+    ErrorResponder::new(ErrorCode::InternalError, "Unable to open a session")
+}
+
 fn same_site_cookie() -> SameSite {
     let cookie_same_site_policy = env::var("COOKIES_SAME_SITE_POLICY");
 
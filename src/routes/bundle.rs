@@ -0,0 +1,204 @@
+use rocket::State;
+use uuid::Uuid;
+
+use crate::{
+    db::{
+        models::{bundle::Bundle, bundle_payment::BundlePayment, download_token::DownloadToken},
+        DbConnection, PostgresConn,
+    },
+    errors::{app::AppError, paywall::PaywallError},
+    graphql::{
+        policy::{self, Action},
+        queries::request_invoice_for_bundle::generate_bundle_payment,
+    },
+    guards::{paywall::PaywallCredentials, userguard::UserGuard},
+    l402::{self, L402Service},
+    lnd::{
+        client::LndClient,
+        invoice::InvoiceDetails,
+        paywall::{PaymentStatus, Paywall},
+        subscriber::InvoiceEvents,
+    },
+    responders::{
+        error::{ErrorCode, ErrorResponder},
+        zip::ZipResponder,
+    },
+    routes::paywall::{
+        download_token_required, invalid_credentials, invoice_not_settled, payment_required,
+    },
+    storage::client::StorageClient,
+    uploads::filename::{sanitize_filename, DEFAULT_FILENAME},
+};
+
+type BundleError = ErrorResponder;
+
+/// A route to download every media of a bundle as a zip archive.
+///
+/// The bundle is unlocked by the download token minted once its payment is settled,
+/// by L402 credentials or by the preimage of its paid invoice.
+#[rocket::get("/bundle/<uuid>")]
+pub async fn get_bundle(
+    uuid: String,
+    credentials: PaywallCredentials,
+    user_guard: UserGuard,
+    db: PostgresConn,
+    lnd: LndClient,
+    storage: StorageClient,
+    invoice_events: &State<InvoiceEvents>,
+) -> Result<ZipResponder, BundleError> {
+    let db = DbConnection::Request(db);
+    let bundle_uuid = match Uuid::parse_str(&uuid) {
+        Ok(uuid) => uuid,
+        Err(_) => {
+            return Err(ErrorResponder::new(
+                ErrorCode::BadRequest,
+                "The provided uuid is not valid",
+            ))
+        }
+    };
+
+    // Unpublished bundles are only served to the users allowed to list them
+    let can_list_all =
+        policy::authorize(user_guard.0.as_ref(), Action::ListAllBundles, None).is_ok();
+    let bundle = match db
+        .run(move |c| Bundle::find_one_by_uuid(bundle_uuid, c))
+        .await
+    {
+        Ok(Some(bundle)) if bundle.published || can_list_all => bundle,
+        Ok(_) => {
+            return Err(ErrorResponder::new(
+                ErrorCode::NotFound,
+                "No bundle found with the provided uuid",
+            ))
+        }
+        Err(_) => return Err(database_error()),
+    };
+
+    if bundle.price_msat > 0 {
+        let paywall = Paywall::new(&db, &lnd, invoice_events);
+        check_bundle_access(&bundle, credentials, &paywall, &db, &lnd).await?;
+    }
+
+    let files = match db.run(move |c| Bundle::find_media(bundle_uuid, c)).await {
+        Ok(media) => media
+            .into_iter()
+            .map(|media| (media.storage_key.clone(), media.filename().to_string()))
+            .collect::<Vec<(String, String)>>(),
+        Err(_) => return Err(database_error()),
+    };
+
+    let filename = format!(
+        "{}.zip",
+        sanitize_filename(&bundle.title).unwrap_or_else(|| DEFAULT_FILENAME.to_string())
+    );
+
+    match ZipResponder::open(&storage, &filename, files).await {
+        Ok(responder) => Ok(responder),
+        Err(e) => {
+            error!("Unable to open the files of bundle {}: {}", bundle.uuid, e);
+            Err(ErrorResponder::new(
+                ErrorCode::InternalError,
+                "Error while reading the files of the bundle",
+            ))
+        }
+    }
+}
+
+/// Checks the provided credentials unlock a paid bundle.
+/// Otherwise replies with the payment requirements.
+async fn check_bundle_access(
+    bundle: &Bundle,
+    credentials: PaywallCredentials,
+    paywall: &Paywall<'_>,
+    db: &DbConnection,
+    lnd: &LndClient,
+) -> Result<(), BundleError> {
+    let service = L402Service::Bundle(bundle.uuid);
+
+    if let Some(token) = credentials.token {
+        return match DownloadToken::decode(&token) {
+            Ok(token) if token.grants_bundle_access(&bundle.uuid) => Ok(()),
+            _ => Err(invalid_credentials(&service, "download token")),
+        };
+    }
+
+    if let Some(authorization) = credentials.l402 {
+        return match l402::verify(&authorization, &service) {
+            Ok(_) => Ok(()),
+            Err(_) => Err(invalid_credentials(&service, "L402 credentials")),
+        };
+    }
+
+    let payment = match credentials.invoice {
+        Some(invoice) => match db
+            .run(move |c| BundlePayment::find_one_by_request(invoice, c))
+            .await
+        {
+            Ok(Some(payment)) if payment.bundle_uuid == bundle.uuid => payment,
+            Ok(Some(_)) => return Err(invoice_not_found()),
+            Ok(None) => return Err(new_payment_required(bundle, lnd, db).await),
+            Err(_) => return Err(database_error()),
+        },
+        None => return Err(new_payment_required(bundle, lnd, db).await),
+    };
+
+    // The preimage of the invoice is a proof of payment
+    let status = match paywall
+        .resolve(&payment, credentials.preimage.as_deref(), None)
+        .await
+    {
+        Ok(status) => status,
+        Err(PaywallError::InvalidPreimage) => {
+            return Err(invalid_credentials(&service, "preimage"))
+        }
+        Err(PaywallError::InvoiceNotFound) => return Err(invoice_not_found()),
+        Err(PaywallError::LightningError(_)) => return Err(lightning_error()),
+    };
+
+    match status {
+        PaymentStatus::Unlocked => Ok(()),
+        PaymentStatus::ProofRequired => Err(download_token_required(&service)),
+        PaymentStatus::Held => Err(invoice_not_settled()),
+        PaymentStatus::Canceled | PaymentStatus::Expired => {
+            Err(new_payment_required(bundle, lnd, db).await)
+        }
+        PaymentStatus::Open => Err(payment_required(
+            &service,
+            InvoiceDetails::new(&payment.request, &payment.hash, payment.expires_at),
+            payment.valid_until,
+        )),
+    }
+}
+
+fn invoice_not_found() -> BundleError {
+    ErrorResponder::new(
+        ErrorCode::NotFound,
+        "No invoice of the bundle found with the provided payment request",
+    )
+}
+
+fn database_error() -> BundleError {
+    ErrorResponder::new(ErrorCode::InternalError, "Error while requesting database")
+}
+
+fn lightning_error() -> BundleError {
+    ErrorResponder::new(
+        ErrorCode::LnUnavailable,
+        "Error while requesting lightning network registry",
+    )
+}
+
+/// Generates a new payment for a bundle and replies with its requirements.
+/// The client secret is provided along with the invoice.
+async fn new_payment_required(bundle: &Bundle, lnd: &LndClient, db: &DbConnection) -> BundleError {
+    match generate_bundle_payment(db, lnd, bundle).await {
+        Ok((payment, secret)) => payment_required(
+            &L402Service::Bundle(bundle.uuid),
+            InvoiceDetails::new(&payment.request, &payment.hash, payment.expires_at)
+                .with_client_secret(secret),
+            payment.valid_until,
+        ),
+        Err(AppError::LnUnavailable) => lightning_error(),
+        Err(_) => database_error(),
+    }
+}
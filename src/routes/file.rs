@@ -1,192 +1,241 @@
-use std::path::Path;
-
-use chrono::Utc;
-use rocket::{
-    fs::NamedFile,
-    http::{Header, Status},
-    response::{content::RawJson, status},
-};
-use tonic::{codegen::InterceptedService, transport::Channel};
-use tonic_lnd::{
-    rpc::{invoice::InvoiceState, lightning_client::LightningClient, Invoice},
-    MacaroonInterceptor,
-};
+use rocket::State;
 use uuid::Uuid;
 
 use crate::{
     db::{
         models::{
-            media::Media,
-            media_payment::{MediaPayment, NewMediaPayment},
+            bundle::Bundle, bundle_payment::BundlePayment, download_token::DownloadToken,
+            media::Media, media_payment::MediaPayment,
         },
-        PostgresConn,
+        DbConnection, PostgresConn,
     },
+    errors::{app::AppError, paywall::PaywallError, storage::StorageError},
+    graphql::queries::request_invoice_for_media::generate_media_payment,
+    guards::paywall::PaywallCredentials,
+    l402::{self, L402Service},
     lnd::{
         client::LndClient,
-        invoice::{InvoiceParams, InvoiceUtils},
+        invoice::InvoiceDetails,
+        paywall::{PaymentStatus, Paywall},
+        subscriber::InvoiceEvents,
+    },
+    rates::client::RateClient,
+    responders::{
+        disposition::Disposition,
+        download::DownloadResponder,
+        error::{ErrorCode, ErrorResponder},
+    },
+    routes::paywall::{
+        download_token_required, invalid_credentials, invoice_not_settled, payment_required,
     },
-    responders::download::DownloadResponder,
+    storage::client::StorageClient,
 };
 
+type FileError = ErrorResponder;
+
 #[derive(Debug)]
 pub enum FileHandlingError {
     MediaNotFound,
     InvoiceNotFound,
     DbFailure,
     LNFailure,
+    RateFailure,
     UuidParsingError,
     PaymentRequired,
 }
 
+impl From<FileHandlingError> for ErrorResponder {
+    fn from(error: FileHandlingError) -> Self {
+        match error {
+            FileHandlingError::MediaNotFound => {
+                ErrorResponder::new(ErrorCode::NotFound, "No media found with the provided uuid")
+            }
+            FileHandlingError::InvoiceNotFound => ErrorResponder::new(
+                ErrorCode::NotFound,
+                "No invoice of the media found with the provided payment request",
+            ),
+            FileHandlingError::DbFailure => {
+                ErrorResponder::new(ErrorCode::InternalError, "Error while requesting database")
+            }
+            FileHandlingError::LNFailure => ErrorResponder::new(
+                ErrorCode::LnUnavailable,
+                "Error while requesting lightning network registry",
+            ),
+            FileHandlingError::RateFailure => ErrorResponder::new(
+                ErrorCode::RateUnavailable,
+                "Unable to convert the price of the media",
+            ),
+            FileHandlingError::UuidParsingError => {
+                ErrorResponder::new(ErrorCode::BadRequest, "The provided uuid is not valid")
+            }
+            FileHandlingError::PaymentRequired => ErrorResponder::new(
+                ErrorCode::PaymentRequired,
+                "The media shall be paid to be accessed",
+            ),
+        }
+    }
+}
+
 /// A route to retrieve files behind the paywall.
-#[rocket::get("/file/<uuid>?<invoice>")]
+#[rocket::get("/file/<uuid>")]
 pub async fn get_file(
     uuid: String,
-    invoice: Option<String>,
+    credentials: PaywallCredentials,
+    db: PostgresConn,
+    lnd: LndClient,
+    rates: RateClient,
+    storage: StorageClient,
+    invoice_events: &State<InvoiceEvents>,
+) -> Result<DownloadResponder, FileError> {
+    let media = get_unlocked_media(uuid, credentials, db, lnd, rates, invoice_events).await?;
+
+    set_download_responder(media, &storage, Disposition::Attachment).await
+}
+
+/// A route to stream files behind the paywall, e.g: to be played
+/// in an HTML5 `<video>` or `<audio>` element.
+#[rocket::get("/stream/<uuid>")]
+pub async fn stream_file(
+    uuid: String,
+    credentials: PaywallCredentials,
+    db: PostgresConn,
+    lnd: LndClient,
+    rates: RateClient,
+    storage: StorageClient,
+    invoice_events: &State<InvoiceEvents>,
+) -> Result<DownloadResponder, FileError> {
+    let media = get_unlocked_media(uuid, credentials, db, lnd, rates, invoice_events).await?;
+
+    set_download_responder(media, &storage, Disposition::Inline).await
+}
+
+/// Retrieves the requested media if it is free or if the provided download token
+/// or L402 credentials grant access to it. Otherwise replies with the payment requirements.
+async fn get_unlocked_media(
+    uuid: String,
+    credentials: PaywallCredentials,
     db: PostgresConn,
     lnd: LndClient,
-) -> Result<DownloadResponder, status::Custom<Option<RawJson<String>>>> {
+    rates: RateClient,
+    invoice_events: &InvoiceEvents,
+) -> Result<Media, FileError> {
+    let db = DbConnection::Request(db);
+
     // Calls the get_media to try to retrieve the requested media from database
-    let media = match get_media(&uuid, &db).await {
-        Ok(media) => media,
-        Err(e) => match e {
-            FileHandlingError::DbFailure => {
-                return Err(status::Custom(Status::InternalServerError, None))
-            }
-            FileHandlingError::MediaNotFound => return Err(status::Custom(Status::NotFound, None)),
-            FileHandlingError::UuidParsingError => {
-                return Err(status::Custom(Status::BadRequest, None))
-            }
-            _ => return Err(status::Custom(Status::ImATeapot, None)),
-        },
-    };
+    let media = get_media(&uuid, &db).await?;
 
     // If the media exists and is free we should deliver it to the user without performing any further operation
-    if media.price == 0 {
-        return set_download_responder(media).await;
+    if media.is_free() {
+        return Ok(media);
     }
 
-    // Otherwise we ensure try to retrieve an associated payment to the requested media.
-    // see get_media_payment for handling process
-    let payment = match get_media_payment(invoice, &media.uuid, &db).await {
-        Ok(payment) => payment,
-        Err(e) => match e {
-            FileHandlingError::DbFailure => {
-                return Err(status::Custom(Status::InternalServerError, None))
-            }
-            FileHandlingError::PaymentRequired => {
-                match request_new_media_payment(&media, lnd, db).await {
-                    Ok(invoice) => {
-                        let data = format!("{{ payment_request: {}}}", invoice.request);
-                        return Err(status::Custom(Status::PaymentRequired, Some(RawJson(data))));
+    let service = L402Service::Media(media.uuid);
+
+    // A download token is minted once the invoice is settled and is the only credential
+    // that unlocks a paid media. Its signature and expiry are checked on decoding.
+    if let Some(token) = credentials.token {
+        return match DownloadToken::decode(&token) {
+            Ok(token) if token.grants_access(&media.uuid) => Ok(media),
+            // A token minted for a bundle unlocks every media of the bundle
+            Ok(token) => match token.bundle {
+                Some(bundle_uuid) if token.grants_bundle_access(&bundle_uuid) => {
+                    match is_in_bundle(bundle_uuid, &media, &db).await? {
+                        true => Ok(media),
+                        false => Err(invalid_credentials(&service, "download token")),
                     }
-                    Err(e) => match e {
-                        FileHandlingError::DbFailure => {
-                            return Err(status::Custom(Status::InternalServerError, None))
-                        }
-                        _ => return Err(status::Custom(Status::ImATeapot, None)),
-                    },
                 }
-            }
-            _ => return Err(status::Custom(Status::ImATeapot, None)),
-        },
-    };
-
-    let invoice = get_invoice(payment.clone(), &lnd.0).await; // .map_err(|error| return error).unwrap();
+                _ => Err(invalid_credentials(&service, "download token")),
+            },
+            _ => Err(invalid_credentials(&service, "download token")),
+        };
+    }
 
-    if invoice.is_err() {
-        return match invoice.unwrap_err() {
-            FileHandlingError::InvoiceNotFound => Err(status::Custom(Status::NotFound, None)),
-            FileHandlingError::LNFailure => Err(status::Custom(Status::InternalServerError, None)),
-            _ => Err(status::Custom(Status::ImATeapot, None)),
+    // L402 credentials embed the proof of payment, no further check is required
+    if let Some(authorization) = credentials.l402 {
+        return match l402::verify(&authorization, &service) {
+            Ok(_) => Ok(media),
+            Err(_) => Err(invalid_credentials(&service, "L402 credentials")),
         };
     }
 
-    let invoice = invoice.unwrap();
+    let paywall = Paywall::new(&db, &lnd, invoice_events);
 
-    match invoice.state() {
-        InvoiceState::Settled => match payment.clone().valid_until {
-            Some(valid_until) => match valid_until >= Utc::now().naive_utc() {
-                true => set_download_responder(media).await,
-                false => match request_new_media_payment(&media, lnd, db).await {
-                    Ok(invoice) => {
-                        let data = format!("{{ payment_request: {}}}", invoice.request);
+    // The preimage of a bundle invoice proves the payment of every media of the bundle
+    if let (Some(invoice), Some(preimage)) = (&credentials.invoice, &credentials.preimage) {
+        if let Some(payment) = get_bundle_payment(invoice.clone(), &media, &db).await? {
+            return match paywall.resolve(&payment, Some(preimage), None).await {
+                Ok(PaymentStatus::Unlocked) => Ok(media),
+                _ => Err(invalid_credentials(&service, "preimage")),
+            };
+        }
+    }
 
-                        Err(status::Custom(Status::PaymentRequired, Some(RawJson(data))))
-                    }
-                    Err(e) => match e {
-                        FileHandlingError::DbFailure => {
-                            Err(status::Custom(Status::InternalServerError, None))
-                        }
-                        _ => Err(status::Custom(Status::ImATeapot, None)),
-                    },
-                },
-            },
-            None => set_download_responder(media).await,
-        },
-        InvoiceState::Accepted => Err(status::Custom(Status::NotFound, None)),
-        InvoiceState::Canceled => {
-            let invoice = request_new_media_payment(&media, lnd, db).await;
-            match invoice {
-                Ok(invoice) => {
-                    let data = format!("{{ payment_request: {}}}", invoice.request);
+    // Otherwise we ensure try to retrieve an associated payment to the requested media.
+    // see get_media_payment for handling process
+    let payment = match get_media_payment(credentials.invoice, &media.uuid, &db).await {
+        Ok(payment) => payment,
+        Err(FileHandlingError::PaymentRequired) => {
+            return Err(new_payment_required(&media, &lnd, &rates, &db).await)
+        }
+        Err(e) => return Err(e.into()),
+    };
 
-                    Err(status::Custom(Status::PaymentRequired, Some(RawJson(data))))
-                }
-                Err(e) => match e {
-                    FileHandlingError::DbFailure => {
-                        Err(status::Custom(Status::InternalServerError, None))
-                    }
-                    _ => Err(status::Custom(Status::ImATeapot, None)),
-                },
-            }
+    // The preimage of the invoice is a proof of payment that spares us a request
+    // to the lightning backend. Otherwise the download token shall be used.
+    let status = match paywall
+        .resolve(&payment, credentials.preimage.as_deref(), None)
+        .await
+    {
+        Ok(status) => status,
+        Err(PaywallError::InvalidPreimage) => {
+            return Err(invalid_credentials(&service, "preimage"))
         }
-        InvoiceState::Open => {
-            let data = format!("{{ payment_request: {}}}", invoice.payment_request);
+        Err(PaywallError::InvoiceNotFound) => return Err(FileHandlingError::InvoiceNotFound.into()),
+        Err(PaywallError::LightningError(_)) => return Err(FileHandlingError::LNFailure.into()),
+    };
 
-            Err(status::Custom(Status::PaymentRequired, Some(RawJson(data))))
+    match status {
+        PaymentStatus::Unlocked => Ok(media),
+        PaymentStatus::ProofRequired => Err(download_token_required(&service)),
+        PaymentStatus::Held => Err(invoice_not_settled()),
+        PaymentStatus::Canceled | PaymentStatus::Expired => {
+            Err(new_payment_required(&media, &lnd, &rates, &db).await)
         }
+        PaymentStatus::Open => Err(payment_required(
+            &service,
+            InvoiceDetails::new(&payment.request, &payment.hash, payment.expires_at),
+            payment.valid_until,
+        )),
     }
 }
 
-/// Generates an invoice and saves its value in databasee
-async fn request_new_media_payment(
+/// Generates a new payment for a media and replies with its requirements.
+/// The client secret is provided along with the invoice so that the client
+/// can later unlock the media once paid.
+async fn new_payment_required(
     media: &Media,
-    lnd_client: LndClient,
-    db: PostgresConn,
-) -> Result<MediaPayment, FileHandlingError> {
-    // Loads the client
-    let client = lnd_client.0;
-
-    // let uuid = Uuid::parse_str(uuid.as_str());
-
-    // Return error if uuid parsing fails.
-    // if uuid.is_err() {
-    //     return Err(FileHandlingError::UuidParsingError);
-    // }
-
-    let uuid = media.uuid.to_owned();
-
-    // Calls utility to generate an invoice/
-    // Todo : Generate
-    let invoice = InvoiceUtils::generate_invoice(
-        client,
-        InvoiceParams::new(Some(media.price.into()), None, None),
-    )
-    .await;
-
-    let media_payment = db
-        .run(move |c| MediaPayment::create(NewMediaPayment::from((invoice, uuid)), c))
-        .await;
-
-    match media_payment {
-        Ok(media_payment) => Ok(media_payment),
-        Err(_) => Err(FileHandlingError::DbFailure),
+    lnd: &LndClient,
+    rates: &RateClient,
+    db: &DbConnection,
+) -> FileError {
+    match generate_media_payment(db, lnd, rates, media.clone()).await {
+        Ok((payment, secret)) => payment_required(
+            &L402Service::Media(media.uuid),
+            InvoiceDetails::new(&payment.request, &payment.hash, payment.expires_at)
+                .with_client_secret(secret),
+            payment.valid_until,
+        ),
+        Err(AppError::RateUnavailable) => {
+            error!("Unable to quote the price of media {}", media.uuid);
+            FileHandlingError::RateFailure.into()
+        }
+        Err(AppError::LnUnavailable) => FileHandlingError::LNFailure.into(),
+        Err(_) => FileHandlingError::DbFailure.into(),
     }
 }
 
 // Retrieves media from database
-async fn get_media(uuid: &String, db: &PostgresConn) -> Result<Media, FileHandlingError> {
+async fn get_media(uuid: &String, db: &DbConnection) -> Result<Media, FileHandlingError> {
     let uuid = Uuid::parse_str(uuid.as_str());
 
     match uuid {
@@ -208,7 +257,7 @@ async fn get_media(uuid: &String, db: &PostgresConn) -> Result<Media, FileHandli
 async fn get_media_payment(
     payment_request: Option<String>,
     media_uuid: &Uuid,
-    db: &PostgresConn,
+    db: &DbConnection,
 ) -> Result<MediaPayment, FileHandlingError> {
     match payment_request {
         // Ensure there is some payment_request provided
@@ -224,7 +273,7 @@ async fn get_media_payment(
                             // Ensure the retrieved payment request matched the requested file association
                             match &payment.media_uuid == media_uuid {
                                 true => Ok(payment),
-                                false => Err(FileHandlingError::MediaNotFound),
+                                false => Err(FileHandlingError::InvoiceNotFound),
                             }
                         }
                         None => Err(FileHandlingError::PaymentRequired),
@@ -237,34 +286,59 @@ async fn get_media_payment(
     }
 }
 
-async fn get_invoice(
-    media_payment: MediaPayment,
-    lnd: &LightningClient<InterceptedService<Channel, MacaroonInterceptor>>,
-) -> Result<Invoice, FileHandlingError> {
-    match InvoiceUtils::get_invoice_state_from_payment_request(lnd, media_payment.request).await {
-        Ok(invoice) => match invoice {
-            Some(invoice) => Ok(invoice),
-            None => Err(FileHandlingError::InvoiceNotFound),
+/// Checks a media belongs to a bundle
+async fn is_in_bundle(
+    bundle_uuid: Uuid,
+    media: &Media,
+    db: &DbConnection,
+) -> Result<bool, FileError> {
+    let media_uuid = media.uuid;
+
+    db.run(move |c| Bundle::contains_media(bundle_uuid, media_uuid, c))
+        .await
+        .map_err(|_| FileHandlingError::DbFailure.into())
+}
+
+/// Retrieves the bundle payment matching a payment request,
+/// if its bundle contains the requested media
+async fn get_bundle_payment(
+    payment_request: String,
+    media: &Media,
+    db: &DbConnection,
+) -> Result<Option<BundlePayment>, FileError> {
+    let payment = db
+        .run(move |c| BundlePayment::find_one_by_request(payment_request, c))
+        .await
+        .map_err(|_| ErrorResponder::from(FileHandlingError::DbFailure))?;
+
+    match payment {
+        Some(payment) => match is_in_bundle(payment.bundle_uuid, media, db).await? {
+            true => Ok(Some(payment)),
+            false => Ok(None),
         },
-        Err(_) => Err(FileHandlingError::LNFailure),
+        None => Ok(None),
     }
 }
 
 async fn set_download_responder(
     media: Media,
-) -> Result<DownloadResponder, status::Custom<Option<RawJson<String>>>> {
-    let path = Path::new(&media.absolute_path);
-    let filename = path.file_name();
+    storage: &StorageClient,
+    disposition: Disposition,
+) -> Result<DownloadResponder, FileError> {
+    let disposition = disposition.header(media.filename());
 
-    match filename {
-        Some(filename) => {
-            let disposition_value =
-                format!(r#"attachment; filename="{}""#, filename.to_str().unwrap());
-            Ok(DownloadResponder {
-                inner: NamedFile::open(path).await.unwrap(),
-                disposition: Header::new("Content-Disposition", disposition_value),
-            })
+    match DownloadResponder::open_media(storage, &media, disposition).await {
+        Ok(responder) => Ok(responder),
+        Err(StorageError::NotFound(_)) => Err(ErrorResponder::new(
+            ErrorCode::NotFound,
+            "The file of the media is not available",
+        )),
+        Err(e) => {
+            error!("Unable to read file of media {}: {}", media.uuid, e);
+            Err(ErrorResponder::new(
+                ErrorCode::InternalError,
+                "Error while reading the file of the media",
+            ))
         }
-        None => Err(status::Custom(Status::InternalServerError, None)),
     }
 }
@@ -0,0 +1,39 @@
+use std::sync::Arc;
+
+use rocket::{http::Status, response::content::RawJson, State};
+
+use crate::lnd::mock::MockBackend;
+
+/// Settles an invoice of the mock lightning backend
+#[rocket::post("/mock/invoice/<hash>/settle")]
+pub async fn settle_invoice(
+    hash: String,
+    mock: &State<Arc<MockBackend>>,
+) -> Result<RawJson<String>, Status> {
+    match mock.settle_invoice(hash.as_str()) {
+        Ok(invoice) => Ok(RawJson(format!(
+            r#"{{"hash": {:?}, "preimage": {:?}}}"#,
+            hash,
+            hex::encode(invoice.r_preimage)
+        ))),
+        Err(_) => Err(Status::NotFound),
+    }
+}
+
+/// Cancels an invoice of the mock lightning backend
+#[rocket::post("/mock/invoice/<hash>/cancel")]
+pub async fn cancel_invoice(hash: String, mock: &State<Arc<MockBackend>>) -> Status {
+    match mock.cancel_invoice(hash.as_str()) {
+        Ok(_) => Status::Ok,
+        Err(_) => Status::NotFound,
+    }
+}
+
+/// Expires an invoice of the mock lightning backend
+#[rocket::post("/mock/invoice/<hash>/expire")]
+pub async fn expire_invoice(hash: String, mock: &State<Arc<MockBackend>>) -> Status {
+    match mock.expire_invoice(hash.as_str()) {
+        Ok(_) => Status::Ok,
+        Err(_) => Status::NotFound,
+    }
+}
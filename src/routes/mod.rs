@@ -1,3 +1,8 @@
 pub mod auth;
+pub mod bundle;
 pub mod file;
+pub mod mock;
+pub mod paywall;
+pub mod preview;
+pub mod tus;
 pub mod utils;
@@ -0,0 +1,68 @@
+use chrono::NaiveDateTime;
+
+use crate::{
+    l402::{self, L402Service},
+    lnd::invoice::InvoiceDetails,
+    responders::error::{ErrorCode, ErrorResponder},
+};
+
+/// Replies with the payment requirements of a paywalled service.
+/// The L402 challenge is provided along the invoice.
+pub fn payment_required(
+    service: &L402Service,
+    invoice: InvoiceDetails,
+    valid_until: Option<NaiveDateTime>,
+) -> ErrorResponder {
+    let challenge = l402::challenge(
+        service,
+        &invoice.hash,
+        &invoice.payment_request,
+        valid_until,
+    );
+    let mut response = ErrorResponder::payment_required(invoice);
+
+    match challenge {
+        Ok(challenge) => response = response.with_header(challenge),
+        Err(e) => warn!("Unable to build the L402 challenge: {}", e),
+    }
+
+    response
+}
+
+/// The provided credentials do not grant access to the service
+pub fn invalid_credentials(service: &L402Service, credentials: &str) -> ErrorResponder {
+    ErrorResponder::new(
+        ErrorCode::InvalidCredentials,
+        format!(
+            "The provided {} does not grant access to the {}",
+            credentials,
+            service.name()
+        ),
+    )
+}
+
+/// A held invoice is accepted until the node settles or cancels it
+pub fn invoice_not_settled() -> ErrorResponder {
+    ErrorResponder::new(
+        ErrorCode::InvoiceNotSettled,
+        "The invoice is accepted but not settled yet",
+    )
+}
+
+/// A settled invoice does not unlock the service by itself,
+/// the client is expected to use the download token minted for it
+/// or to prove the payment with the invoice preimage.
+pub fn download_token_required(service: &L402Service) -> ErrorResponder {
+    let query = match service {
+        L402Service::Bundle(_) => "requestInvoiceForBundle",
+        _ => "requestInvoiceForMedia",
+    };
+
+    ErrorResponder::new(
+        ErrorCode::DownloadTokenRequired,
+        format!(
+            "The invoice is settled, use the download token provided through the {} query or the invoice preimage",
+            query
+        ),
+    )
+}
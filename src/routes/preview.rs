@@ -0,0 +1,76 @@
+use rocket::http::Status;
+use uuid::Uuid;
+
+use crate::{
+    db::{models::media::Media, PostgresConn},
+    errors::storage::StorageError,
+    responders::{disposition::Disposition, download::DownloadResponder},
+    storage::client::StorageClient,
+};
+
+/// A route to retrieve the free preview of a media, e.g: a short clip or the first pages.
+/// The preview is not behind the paywall.
+#[rocket::get("/preview/<uuid>")]
+pub async fn get_preview(
+    uuid: String,
+    db: PostgresConn,
+    storage: StorageClient,
+) -> Result<DownloadResponder, Status> {
+    let media = get_media(&uuid, &db).await?;
+
+    match (&media.preview_key, &media.preview_filename) {
+        (Some(key), Some(filename)) => open_asset(&media, &storage, key, filename).await,
+        _ => Err(Status::NotFound),
+    }
+}
+
+/// A route to retrieve the thumbnail image of a media.
+/// The thumbnail is not behind the paywall.
+#[rocket::get("/preview/<uuid>/thumbnail")]
+pub async fn get_thumbnail(
+    uuid: String,
+    db: PostgresConn,
+    storage: StorageClient,
+) -> Result<DownloadResponder, Status> {
+    let media = get_media(&uuid, &db).await?;
+
+    match &media.thumbnail_key {
+        Some(key) => open_asset(&media, &storage, key, "thumbnail").await,
+        None => Err(Status::NotFound),
+    }
+}
+
+async fn get_media(uuid: &str, db: &PostgresConn) -> Result<Media, Status> {
+    let media_uuid = match Uuid::parse_str(uuid) {
+        Ok(uuid) => uuid,
+        Err(_) => return Err(Status::BadRequest),
+    };
+
+    match db
+        .run(move |c| Media::find_one_by_uuid(media_uuid, c))
+        .await
+    {
+        Ok(Some(media)) => Ok(media),
+        Ok(None) => Err(Status::NotFound),
+        Err(_) => Err(Status::InternalServerError),
+    }
+}
+
+/// Serves an asset of a media inline, so it can be displayed or played directly
+async fn open_asset(
+    media: &Media,
+    storage: &StorageClient,
+    key: &str,
+    filename: &str,
+) -> Result<DownloadResponder, Status> {
+    let disposition = Disposition::Inline.header(filename);
+
+    match DownloadResponder::open(storage, key, filename, disposition).await {
+        Ok(responder) => Ok(responder),
+        Err(StorageError::NotFound(_)) => Err(Status::NotFound),
+        Err(e) => {
+            error!("Unable to read a preview of media {}: {}", media.uuid, e);
+            Err(Status::InternalServerError)
+        }
+    }
+}
@@ -0,0 +1,710 @@
+use std::{env, io::SeekFrom};
+
+use rocket::{
+    data::{Data, ToByteUnit},
+    http::Status,
+    tokio::{
+        fs::OpenOptions,
+        io::{AsyncSeekExt, AsyncWriteExt},
+    },
+    State,
+};
+use uuid::Uuid;
+
+use crate::{
+    db::{
+        models::{
+            upload::{NewUpload, Upload},
+            user::{User, UserRoleEnum},
+        },
+        pool::DbPool,
+    },
+    graphql::policy::{self, Action},
+    guards::{
+        tus::{TusHeaders, TUS_VERSION},
+        userguard::UserGuard,
+    },
+    rates::client::RateClient,
+    responders::tus::TusResponder,
+    storage::client::StorageClient,
+    uploads::{
+        finalizer::spawn_finalization, lock::UploadLock, metadata::parse_metadata,
+        partial::PartialUploads,
+    },
+};
+
+type TusResult = Result<TusResponder, TusResponder>;
+
+/// Default delay - in seconds - for an upload to be complete before it expires
+const DEFAULT_UPLOAD_EXPIRATION: i64 = 86400;
+
+/// Default maximum size - in bytes - of an uploaded file
+const DEFAULT_MAX_SIZE: u64 = 1024 * 1024 * 1024;
+
+/// The content type expected for the chunks of an upload
+const CHUNK_CONTENT_TYPE: &str = "application/offset+octet-stream";
+
+/// Describes the tus protocol implementation of the server.
+#[rocket::options("/tus")]
+pub async fn tus_options() -> TusResponder {
+    TusResponder::new(Status::NoContent)
+        .with_header("Tus-Version", TUS_VERSION)
+        .with_header("Tus-Extension", "creation,termination,expiration")
+        .with_header("Tus-Max-Size", max_size().to_string())
+}
+
+/// A void handler for browsers pre-flight checks on an upload
+#[rocket::options("/tus/<_uuid>")]
+pub async fn tus_upload_options(_uuid: String) -> TusResponder {
+    TusResponder::new(Status::NoContent)
+}
+
+/// Creates a resumable upload.
+/// The file and the media details are announced through the
+/// `Upload-Length` and `Upload-Metadata` headers.
+#[rocket::post("/tus")]
+pub async fn create_upload(
+    tus: TusHeaders,
+    user_guard: UserGuard,
+    db: &State<DbPool>,
+    storage: StorageClient,
+    rates: RateClient,
+    partials: &State<PartialUploads>,
+) -> TusResult {
+    let user = match user_guard.0 {
+        Some(user) => user,
+        None => return Err(TusResponder::new(Status::Unauthorized)),
+    };
+
+    // Uploads are restricted as the `uploadFile` mutation
+    if policy::authorize(Some(&user), Action::UploadMedia, None).is_err() {
+        return Err(TusResponder::new(Status::Forbidden));
+    }
+
+    // Deferred lengths are not supported
+    let length = match tus.upload_length {
+        Some(length) if length <= i64::MAX as u64 => length,
+        _ => return Err(TusResponder::new(Status::BadRequest)),
+    };
+
+    if length > max_size() {
+        return Err(TusResponder::new(Status::PayloadTooLarge));
+    }
+
+    // The media details are checked before any byte is received
+    let metadata = tus.upload_metadata.unwrap_or_default();
+    let file_input = match parse_metadata(&metadata) {
+        Ok(file_input) => file_input,
+        Err(e) => {
+            info!("Rejected upload: {}", e);
+            return Err(TusResponder::new(Status::BadRequest));
+        }
+    };
+
+    if let Err(e) = rates
+        .check_price(
+            Some(file_input.price_msat.0),
+            file_input.price_currency.as_deref(),
+            file_input.price_amount,
+        )
+        .await
+    {
+        info!("Rejected upload: {}", e);
+        return Err(TusResponder::new(Status::BadRequest));
+    }
+
+    let new_upload = NewUpload::new(user.uuid, length as i64, metadata, upload_expiration());
+
+    if let Err(e) = partials.create(&new_upload.uuid).await {
+        error!("Unable to create the partial file of an upload: {}", e);
+        return Err(TusResponder::new(Status::InternalServerError));
+    }
+
+    let upload = match db.run(move |c| Upload::create(new_upload, c)).await {
+        Ok(upload) => upload,
+        Err(e) => {
+            error!("Unable to register an upload: {}", e);
+            return Err(TusResponder::new(Status::InternalServerError));
+        }
+    };
+
+    let response = TusResponder::new(Status::Created)
+        .with_header("Location", format!("/tus/{}", upload.uuid))
+        .with_header("Upload-Expires", http_date(&upload));
+
+    // An empty file is complete as soon as it is announced
+    if upload.is_received() {
+        let lock = lock_upload(&upload, db).await?;
+        spawn_finalization(
+            upload,
+            lock,
+            db.inner().clone(),
+            storage,
+            partials.inner().clone(),
+        );
+    }
+
+    Ok(response)
+}
+
+/// Provides the number of bytes received for an upload so the client can resume it.
+/// The media uuid is provided once the media is created from the complete upload.
+#[rocket::head("/tus/<uuid>")]
+pub async fn get_upload_offset(
+    uuid: String,
+    _tus: TusHeaders,
+    user_guard: UserGuard,
+    db: &State<DbPool>,
+    storage: StorageClient,
+    partials: &State<PartialUploads>,
+) -> TusResult {
+    let upload = get_upload(&uuid, user_guard.0, db).await?;
+    let upload = sync_offset(upload, db, partials).await?;
+
+    // A complete upload left without media, e.g: if the server stopped while creating it,
+    // is completed again unless it is still being completed.
+    if upload.is_received() && !upload.is_complete() {
+        let upload_uuid = upload.uuid;
+        match UploadLock::acquire(db, upload_uuid).await {
+            Ok(Some(lock)) => spawn_finalization(
+                upload.clone(),
+                lock,
+                db.inner().clone(),
+                storage,
+                partials.inner().clone(),
+            ),
+            Ok(None) => (),
+            Err(e) => error!("Unable to lock upload {}: {}", upload_uuid, e),
+        }
+    }
+
+    let response = TusResponder::new(Status::Ok)
+        .with_header("Upload-Offset", upload.upload_offset.to_string())
+        .with_header("Upload-Length", upload.upload_length.to_string())
+        .with_header("Upload-Expires", http_date(&upload))
+        .with_header("Cache-Control", "no-store");
+
+    match upload.media_uuid {
+        Some(media_uuid) => Ok(response.with_header("Media-Uuid", media_uuid.to_string())),
+        None => Ok(response),
+    }
+}
+
+/// Appends a chunk to an upload at the provided `Upload-Offset`.
+/// The media is created in the background once the last chunk is received.
+#[rocket::patch("/tus/<uuid>", data = "<data>")]
+pub async fn patch_upload(
+    uuid: String,
+    data: Data<'_>,
+    tus: TusHeaders,
+    user_guard: UserGuard,
+    db: &State<DbPool>,
+    storage: StorageClient,
+    partials: &State<PartialUploads>,
+) -> TusResult {
+    if tus.content_type.as_deref() != Some(CHUNK_CONTENT_TYPE) {
+        return Err(TusResponder::new(Status::UnsupportedMediaType));
+    }
+
+    let offset = match tus.upload_offset {
+        Some(offset) => offset,
+        None => return Err(TusResponder::new(Status::BadRequest)),
+    };
+
+    let upload = get_upload(&uuid, user_guard.0, db).await?;
+
+    // A single chunk is written at a time for an upload
+    let lock = lock_upload(&upload, db).await?;
+
+    let upload = sync_offset(upload, db, partials).await?;
+
+    if upload.upload_offset as u64 != offset {
+        return Err(TusResponder::new(Status::Conflict)
+            .with_header("Upload-Offset", upload.upload_offset.to_string()));
+    }
+
+    if let Some(media_uuid) = upload.media_uuid {
+        return Ok(TusResponder::new(Status::NoContent)
+            .with_header("Upload-Offset", upload.upload_offset.to_string())
+            .with_header("Media-Uuid", media_uuid.to_string()));
+    }
+
+    // Bytes written after the recorded offset by an interrupted request are discarded
+    let path = partials.path(&upload.uuid);
+    let written = async {
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .await?;
+        file.set_len(offset).await?;
+        file.seek(SeekFrom::End(0)).await?;
+
+        let remaining = upload.upload_length as u64 - offset;
+        let written = data.open(remaining.bytes()).stream_to(&mut file).await;
+        file.flush().await?;
+
+        written
+    }
+    .await;
+
+    // The bytes received are kept even if the request is interrupted,
+    // so that the client can resume the upload from there.
+    let received = match partials.length(&upload.uuid).await {
+        Ok(received) => received,
+        Err(e) => {
+            error!(
+                "Unable to read the partial file of upload {}: {}",
+                upload.uuid, e
+            );
+            return Err(TusResponder::new(Status::InternalServerError));
+        }
+    };
+
+    let upload_uuid = upload.uuid;
+    let upload = match db
+        .run(move |c| Upload::update_offset(upload_uuid, received as i64, c))
+        .await
+    {
+        Ok(upload) => upload,
+        Err(e) => {
+            error!(
+                "Unable to record the offset of upload {}: {}",
+                upload_uuid, e
+            );
+            return Err(TusResponder::new(Status::InternalServerError));
+        }
+    };
+
+    if let Err(e) = written {
+        info!("Upload {} interrupted: {}", upload.uuid, e);
+        return Err(TusResponder::new(Status::BadRequest)
+            .with_header("Upload-Offset", upload.upload_offset.to_string()));
+    }
+
+    let response = TusResponder::new(Status::NoContent)
+        .with_header("Upload-Offset", upload.upload_offset.to_string());
+
+    if upload.is_received() {
+        spawn_finalization(
+            upload,
+            lock,
+            db.inner().clone(),
+            storage,
+            partials.inner().clone(),
+        );
+    }
+
+    Ok(response)
+}
+
+/// Terminates an upload and removes the bytes received for it.
+/// The media created from a complete upload is kept.
+#[rocket::delete("/tus/<uuid>")]
+pub async fn delete_upload(
+    uuid: String,
+    _tus: TusHeaders,
+    user_guard: UserGuard,
+    db: &State<DbPool>,
+    partials: &State<PartialUploads>,
+) -> TusResult {
+    let upload = get_upload(&uuid, user_guard.0, db).await?;
+
+    let _lock = lock_upload(&upload, db).await?;
+
+    let upload_uuid = upload.uuid;
+    if let Err(e) = db.run(move |c| Upload::delete(upload_uuid, c)).await {
+        error!("Unable to delete upload {}: {}", upload_uuid, e);
+        return Err(TusResponder::new(Status::InternalServerError));
+    }
+
+    if let Err(e) = partials.remove(&upload_uuid).await {
+        error!(
+            "Unable to remove the partial file of upload {}: {}",
+            upload_uuid, e
+        );
+    }
+
+    Ok(TusResponder::new(Status::NoContent))
+}
+
+/// Retrieves an upload that has not expired yet.
+/// Only the user who created the upload and the admins can access it,
+/// as long as they are still allowed to upload media.
+async fn get_upload(uuid: &str, user: Option<User>, db: &DbPool) -> Result<Upload, TusResponder> {
+    let user = match user {
+        Some(user) => user,
+        None => return Err(TusResponder::new(Status::Unauthorized)),
+    };
+
+    if policy::authorize(Some(&user), Action::UploadMedia, None).is_err() {
+        return Err(TusResponder::new(Status::Forbidden));
+    }
+
+    let upload_uuid = match Uuid::parse_str(uuid) {
+        Ok(uuid) => uuid,
+        Err(_) => return Err(TusResponder::new(Status::NotFound)),
+    };
+
+    let upload = match db
+        .run(move |c| Upload::find_one_by_uuid(upload_uuid, c))
+        .await
+    {
+        Ok(Some(upload)) => upload,
+        Ok(None) => return Err(TusResponder::new(Status::NotFound)),
+        Err(e) => {
+            error!("Unable to find upload {}: {}", upload_uuid, e);
+            return Err(TusResponder::new(Status::InternalServerError));
+        }
+    };
+
+    if upload.user_uuid != user.uuid && user.role != UserRoleEnum::Admin {
+        return Err(TusResponder::new(Status::Forbidden));
+    }
+
+    if upload.is_expired() && !upload.is_complete() {
+        return Err(TusResponder::new(Status::Gone));
+    }
+
+    Ok(upload)
+}
+
+/// Leases an upload to the current request, answers with a `423` if it is already leased
+async fn lock_upload(upload: &Upload, db: &DbPool) -> Result<UploadLock, TusResponder> {
+    match UploadLock::acquire(db, upload.uuid).await {
+        Ok(Some(lock)) => Ok(lock),
+        Ok(None) => Err(TusResponder::new(Status::Locked)),
+        Err(e) => {
+            error!("Unable to lock upload {}: {}", upload.uuid, e);
+            Err(TusResponder::new(Status::InternalServerError))
+        }
+    }
+}
+
+/// Aligns the recorded offset of an upload on the bytes actually kept,
+/// e.g: if the partial file was lost or truncated while the server was down.
+async fn sync_offset(
+    upload: Upload,
+    db: &DbPool,
+    partials: &PartialUploads,
+) -> Result<Upload, TusResponder> {
+    if upload.is_complete() {
+        return Ok(upload);
+    }
+
+    let received = match partials.length(&upload.uuid).await {
+        Ok(received) => received as i64,
+        Err(e) => {
+            error!(
+                "Unable to read the partial file of upload {}: {}",
+                upload.uuid, e
+            );
+            return Err(TusResponder::new(Status::InternalServerError));
+        }
+    };
+
+    if received >= upload.upload_offset {
+        return Ok(upload);
+    }
+
+    let upload_uuid = upload.uuid;
+    match db
+        .run(move |c| Upload::update_offset(upload_uuid, received, c))
+        .await
+    {
+        Ok(upload) => Ok(upload),
+        Err(e) => {
+            error!(
+                "Unable to record the offset of upload {}: {}",
+                upload_uuid, e
+            );
+            Err(TusResponder::new(Status::InternalServerError))
+        }
+    }
+}
+
+fn upload_expiration() -> i64 {
+    env::var("TUS_UPLOAD_EXPIRATION")
+        .ok()
+        .and_then(|seconds| seconds.parse::<i64>().ok())
+        .unwrap_or(DEFAULT_UPLOAD_EXPIRATION)
+}
+
+fn max_size() -> u64 {
+    env::var("TUS_MAX_SIZE")
+        .ok()
+        .and_then(|size| size.parse::<u64>().ok())
+        .unwrap_or(DEFAULT_MAX_SIZE)
+}
+
+fn http_date(upload: &Upload) -> String {
+    upload
+        .expires_at
+        .format("%a, %d %b %Y %H:%M:%S GMT")
+        .to_string()
+}
+
+/// The routes are run against the database set through `DATABASE_URL`,
+/// which is expected to be migrated, e.g: by starting the server once.
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use rocket::{
+        fairing::AdHoc,
+        http::{Cookie, Header},
+        local::asynchronous::{Client, LocalResponse},
+        tokio::{fs, time::sleep},
+    };
+
+    use super::*;
+    use crate::{
+        db::{
+            igniter::setup_db_pool,
+            models::{
+                media::Media,
+                session::{NewUserSession, UserSession},
+                user::NewUser,
+                user_token::UserToken,
+            },
+            PostgresConn,
+        },
+        storage::local::LocalBackend,
+    };
+
+    struct TestServer {
+        client: Client,
+        db: DbPool,
+        partials: PartialUploads,
+        user: User,
+        session: String,
+    }
+
+    impl TestServer {
+        async fn start() -> Self {
+            if env::var("JWT_TOKEN_SECRET").is_err() {
+                env::set_var("JWT_TOKEN_SECRET", "secret");
+            }
+
+            let url = env::var("DATABASE_URL").expect("DATABASE_URL to be set");
+            let root = env::temp_dir().join(format!("lnfs-tus-{}", Uuid::new_v4()));
+            let partials = PartialUploads::new(root.join("uploads"));
+            fs::create_dir_all(root.join("uploads")).await.unwrap();
+            fs::create_dir_all(root.join("storage")).await.unwrap();
+
+            let figment = rocket::Config::figment()
+                .merge(("databases.main_db.url", url))
+                .merge(("log_level", "off"));
+            let rocket = rocket::custom(figment)
+                .attach(PostgresConn::fairing())
+                .attach(AdHoc::try_on_ignite("Database pool", setup_db_pool))
+                .manage(partials.clone())
+                .manage(StorageClient::from(LocalBackend::new(root.join("storage"))))
+                .mount("/", rocket::routes![get_upload_offset, patch_upload]);
+
+            let client = Client::untracked(rocket).await.unwrap();
+            let db = client.rocket().state::<DbPool>().unwrap().clone();
+
+            let login = format!("tus-{}", Uuid::new_v4());
+            let new_user = NewUser {
+                uuid: Uuid::new_v4(),
+                email: format!("{}@lnfilestore.test", login),
+                login,
+                password: String::new(),
+                role: UserRoleEnum::Publisher,
+            };
+            let user = db.run(move |c| User::create(new_user, c)).await.unwrap();
+            let new_session = NewUserSession::from(&user);
+            let session = db
+                .run(move |c| Ok(UserSession::create(new_session, c)))
+                .await
+                .unwrap()
+                .unwrap();
+
+            Self {
+                client,
+                db,
+                partials,
+                user,
+                session: UserToken::generate_token(session).unwrap(),
+            }
+        }
+
+        async fn create_upload(&self, length: i64) -> Upload {
+            let metadata = format!(
+                "filename {},title {},price_msat {}",
+                base64::encode("notes.txt"),
+                base64::encode("Notes"),
+                base64::encode("0")
+            );
+            let new_upload = NewUpload::new(self.user.uuid, length, metadata, 3600);
+
+            self.partials.create(&new_upload.uuid).await.unwrap();
+            self.db
+                .run(move |c| Upload::create(new_upload, c))
+                .await
+                .unwrap()
+        }
+
+        async fn patch(&self, upload: &Upload, offset: u64, chunk: &[u8]) -> LocalResponse<'_> {
+            self.client
+                .patch(format!("/tus/{}", upload.uuid))
+                .header(Header::new("Tus-Resumable", TUS_VERSION))
+                .header(Header::new("Upload-Offset", offset.to_string()))
+                .header(Header::new("Content-Type", CHUNK_CONTENT_TYPE))
+                .cookie(Cookie::new("session", self.session.clone()))
+                .body(chunk)
+                .dispatch()
+                .await
+        }
+
+        async fn head(&self, upload: &Upload) -> LocalResponse<'_> {
+            self.client
+                .head(format!("/tus/{}", upload.uuid))
+                .header(Header::new("Tus-Resumable", TUS_VERSION))
+                .cookie(Cookie::new("session", self.session.clone()))
+                .dispatch()
+                .await
+        }
+
+        /// Waits for the media created in the background from a complete upload
+        async fn media_uuid(&self, upload: &Upload) -> Option<Uuid> {
+            for _ in 0..50 {
+                let response = self.head(upload).await;
+                if let Some(media_uuid) = response.headers().get_one("Media-Uuid") {
+                    return Uuid::parse_str(media_uuid).ok();
+                }
+
+                sleep(Duration::from_millis(100)).await;
+            }
+
+            None
+        }
+
+        async fn media(&self, media_uuid: Uuid) -> Option<Media> {
+            self.db
+                .run(move |c| Media::find_one_by_uuid(media_uuid, c))
+                .await
+                .unwrap()
+        }
+    }
+
+    fn header(response: &LocalResponse<'_>, name: &str) -> Option<String> {
+        response.headers().get_one(name).map(str::to_string)
+    }
+
+    #[rocket::async_test]
+    #[ignore]
+    async fn chunks_are_only_appended_at_the_current_offset() {
+        let server = TestServer::start().await;
+        let upload = server.create_upload(6).await;
+
+        let response = server.patch(&upload, 0, b"abc").await;
+        assert_eq!(response.status(), Status::NoContent);
+        assert_eq!(header(&response, "Upload-Offset").as_deref(), Some("3"));
+
+        let response = server.patch(&upload, 0, b"def").await;
+        assert_eq!(response.status(), Status::Conflict);
+        assert_eq!(header(&response, "Upload-Offset").as_deref(), Some("3"));
+
+        let response = server.patch(&upload, 4, b"ef").await;
+        assert_eq!(response.status(), Status::Conflict);
+
+        assert_eq!(server.partials.length(&upload.uuid).await.unwrap(), 3);
+    }
+
+    #[rocket::async_test]
+    #[ignore]
+    async fn locked_uploads_are_not_written_concurrently() {
+        let server = TestServer::start().await;
+        let upload = server.create_upload(6).await;
+
+        // A chunk is being written by another request or server instance
+        let lock = UploadLock::acquire(&server.db, upload.uuid)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(UploadLock::acquire(&server.db, upload.uuid)
+            .await
+            .unwrap()
+            .is_none());
+
+        let response = server.patch(&upload, 0, b"abc").await;
+        assert_eq!(response.status(), Status::Locked);
+        assert_eq!(server.partials.length(&upload.uuid).await.unwrap(), 0);
+
+        // The lock is released in the background once dropped
+        drop(lock);
+        let mut status = Status::Locked;
+        for _ in 0..50 {
+            status = server.patch(&upload, 0, b"abc").await.status();
+            if status != Status::Locked {
+                break;
+            }
+
+            sleep(Duration::from_millis(100)).await;
+        }
+
+        assert_eq!(status, Status::NoContent);
+        assert_eq!(server.partials.length(&upload.uuid).await.unwrap(), 3);
+    }
+
+    #[rocket::async_test]
+    #[ignore]
+    async fn complete_uploads_are_finalized_in_the_background() {
+        let server = TestServer::start().await;
+        let upload = server.create_upload(11).await;
+
+        let response = server.patch(&upload, 0, b"hello ").await;
+        assert_eq!(response.status(), Status::NoContent);
+        assert_eq!(header(&response, "Media-Uuid"), None);
+
+        // The last chunk is acknowledged before the media is created
+        let response = server.patch(&upload, 6, b"world").await;
+        assert_eq!(response.status(), Status::NoContent);
+        assert_eq!(header(&response, "Upload-Offset").as_deref(), Some("11"));
+        assert_eq!(header(&response, "Media-Uuid"), None);
+
+        let media_uuid = server
+            .media_uuid(&upload)
+            .await
+            .expect("media to be created");
+        let media = server.media(media_uuid).await.expect("media to exist");
+        assert_eq!(media.title, "Notes");
+        assert_eq!(media.owner_uuid, Some(server.user.uuid));
+        assert_eq!(server.partials.length(&upload.uuid).await.unwrap(), 0);
+
+        // Later requests on the complete upload provide the same media
+        let response = server.patch(&upload, 11, b"").await;
+        assert_eq!(response.status(), Status::NoContent);
+        assert_eq!(
+            header(&response, "Media-Uuid"),
+            Some(media_uuid.to_string())
+        );
+    }
+
+    #[rocket::async_test]
+    #[ignore]
+    async fn complete_uploads_left_without_media_are_finalized_again() {
+        let server = TestServer::start().await;
+        let upload = server.create_upload(5).await;
+
+        // The server stopped once the last chunk was received
+        fs::write(server.partials.path(&upload.uuid), b"hello")
+            .await
+            .unwrap();
+        let upload_uuid = upload.uuid;
+        let upload = server
+            .db
+            .run(move |c| Upload::update_offset(upload_uuid, 5, c))
+            .await
+            .unwrap();
+
+        let media_uuid = server
+            .media_uuid(&upload)
+            .await
+            .expect("media to be created");
+        assert!(server.media(media_uuid).await.is_some());
+    }
+}
@@ -0,0 +1,68 @@
+use std::{ops::RangeInclusive, pin::Pin};
+
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use futures::{stream::BoxStream, Stream};
+
+use crate::errors::storage::StorageError;
+
+/// The content of a stored object, streamed by chunks.
+pub type ObjectStream = BoxStream<'static, std::io::Result<Bytes>>;
+
+/// The content of an object to write, streamed by chunks.
+/// It can be shared between threads as HTTP clients expect of a request body.
+pub type ContentStream = Pin<Box<dyn Stream<Item = std::io::Result<Bytes>> + Send + Sync>>;
+
+/// The metadata of a stored object
+#[derive(Debug, Clone)]
+pub struct ObjectMetadata {
+    pub size: u64,
+    pub last_modified: DateTime<Utc>,
+}
+
+/// Provides the set of operations the server relies on to persist media files.
+///
+/// Files are addressed by a storage key rather than a path, which allows us
+/// to swap the local disk with any shared storage, e.g: an S3 compatible
+/// bucket, so that several server instances can serve the same files.
+#[rocket::async_trait]
+pub trait StorageBackend: Send + Sync {
+    /// Writes an object of the provided length by chunks, without loading it in memory.
+    /// An existing object with the same key is replaced.
+    async fn put_stream(
+        &self,
+        key: &str,
+        content: ContentStream,
+        length: u64,
+    ) -> Result<(), StorageError>;
+
+    /// Moves an object to another key.
+    /// An existing object with the destination key is replaced.
+    async fn rename(&self, from: &str, to: &str) -> Result<(), StorageError>;
+
+    /// Provides the size and modification date of an object
+    async fn metadata(&self, key: &str) -> Result<ObjectMetadata, StorageError>;
+
+    /// Reads an object.
+    /// If a range is provided only the bytes within its inclusive bounds are read.
+    async fn read(
+        &self,
+        key: &str,
+        range: Option<RangeInclusive<u64>>,
+    ) -> Result<ObjectStream, StorageError>;
+
+    /// Deletes an object. Deleting a missing object is not an error.
+    async fn delete(&self, key: &str) -> Result<(), StorageError>;
+
+    /// Lists every stored object along with its metadata
+    async fn list(&self) -> Result<Vec<(String, ObjectMetadata)>, StorageError>;
+
+    /// Provides the key an object is listed with.
+    /// Keys recorded in another form, e.g: as absolute paths, are normalized.
+    fn canonical_key(&self, key: &str) -> String {
+        key.to_string()
+    }
+
+    /// Ensures the storage is reachable and writable
+    async fn health_check(&self) -> Result<(), StorageError>;
+}
@@ -0,0 +1,215 @@
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use bitcoin_hashes::{sha256, Hash, HashEngine};
+use bytes::Bytes;
+use futures::{future, stream, StreamExt};
+use rocket::{
+    http::Status,
+    request::{FromRequest, Outcome},
+    tokio::fs,
+    Request,
+};
+use tokio_util::io::ReaderStream;
+use uuid::Uuid;
+
+use super::backend::{ContentStream, StorageBackend};
+use crate::errors::storage::StorageError;
+
+/// The prefix of the keys of the files stored by their content hash
+pub const CONTENT_ADDRESSED_PREFIX: &str = "sha256/";
+
+/// The prefix of the keys of the files being stored,
+/// before they are moved under their content hash
+pub const PARTIAL_PREFIX: &str = "partial/";
+
+/// A file stored by its content hash
+#[derive(Debug, Clone)]
+pub struct StoredFile {
+    pub key: String,
+    /// The hex encoded SHA-256 of the file content
+    pub checksum: String,
+}
+
+impl StoredFile {
+    fn from_checksum(checksum: String) -> Self {
+        Self {
+            key: format!("{}{}", CONTENT_ADDRESSED_PREFIX, checksum),
+            checksum,
+        }
+    }
+}
+
+/// A handle to the storage backend the media files are written to and read from.
+#[derive(Clone)]
+pub struct StorageClient(pub Arc<dyn StorageBackend>);
+
+impl<B: StorageBackend + 'static> From<B> for StorageClient {
+    fn from(backend: B) -> Self {
+        StorageClient(Arc::new(backend))
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for StorageClient {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match request.rocket().state::<StorageClient>() {
+            Some(client) => Outcome::Success(client.clone()),
+            None => {
+                error!("No storage backend is managed by the server");
+                Outcome::Failure((Status::ServiceUnavailable, ()))
+            }
+        }
+    }
+}
+
+impl StorageClient {
+    /// Stores a file under the SHA-256 of its content.
+    /// A file already stored with the same content is replaced by the new one,
+    /// so that the same bytes are stored once no matter how many media rely on them.
+    pub async fn store(&self, content: Bytes) -> Result<StoredFile, StorageError> {
+        let length = content.len() as u64;
+        self.store_stream(Box::pin(stream::once(future::ready(Ok(content)))), length)
+            .await
+    }
+
+    /// Stores a local file under the SHA-256 of its content.
+    /// The file is read by chunks, so it is never loaded in memory.
+    pub async fn store_file(&self, path: &Path) -> Result<StoredFile, StorageError> {
+        let file = fs::File::open(path).await?;
+        let length = file.metadata().await?.len();
+
+        self.store_stream(Box::pin(ReaderStream::new(file)), length)
+            .await
+    }
+
+    /// Stores a content of the provided length under the SHA-256 of its content.
+    ///
+    /// The content is hashed while it is written under a partial key,
+    /// then the partial file is moved under its content hash.
+    pub async fn store_stream(
+        &self,
+        content: ContentStream,
+        length: u64,
+    ) -> Result<StoredFile, StorageError> {
+        let partial_key = format!("{}{}", PARTIAL_PREFIX, Uuid::new_v4());
+
+        let result = self.store_partial(&partial_key, content, length).await;
+        if result.is_err() {
+            if let Err(e) = self.0.delete(&partial_key).await {
+                warn!("Unable to remove partial file {}: {}", partial_key, e);
+            }
+        }
+
+        result
+    }
+
+    async fn store_partial(
+        &self,
+        partial_key: &str,
+        content: ContentStream,
+        length: u64,
+    ) -> Result<StoredFile, StorageError> {
+        let engine = Arc::new(Mutex::new(sha256::Hash::engine()));
+
+        let hashing = engine.clone();
+        let content = content.inspect(move |chunk| {
+            if let Ok(chunk) = chunk {
+                hashing.lock().unwrap().input(chunk);
+            }
+        });
+        self.0
+            .put_stream(partial_key, Box::pin(content), length)
+            .await?;
+
+        let hash = sha256::Hash::from_engine(engine.lock().unwrap().clone());
+        let stored = StoredFile::from_checksum(hex::encode(hash));
+
+        // A file stored with the same content is replaced rather than kept as is, so that
+        // its modification date is refreshed: the garbage collection then keeps it until
+        // the media relying on it is recorded, even if no media referred to it so far.
+        self.0.rename(partial_key, &stored.key).await?;
+
+        Ok(stored)
+    }
+
+    /// Reads at most the `length` first bytes of an object,
+    /// e.g: to detect its type from its content.
+    pub async fn read_head(&self, key: &str, length: u64) -> Result<Vec<u8>, StorageError> {
+        let mut stream = self.0.read(key, Some(0..=length.saturating_sub(1))).await?;
+
+        let mut head = Vec::new();
+        while let Some(chunk) = stream.next().await {
+            head.extend_from_slice(&chunk?);
+        }
+        head.truncate(length as usize);
+
+        Ok(head)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env,
+        time::{Duration, SystemTime},
+    };
+
+    use chrono::{DateTime, Utc};
+
+    use super::*;
+    use crate::storage::local::LocalBackend;
+
+    #[rocket::async_test]
+    async fn files_are_stored_under_the_hash_of_their_content() {
+        let root = env::temp_dir().join(format!("lnfilestore-storage-test-{}", Uuid::new_v4()));
+        let storage = StorageClient::from(LocalBackend::new(root.clone()));
+
+        let path = root.join("upload");
+        fs::create_dir_all(&root).await.unwrap();
+        fs::write(&path, b"hello world").await.unwrap();
+
+        let stored = storage.store_file(&path).await.unwrap();
+        assert_eq!(
+            stored.checksum,
+            "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9"
+        );
+        assert_eq!(stored.key, format!("sha256/{}", stored.checksum));
+
+        // The same content is stored once, its modification date is refreshed
+        let written_at = SystemTime::now() - Duration::from_secs(86400);
+        std::fs::File::options()
+            .write(true)
+            .open(root.join(&stored.key))
+            .unwrap()
+            .set_modified(written_at)
+            .unwrap();
+
+        let again = storage
+            .store(Bytes::from_static(b"hello world"))
+            .await
+            .unwrap();
+        assert_eq!(again.key, stored.key);
+
+        let metadata = storage.0.metadata(&stored.key).await.unwrap();
+        assert!(metadata.last_modified > DateTime::<Utc>::from(written_at));
+
+        let keys = storage
+            .0
+            .list()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect::<Vec<String>>();
+        assert_eq!(keys.len(), 2);
+        assert!(keys.contains(&stored.key));
+        assert!(!keys.iter().any(|key| key.starts_with(PARTIAL_PREFIX)));
+
+        fs::remove_dir_all(&root).await.unwrap();
+    }
+}
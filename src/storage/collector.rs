@@ -0,0 +1,224 @@
+use std::{collections::HashSet, env, time::Duration};
+
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use rocket::{futures::future::BoxFuture, tokio::time::interval, Orbit, Rocket};
+
+use super::client::{StorageClient, CONTENT_ADDRESSED_PREFIX, PARTIAL_PREFIX};
+use crate::db::{models::media::Media, pool::DbPool};
+
+/// Default delay - in seconds - before the file of a deleted media is purged
+const DEFAULT_DELETION_GRACE_PERIOD: i64 = 604800;
+
+/// Default interval - in seconds - between two collections
+const DEFAULT_COLLECTION_INTERVAL: u64 = 3600;
+
+// Periodically removes from the storage the files that are not needed anymore once the server is launched:
+//  - the files of the media deleted for longer than `MEDIA_DELETION_GRACE_PERIOD`,
+//  - the files no media refers to, if `STORAGE_GC_ORPHANS` is enabled.
+// The collection interval - in seconds - can be set through `STORAGE_GC_INTERVAL`.
+pub fn collect_garbage<'a>(rocket: &'a Rocket<Orbit>) -> BoxFuture<'a, ()> {
+    Box::pin(async move {
+        let storage = match rocket.state::<StorageClient>() {
+            Some(storage) => storage.clone(),
+            None => return,
+        };
+
+        // A connection is only taken from the pool while collecting
+        let db = match rocket.state::<DbPool>() {
+            Some(db) => db.clone(),
+            None => {
+                error!("The storage garbage collection requires the database pool");
+                return;
+            }
+        };
+
+        let seconds = env::var("STORAGE_GC_INTERVAL")
+            .ok()
+            .and_then(|seconds| seconds.parse::<u64>().ok())
+            .unwrap_or(DEFAULT_COLLECTION_INTERVAL);
+
+        let grace_period = ChronoDuration::seconds(
+            env::var("MEDIA_DELETION_GRACE_PERIOD")
+                .ok()
+                .and_then(|seconds| seconds.parse::<i64>().ok())
+                .unwrap_or(DEFAULT_DELETION_GRACE_PERIOD),
+        );
+
+        let collect_orphans = env::var("STORAGE_GC_ORPHANS")
+            .ok()
+            .and_then(|enabled| enabled.parse::<bool>().ok())
+            .unwrap_or(false);
+
+        rocket::tokio::spawn(async move {
+            let mut ticker = interval(Duration::from_secs(seconds));
+
+            loop {
+                ticker.tick().await;
+
+                purge_deleted_media(&db, &storage, grace_period).await;
+
+                if collect_orphans {
+                    remove_orphan_files(&db, &storage, grace_period).await;
+                }
+            }
+        });
+    })
+}
+
+/// Removes the files of the media deleted before the grace period
+async fn purge_deleted_media(db: &DbPool, storage: &StorageClient, grace_period: ChronoDuration) {
+    let deleted_before = (Utc::now() - grace_period).naive_utc();
+    let modified_before = Utc::now() - grace_period;
+
+    let purgeable = match db
+        .run(move |c| Media::find_purgeable(deleted_before, c))
+        .await
+    {
+        Ok(purgeable) => purgeable,
+        Err(e) => {
+            error!("Unable to find the deleted media to purge: {}", e);
+            return;
+        }
+    };
+
+    for media in purgeable {
+        let uuid = media.uuid;
+        let mut purged = true;
+
+        // The file, preview and thumbnail of the media
+        for stored_key in media.storage_keys() {
+            if !purge_file(db, storage, stored_key, uuid, modified_before).await {
+                purged = false;
+            }
+        }
+
+        if !purged {
+            continue;
+        }
+
+        if let Err(e) = db.run(move |c| Media::mark_purged(uuid, c)).await {
+            error!("Unable to record the purge of media {}: {}", uuid, e);
+        }
+    }
+}
+
+/// Removes a file of a deleted media from the storage.
+/// The file is kept as long as another media relies on it,
+/// or if it has been stored again since, e.g: for an upload in progress.
+async fn purge_file(
+    db: &DbPool,
+    storage: &StorageClient,
+    stored_key: String,
+    media_uuid: uuid::Uuid,
+    modified_before: DateTime<Utc>,
+) -> bool {
+    let key = stored_key.clone();
+    let is_shared = db
+        .run(move |c| Media::is_storage_key_shared(key, media_uuid, c))
+        .await;
+
+    match is_shared {
+        Ok(true) => true,
+        Ok(false) if is_modified_after(storage, &stored_key, modified_before).await => true,
+        Ok(false) => match storage.0.delete(&stored_key).await {
+            Ok(_) => true,
+            Err(e) => {
+                error!("Unable to purge file of media {}: {}", media_uuid, e);
+                false
+            }
+        },
+        Err(e) => {
+            error!(
+                "Unable to check the usage of file of media {}: {}",
+                media_uuid, e
+            );
+            false
+        }
+    }
+}
+
+/// Checks a file has been written after the provided date
+async fn is_modified_after(storage: &StorageClient, key: &str, date: DateTime<Utc>) -> bool {
+    match storage.0.metadata(key).await {
+        Ok(metadata) => metadata.last_modified > date,
+        Err(_) => false,
+    }
+}
+
+/// Removes the files no media refers to.
+/// Recent files are kept as they may belong to an upload in progress.
+async fn remove_orphan_files(db: &DbPool, storage: &StorageClient, grace_period: ChronoDuration) {
+    let objects = match storage.0.list().await {
+        Ok(objects) => objects,
+        Err(e) => {
+            error!("Unable to list the stored files: {}", e);
+            return;
+        }
+    };
+
+    let referenced = match db.run(Media::find_storage_keys).await {
+        Ok(keys) => keys
+            .iter()
+            .map(|key| storage.0.canonical_key(key))
+            .collect::<HashSet<String>>(),
+        Err(e) => {
+            error!("Unable to find the stored files in use: {}", e);
+            return;
+        }
+    };
+
+    let modified_before = Utc::now() - grace_period;
+
+    for (key, metadata) in objects {
+        if referenced.contains(&key)
+            || !is_managed_key(&key)
+            || metadata.last_modified > modified_before
+        {
+            continue;
+        }
+
+        match storage.0.delete(&key).await {
+            Ok(_) => info!("Removed orphan file {}", key),
+            Err(e) => error!("Unable to remove orphan file {}: {}", key, e),
+        }
+    }
+}
+
+/// Checks the key is the one of a content addressed file, `sha256/<hex>`,
+/// or of a partial file left by an interrupted write, `partial/<uuid>`,
+/// so that files stored by anyone else are never removed.
+/// Files uploaded before content addressing are only removed along with their media.
+fn is_managed_key(key: &str) -> bool {
+    if let Some(uuid) = key.strip_prefix(PARTIAL_PREFIX) {
+        return uuid::Uuid::parse_str(uuid).is_ok();
+    }
+
+    match key.strip_prefix(CONTENT_ADDRESSED_PREFIX) {
+        Some(checksum) => checksum.len() == 64 && checksum.chars().all(|c| c.is_ascii_hexdigit()),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_content_addressed_keys_are_managed() {
+        let checksum = "a".repeat(64);
+
+        assert!(is_managed_key(&format!("sha256/{}", checksum)));
+        assert!(!is_managed_key(&format!("sha256/{}/x", checksum)));
+        assert!(!is_managed_key("sha256/abc"));
+        assert!(!is_managed_key(&format!("sha256/{}", "z".repeat(64))));
+        assert!(is_managed_key(
+            "partial/4c0f5a36-9a4b-4a52-8a5b-4f1e0a3d9c61"
+        ));
+        assert!(!is_managed_key("partial/track.mp3"));
+        // Files uploaded before content addressing and files stored by anyone else
+        assert!(!is_managed_key(
+            "4c0f5a36-9a4b-4a52-8a5b-4f1e0a3d9c61/track.mp3"
+        ));
+        assert!(!is_managed_key("systemd-private/tmp.txt"));
+    }
+}
@@ -0,0 +1,121 @@
+use std::{env, path::PathBuf};
+
+use rocket::{Build, Config, Rocket};
+
+use super::{
+    client::StorageClient,
+    local::LocalBackend,
+    s3::{S3Backend, S3Config},
+};
+
+// Creates the storage backend shared by every request on ignite of the server.
+// Files are stored on the local disk unless `STORAGE_BACKEND` env var is set to `s3`,
+// in which case they are stored in an S3 compatible bucket.
+pub async fn setup_storage_backend(rocket: Rocket<Build>) -> Result<Rocket<Build>, Rocket<Build>> {
+    let backend = env::var("STORAGE_BACKEND").unwrap_or("local".to_string());
+
+    let client = match backend.as_str() {
+        "s3" => {
+            let config = match s3_config() {
+                Ok(config) => config,
+                Err(variable) => {
+                    error!(
+                        "Missing `{}` environment variable to connect to the S3 bucket",
+                        variable
+                    );
+                    return Err(rocket);
+                }
+            };
+
+            let bucket = config.bucket.clone();
+
+            match S3Backend::new(config) {
+                Ok(backend) => {
+                    info!("Storing files in the `{}` S3 bucket", bucket);
+                    StorageClient::from(backend)
+                }
+                Err(e) => {
+                    error!("Unable to set up the S3 storage: {}", e);
+                    return Err(rocket);
+                }
+            }
+        }
+        _ => {
+            let root = match local_root(&rocket) {
+                Ok(root) => root,
+                Err(e) => {
+                    error!("Unable to set up the local storage: {}", e);
+                    return Err(rocket);
+                }
+            };
+
+            info!("Storing files in {}", root.display());
+            StorageClient::from(LocalBackend::new(root))
+        }
+    };
+
+    if let Err(e) = client.0.health_check().await {
+        error!("The storage backend is not available: {}", e);
+        return Err(rocket);
+    }
+
+    Ok(rocket.manage(client))
+}
+
+/// Provides the directory dedicated to the files of the `local` backend.
+///
+/// Without `STORAGE_LOCAL_PATH`, files are stored where uploads were persisted
+/// before the storage backends: the Rocket temporary directory, relative to the
+/// working directory. As the files it holds can be collected, the root and
+/// the temporary directory of the system are refused.
+fn local_root(rocket: &Rocket<Build>) -> Result<PathBuf, String> {
+    let root = match env::var("STORAGE_LOCAL_PATH") {
+        Ok(root) => PathBuf::from(root),
+        Err(_) => {
+            let config = rocket
+                .figment()
+                .extract::<Config>()
+                .map_err(|e| format!("unable to read the Rocket configuration: {}", e))?;
+            let root = env::current_dir()
+                .map_err(|e| format!("unable to get the working directory: {}", e))?
+                .join(config.temp_dir.original());
+            warn!(
+                "`STORAGE_LOCAL_PATH` is not set, files are stored in the upload directory {}",
+                root.display()
+            );
+            root
+        }
+    };
+
+    if !root.is_absolute() || root.parent().is_none() || root == env::temp_dir() {
+        return Err(format!(
+            "`STORAGE_LOCAL_PATH` shall be an absolute path to a dedicated directory, {} is not",
+            root.display()
+        ));
+    }
+
+    Ok(root)
+}
+
+fn s3_config() -> Result<S3Config, &'static str> {
+    let bucket = env::var("S3_BUCKET").map_err(|_| "S3_BUCKET")?;
+    let access_key = env::var("S3_ACCESS_KEY_ID").map_err(|_| "S3_ACCESS_KEY_ID")?;
+    let secret_key = env::var("S3_SECRET_ACCESS_KEY").map_err(|_| "S3_SECRET_ACCESS_KEY")?;
+    let region = env::var("S3_REGION").unwrap_or("us-east-1".to_string());
+    let endpoint =
+        env::var("S3_ENDPOINT").unwrap_or_else(|_| format!("https://s3.{}.amazonaws.com", region));
+
+    let path_style = env::var("S3_PATH_STYLE")
+        .unwrap_or("true".to_string())
+        .parse::<bool>()
+        .unwrap_or(true);
+
+    Ok(S3Config {
+        endpoint,
+        bucket,
+        region,
+        access_key,
+        secret_key,
+        path_style,
+    })
+}
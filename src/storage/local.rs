@@ -0,0 +1,198 @@
+use std::{
+    io::SeekFrom,
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use rocket::tokio::{
+    fs,
+    io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt},
+};
+use tokio_util::io::ReaderStream;
+
+use super::backend::{ContentStream, ObjectMetadata, ObjectStream, StorageBackend};
+use crate::errors::storage::StorageError;
+
+/// Stores the objects as files under a root directory of the local disk.
+///
+/// The key of an object is its path relative to the root directory.
+/// Keys recorded as absolute paths are resolved as is.
+pub struct LocalBackend {
+    root: PathBuf,
+}
+
+impl LocalBackend {
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.root.join(key)
+    }
+}
+
+#[rocket::async_trait]
+impl StorageBackend for LocalBackend {
+    async fn put_stream(
+        &self,
+        key: &str,
+        mut content: ContentStream,
+        _length: u64,
+    ) -> Result<(), StorageError> {
+        let path = self.path(key);
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+
+        let mut file = fs::File::create(path).await?;
+        while let Some(chunk) = content.next().await {
+            file.write_all(&chunk?).await?;
+        }
+        file.flush().await?;
+
+        Ok(())
+    }
+
+    async fn rename(&self, from: &str, to: &str) -> Result<(), StorageError> {
+        let destination = self.path(to);
+
+        if let Some(parent) = destination.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+
+        fs::rename(self.path(from), destination)
+            .await
+            .map_err(|e| not_found_or_io(from, e))
+    }
+
+    async fn metadata(&self, key: &str) -> Result<ObjectMetadata, StorageError> {
+        let metadata = fs::metadata(self.path(key))
+            .await
+            .map_err(|e| not_found_or_io(key, e))?;
+
+        Ok(ObjectMetadata {
+            size: metadata.len(),
+            last_modified: metadata
+                .modified()
+                .map(DateTime::<Utc>::from)
+                .unwrap_or_else(|_| Utc::now()),
+        })
+    }
+
+    async fn read(
+        &self,
+        key: &str,
+        range: Option<RangeInclusive<u64>>,
+    ) -> Result<ObjectStream, StorageError> {
+        let mut file = fs::File::open(self.path(key))
+            .await
+            .map_err(|e| not_found_or_io(key, e))?;
+
+        let stream = match range {
+            Some(range) => {
+                file.seek(SeekFrom::Start(*range.start())).await?;
+                let length = range.end().saturating_sub(*range.start()) + 1;
+                ReaderStream::new(file.take(length)).boxed()
+            }
+            None => ReaderStream::new(file).boxed(),
+        };
+
+        Ok(stream)
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StorageError> {
+        let path = self.path(key);
+
+        match fs::remove_file(&path).await {
+            Ok(_) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e.into()),
+        }
+
+        // Files are stored in their own directory which is removed once empty.
+        // Directories outside of the root, e.g: of keys recorded as absolute paths, are kept.
+        if let Some(parent) = path.parent() {
+            if parent != self.root && parent.starts_with(&self.root) && !key.contains("..") {
+                let _ = fs::remove_dir(parent).await;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn list(&self) -> Result<Vec<(String, ObjectMetadata)>, StorageError> {
+        let mut objects = vec![];
+        let mut directories = vec![self.root.clone()];
+
+        while let Some(directory) = directories.pop() {
+            // Directories we are not allowed to read can't hold our files
+            let mut entries = match fs::read_dir(&directory).await {
+                Ok(entries) => entries,
+                Err(_) => continue,
+            };
+
+            while let Some(entry) = entries.next_entry().await? {
+                // Symbolic links are not followed
+                let file_type = match entry.file_type().await {
+                    Ok(file_type) => file_type,
+                    Err(_) => continue,
+                };
+
+                if file_type.is_dir() {
+                    directories.push(entry.path());
+                    continue;
+                }
+
+                let metadata = match entry.metadata().await {
+                    Ok(metadata) if file_type.is_file() => metadata,
+                    _ => continue,
+                };
+
+                if let Ok(key) = entry.path().strip_prefix(&self.root) {
+                    objects.push((
+                        key.to_string_lossy().to_string(),
+                        ObjectMetadata {
+                            size: metadata.len(),
+                            last_modified: metadata
+                                .modified()
+                                .map(DateTime::<Utc>::from)
+                                .unwrap_or_else(|_| Utc::now()),
+                        },
+                    ));
+                }
+            }
+        }
+
+        Ok(objects)
+    }
+
+    fn canonical_key(&self, key: &str) -> String {
+        match Path::new(key).strip_prefix(&self.root) {
+            Ok(relative) => relative.to_string_lossy().to_string(),
+            Err(_) => key.to_string(),
+        }
+    }
+
+    async fn health_check(&self) -> Result<(), StorageError> {
+        fs::create_dir_all(&self.root).await?;
+
+        let metadata = fs::metadata(&self.root).await?;
+        match metadata.permissions().readonly() {
+            true => Err(StorageError::IoError(format!(
+                "{} is read only",
+                self.root.display()
+            ))),
+            false => Ok(()),
+        }
+    }
+}
+
+fn not_found_or_io(key: &str, error: std::io::Error) -> StorageError {
+    match error.kind() {
+        std::io::ErrorKind::NotFound => StorageError::NotFound(key.to_string()),
+        _ => error.into(),
+    }
+}
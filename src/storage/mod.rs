@@ -0,0 +1,6 @@
+pub mod backend;
+pub mod client;
+pub mod collector;
+pub mod igniter;
+pub mod local;
+pub mod s3;
@@ -0,0 +1,56 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use rocket::{futures::future::BoxFuture, tokio::time::interval, Orbit, Rocket};
+
+use super::partial::PartialUploads;
+use crate::db::{models::upload::Upload, pool::DbPool};
+
+/// Interval - in seconds - between two removals of the expired uploads
+const EXPIRED_UPLOADS_INTERVAL: u64 = 3600;
+
+// Periodically removes the expired uploads along with the bytes received for them
+// once the server is launched. Media created from complete uploads are kept.
+pub fn remove_expired_uploads<'a>(rocket: &'a Rocket<Orbit>) -> BoxFuture<'a, ()> {
+    Box::pin(async move {
+        // A connection is only taken from the pool while removing the expired uploads
+        let db = match rocket.state::<DbPool>() {
+            Some(db) => db.clone(),
+            None => {
+                error!("Removing the expired uploads requires the database pool");
+                return;
+            }
+        };
+
+        let partials = match rocket.state::<PartialUploads>() {
+            Some(partials) => partials.clone(),
+            None => return,
+        };
+
+        rocket::tokio::spawn(async move {
+            let mut ticker = interval(Duration::from_secs(EXPIRED_UPLOADS_INTERVAL));
+
+            loop {
+                ticker.tick().await;
+
+                let now = Utc::now().naive_utc();
+                let expired = match db.run(move |c| Upload::delete_expired(now, c)).await {
+                    Ok(expired) => expired,
+                    Err(e) => {
+                        error!("Unable to remove the expired uploads: {}", e);
+                        continue;
+                    }
+                };
+
+                for upload in expired {
+                    if let Err(e) = partials.remove(&upload.uuid).await {
+                        error!(
+                            "Unable to remove the partial file of upload {}: {}",
+                            upload.uuid, e
+                        );
+                    }
+                }
+            }
+        });
+    })
+}
@@ -0,0 +1,94 @@
+/// Name given to an uploaded file which name can't be kept
+pub const DEFAULT_FILENAME: &str = "file";
+
+/// Maximum length - in bytes - of a filename, as supported by most file systems
+const MAX_FILENAME_LENGTH: usize = 255;
+
+/// Maximum length - in bytes - of an extension kept when a filename is shortened
+const MAX_EXTENSION_LENGTH: usize = 16;
+
+/// Cleans up a filename provided by a client before it is stored and sent back to downloaders:
+/// the directories and control characters are removed, as well as the leading and trailing
+/// dots and spaces. Overly long names are shortened, keeping their extension.
+/// Returns `None` if nothing is left of the name.
+pub fn sanitize_filename(name: &str) -> Option<String> {
+    let name = name.rsplit(['/', '\\']).next().unwrap_or_default();
+    let name = name.chars().filter(|c| !c.is_control()).collect::<String>();
+    let name = name.trim_matches(|c: char| c == '.' || c.is_whitespace());
+
+    match name.is_empty() {
+        true => None,
+        false => Some(shorten(name)),
+    }
+}
+
+fn shorten(name: &str) -> String {
+    if name.len() <= MAX_FILENAME_LENGTH {
+        return name.to_string();
+    }
+
+    let (stem, extension) = match name.rfind('.') {
+        Some(index) if name.len() - index <= MAX_EXTENSION_LENGTH => name.split_at(index),
+        _ => (name, ""),
+    };
+
+    let mut end = MAX_FILENAME_LENGTH - extension.len();
+    while !stem.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    format!("{}{}", stem[..end].trim_end(), extension)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn directories_and_control_characters_are_removed() {
+        assert_eq!(
+            sanitize_filename("../../etc/passwd"),
+            Some("passwd".to_string())
+        );
+        assert_eq!(
+            sanitize_filename(r"C:\Users\satoshi\white paper.pdf"),
+            Some("white paper.pdf".to_string())
+        );
+        assert_eq!(
+            sanitize_filename("in\u{0}voice\n.pdf"),
+            Some("invoice.pdf".to_string())
+        );
+        assert_eq!(sanitize_filename(" .hidden. "), Some("hidden".to_string()));
+        assert_eq!(
+            sanitize_filename("résumé.pdf"),
+            Some("résumé.pdf".to_string())
+        );
+    }
+
+    #[test]
+    fn empty_names_are_not_kept() {
+        assert_eq!(sanitize_filename(""), None);
+        assert_eq!(sanitize_filename("uploads/"), None);
+        assert_eq!(sanitize_filename(" .. "), None);
+    }
+
+    #[test]
+    fn long_names_are_shortened_keeping_their_extension() {
+        let shortened = shorten(&format!("{}.mp3", "a".repeat(300)));
+        assert_eq!(shortened.len(), MAX_FILENAME_LENGTH);
+        assert!(shortened.ends_with("a.mp3"));
+
+        // Overly long extensions are not kept
+        let extension = format!(".{}", "x".repeat(MAX_EXTENSION_LENGTH));
+        let shortened = shorten(&format!("{}{}", "a".repeat(300), extension));
+        assert_eq!(shortened, "a".repeat(MAX_FILENAME_LENGTH));
+    }
+
+    #[test]
+    fn long_names_are_shortened_on_character_boundaries() {
+        // 'é' takes 2 bytes, so the limit falls in the middle of a character
+        let shortened = shorten(&format!("{}.txt", "é".repeat(200)));
+        assert_eq!(shortened, format!("{}.txt", "é".repeat(125)));
+        assert!(shortened.len() <= MAX_FILENAME_LENGTH);
+    }
+}
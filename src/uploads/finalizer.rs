@@ -0,0 +1,68 @@
+use crate::{
+    db::{
+        models::{
+            media::{Media, NewMedia},
+            upload::Upload,
+        },
+        pool::DbPool,
+    },
+    errors::upload::UploadError,
+    extractor::{apply_metadata, extract_from_file},
+    storage::client::StorageClient,
+};
+
+use super::{lock::UploadLock, metadata::parse_metadata, partial::PartialUploads};
+
+/// Completes an upload in the background once all its bytes are received,
+/// the lock is held until the media is created.
+///
+/// Hashing, storing and extracting a large file takes a while, so the client is not
+/// kept waiting: the media uuid is provided through the upload offset once created.
+pub fn spawn_finalization(
+    upload: Upload,
+    lock: UploadLock,
+    db: DbPool,
+    storage: StorageClient,
+    partials: PartialUploads,
+) {
+    rocket::tokio::spawn(async move {
+        match finalize_upload(&upload, &db, &storage, &partials).await {
+            Ok(media) => info!("Upload {} completed as media {}", upload.uuid, media.uuid),
+            Err(e) => error!("Unable to complete upload {}: {}", upload.uuid, e),
+        }
+
+        drop(lock);
+    });
+}
+
+/// Stores the file of a complete upload and creates its media
+/// along with the metadata extracted from the file.
+/// The partial file is removed once the media is created.
+pub async fn finalize_upload(
+    upload: &Upload,
+    db: &DbPool,
+    storage: &StorageClient,
+    partials: &PartialUploads,
+) -> Result<Media, UploadError> {
+    let file_input = parse_metadata(&upload.metadata)?;
+    let stored = storage.store_file(&partials.path(&upload.uuid)).await?;
+
+    let filename = file_input.filename.clone();
+    let metadata = extract_from_file(partials.path(&upload.uuid), filename.clone()).await;
+    let new_media = NewMedia::from((stored, filename, file_input)).with_owner(upload.user_uuid);
+    let new_media = apply_metadata(new_media, metadata, storage).await;
+    let media = db.run(move |c| Media::create(new_media, c)).await?;
+
+    let (upload_uuid, media_uuid) = (upload.uuid, media.uuid);
+    db.run(move |c| Upload::complete(upload_uuid, media_uuid, c))
+        .await?;
+
+    if let Err(e) = partials.remove(&upload.uuid).await {
+        warn!(
+            "Unable to remove the partial file of upload {}: {}",
+            upload.uuid, e
+        );
+    }
+
+    Ok(media)
+}
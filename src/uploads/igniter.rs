@@ -0,0 +1,45 @@
+use std::{env, path::PathBuf};
+
+use rocket::{tokio::fs, Build, Rocket};
+
+use super::partial::PartialUploads;
+
+// Prepares the directory holding the uploads in progress on ignite of the server.
+// The directory is set through `TUS_UPLOADS_PATH` and should be kept across restarts
+// so that uploads can be resumed.
+pub async fn setup_partial_uploads(rocket: Rocket<Build>) -> Result<Rocket<Build>, Rocket<Build>> {
+    let root = match uploads_root() {
+        Ok(root) => root,
+        Err(e) => {
+            error!("Unable to set up the uploads directory: {}", e);
+            return Err(rocket);
+        }
+    };
+
+    if let Err(e) = fs::create_dir_all(&root).await {
+        error!(
+            "Unable to create the uploads directory {}: {}",
+            root.display(),
+            e
+        );
+        return Err(rocket);
+    }
+
+    info!("Storing uploads in progress in {}", root.display());
+    Ok(rocket.manage(PartialUploads::new(root)))
+}
+
+fn uploads_root() -> Result<PathBuf, String> {
+    let root = env::var("TUS_UPLOADS_PATH")
+        .map(PathBuf::from)
+        .map_err(|_| "missing `TUS_UPLOADS_PATH` environment variable".to_string())?;
+
+    if !root.is_absolute() || root.parent().is_none() || root == env::temp_dir() {
+        return Err(format!(
+            "`TUS_UPLOADS_PATH` shall be an absolute path to a dedicated directory, {} is not",
+            root.display()
+        ));
+    }
+
+    Ok(root)
+}
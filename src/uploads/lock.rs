@@ -0,0 +1,95 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use diesel::QueryResult;
+use rocket::tokio::{self, task::JoinHandle, time::interval};
+use uuid::Uuid;
+
+use crate::db::{models::upload::Upload, pool::DbPool};
+
+/// Duration - in seconds - of the lease held on an upload.
+/// A lease left by a server that stopped expires after it.
+const LOCK_DURATION: i64 = 60;
+
+/// Interval - in seconds - between two renewals of a lease
+const LOCK_RENEWAL_INTERVAL: u64 = 20;
+
+/// Grants exclusive write access to an upload until dropped.
+///
+/// The lease is recorded in the database so that a single request or task
+/// writes to an upload at a time, whichever server instance handles it.
+/// It is renewed while held and released once dropped.
+pub struct UploadLock {
+    upload_uuid: Uuid,
+    holder: Uuid,
+    db: DbPool,
+    renewal: JoinHandle<()>,
+}
+
+impl UploadLock {
+    /// Leases an upload.
+    /// Returns `None` if the upload is already leased.
+    pub async fn acquire(db: &DbPool, upload_uuid: Uuid) -> QueryResult<Option<Self>> {
+        let holder = Uuid::new_v4();
+        let acquired = db
+            .run(move |c| Upload::acquire_lock(upload_uuid, holder, lock_until(), c))
+            .await?;
+
+        if !acquired {
+            return Ok(None);
+        }
+
+        let pool = db.clone();
+        let renewal = tokio::spawn(async move {
+            let mut ticker = interval(Duration::from_secs(LOCK_RENEWAL_INTERVAL));
+            // the first tick completes immediately
+            ticker.tick().await;
+
+            loop {
+                ticker.tick().await;
+
+                match pool
+                    .run(move |c| Upload::renew_lock(upload_uuid, holder, lock_until(), c))
+                    .await
+                {
+                    Ok(true) => (),
+                    Ok(false) => {
+                        warn!("The lock of upload {} was lost", upload_uuid);
+                        return;
+                    }
+                    Err(e) => error!("Unable to renew the lock of upload {}: {}", upload_uuid, e),
+                }
+            }
+        });
+
+        Ok(Some(Self {
+            upload_uuid,
+            holder,
+            db: db.clone(),
+            renewal,
+        }))
+    }
+}
+
+impl Drop for UploadLock {
+    fn drop(&mut self) {
+        self.renewal.abort();
+
+        let (db, upload_uuid, holder) = (self.db.clone(), self.upload_uuid, self.holder);
+        tokio::spawn(async move {
+            if let Err(e) = db
+                .run(move |c| Upload::release_lock(upload_uuid, holder, c))
+                .await
+            {
+                error!(
+                    "Unable to release the lock of upload {}: {}",
+                    upload_uuid, e
+                );
+            }
+        });
+    }
+}
+
+fn lock_until() -> chrono::NaiveDateTime {
+    (Utc::now() + chrono::Duration::seconds(LOCK_DURATION)).naive_utc()
+}
@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+
+use crate::{
+    errors::upload::UploadError,
+    graphql::types::{input::file::FileInput, scalars::Msat},
+};
+
+/// Parses the `Upload-Metadata` header of a resumable upload into the
+/// fields expected by the `uploadFile` mutation.
+///
+/// The header is a comma separated list of keys and base64 encoded values, e.g:
+/// `filename bXkgZmlsZS5tcDQ=,title TXkgZmlsZQ==,price_msat MTAwMDAw`.
+/// `filename`, `title` and `price_msat` are required, `description`, `price_currency`,
+/// `price_amount`, `payment_duration` and `published` are optional.
+pub fn parse_metadata(header: &str) -> Result<FileInput, UploadError> {
+    let mut values = HashMap::new();
+
+    for pair in header
+        .split(',')
+        .map(str::trim)
+        .filter(|pair| !pair.is_empty())
+    {
+        let (key, value) = match pair.split_once(' ') {
+            Some((key, value)) => (key, value.trim()),
+            None => (pair, ""),
+        };
+
+        let value = base64::decode(value)
+            .ok()
+            .and_then(|value| String::from_utf8(value).ok())
+            .ok_or_else(|| {
+                UploadError::InvalidMetadata(format!("`{}` is not valid base64", key))
+            })?;
+
+        values.insert(key.to_string(), value);
+    }
+
+    let filename = required(&values, "filename")?;
+    let title = required(&values, "title")?;
+    let price_msat = parse(&values, "price_msat")?
+        .ok_or_else(|| UploadError::InvalidMetadata("`price_msat` is required".to_string()))?;
+    if price_msat < 0 {
+        return Err(UploadError::InvalidMetadata(
+            "`price_msat` can't be negative".to_string(),
+        ));
+    }
+
+    Ok(FileInput {
+        filename,
+        title,
+        description: values.get("description").cloned(),
+        price_msat: Msat(price_msat),
+        price_currency: values
+            .get("price_currency")
+            .map(|currency| currency.trim().to_uppercase()),
+        price_amount: parse(&values, "price_amount")?,
+        payment_duration: parse(&values, "payment_duration")?,
+        published: parse(&values, "published")?.unwrap_or(false),
+        file: None,
+        preview: None,
+        thumbnail: None,
+    })
+}
+
+fn required(values: &HashMap<String, String>, key: &str) -> Result<String, UploadError> {
+    match values.get(key) {
+        Some(value) if !value.trim().is_empty() => Ok(value.clone()),
+        _ => Err(UploadError::InvalidMetadata(format!(
+            "`{}` is required",
+            key
+        ))),
+    }
+}
+
+fn parse<T: std::str::FromStr>(
+    values: &HashMap<String, String>,
+    key: &str,
+) -> Result<Option<T>, UploadError> {
+    match values.get(key) {
+        Some(value) => value
+            .trim()
+            .parse::<T>()
+            .map(Some)
+            .map_err(|_| UploadError::InvalidMetadata(format!("`{}` is invalid", key))),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata(price_msat: &str) -> String {
+        format!(
+            "filename {},title {},price_msat {}",
+            base64::encode("track.mp3"),
+            base64::encode("Track"),
+            base64::encode(price_msat)
+        )
+    }
+
+    #[test]
+    fn parses_the_price() {
+        let file_input = parse_metadata(&metadata("1500")).unwrap();
+
+        assert_eq!(file_input.filename, "track.mp3");
+        assert_eq!(file_input.price_msat.0, 1500);
+        assert!(!file_input.published);
+    }
+
+    #[test]
+    fn rejects_negative_prices() {
+        assert!(matches!(
+            parse_metadata(&metadata("-1")),
+            Err(UploadError::InvalidMetadata(_))
+        ));
+    }
+}
@@ -0,0 +1,8 @@
+pub mod collector;
+pub mod filename;
+pub mod finalizer;
+pub mod igniter;
+pub mod lock;
+pub mod metadata;
+pub mod multipart;
+pub mod partial;
@@ -0,0 +1,186 @@
+use std::collections::HashMap;
+
+use infer::{Infer, MatcherType};
+use juniper_rocket_multipart_handler::temp_file::TempFile;
+use uuid::Uuid;
+
+use crate::{
+    db::models::media::NewMedia,
+    errors::upload::UploadError,
+    extractor::{apply_metadata, extract_from_bytes},
+    graphql::types::input::file::FileInput,
+    storage::client::StorageClient,
+};
+
+/// Suffix of the multipart field providing the preview of a media
+const PREVIEW_SUFFIX: &str = ".preview";
+
+/// Suffix of the multipart field providing the thumbnail of a media
+const THUMBNAIL_SUFFIX: &str = ".thumbnail";
+
+/// The files uploaded for a media through a multipart request:
+/// the media file, e.g: in the `0` field, along with its optional
+/// preview and thumbnail in the `0.preview` and `0.thumbnail` fields.
+pub struct MediaFiles<'a> {
+    pub file: &'a TempFile,
+    pub preview: Option<&'a TempFile>,
+    pub thumbnail: Option<&'a TempFile>,
+}
+
+/// Groups the uploaded files by media, in the order of the
+/// field names of the media files (`0`, `1`, ...).
+pub fn group_media_files(
+    files_map: &HashMap<String, TempFile>,
+) -> Result<Vec<MediaFiles<'_>>, UploadError> {
+    let mut names = files_map
+        .keys()
+        .filter(|name| asset_of(name).is_none())
+        .collect::<Vec<&String>>();
+    names.sort_by_key(|name| (name.parse::<u64>().unwrap_or(u64::MAX), name.to_string()));
+
+    // An asset is always attached to a media file
+    for name in files_map.keys() {
+        if let Some(media_name) = asset_of(name) {
+            if !names.iter().any(|name| name.as_str() == media_name) {
+                return Err(UploadError::InvalidAsset(format!(
+                    "no media file provided for {}",
+                    name
+                )));
+            }
+        }
+    }
+
+    Ok(names
+        .into_iter()
+        .map(|name| MediaFiles {
+            file: &files_map[name],
+            preview: files_map.get(&format!("{}{}", name, PREVIEW_SUFFIX)),
+            thumbnail: files_map.get(&format!("{}{}", name, THUMBNAIL_SUFFIX)),
+        })
+        .collect())
+}
+
+/// Stores the files of a media uploaded by a user along with its extracted metadata
+/// and prepares its creation
+pub async fn store_media_files(
+    storage: &StorageClient,
+    files: &MediaFiles<'_>,
+    file_input: FileInput,
+    owner: Uuid,
+) -> Result<NewMedia, UploadError> {
+    if let Some(thumbnail) = files.thumbnail {
+        let is_image = Infer::new()
+            .get(thumbnail.get_content())
+            .map(|kind| kind.matcher_type() == MatcherType::Image)
+            .unwrap_or(false);
+
+        if !is_image {
+            return Err(UploadError::InvalidAsset(
+                "the thumbnail must be an image".to_string(),
+            ));
+        }
+    }
+
+    let stored = storage.store(files.file.get_content().clone()).await?;
+    let mut new_media =
+        NewMedia::from((stored, files.file.get_name().to_string(), file_input)).with_owner(owner);
+
+    if let Some(preview) = files.preview {
+        let stored = storage.store(preview.get_content().clone()).await?;
+        new_media = new_media.with_preview(stored, preview.get_name().to_string());
+    }
+
+    if let Some(thumbnail) = files.thumbnail {
+        let stored = storage.store(thumbnail.get_content().clone()).await?;
+        new_media = new_media.with_thumbnail(stored);
+    }
+
+    let metadata = extract_from_bytes(
+        files.file.get_content().clone(),
+        files.file.get_name().to_string(),
+    )
+    .await;
+
+    Ok(apply_metadata(new_media, metadata, storage).await)
+}
+
+/// The field name of the media file an asset field belongs to
+fn asset_of(name: &str) -> Option<&str> {
+    name.strip_suffix(PREVIEW_SUFFIX)
+        .or_else(|| name.strip_suffix(THUMBNAIL_SUFFIX))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+    use crate::{storage::local::LocalBackend, uploads::metadata::parse_metadata};
+
+    fn files(names: &[&str]) -> HashMap<String, TempFile> {
+        names
+            .iter()
+            .map(|name| {
+                let file = TempFile {
+                    local_path: env::temp_dir(),
+                    name: format!("{}.bin", name),
+                    size: None,
+                    content: name.as_bytes().to_vec().into(),
+                };
+
+                (name.to_string(), file)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn media_files_are_grouped_with_their_assets_in_order() {
+        let files_map = files(&["10", "1.thumbnail", "0", "1", "0.preview"]);
+        let media_files = group_media_files(&files_map).unwrap();
+
+        let names = media_files
+            .iter()
+            .map(|files| files.file.get_name())
+            .collect::<Vec<&str>>();
+        assert_eq!(names, ["0.bin", "1.bin", "10.bin"]);
+
+        assert_eq!(
+            media_files[0].preview.map(TempFile::get_name),
+            Some("0.preview.bin")
+        );
+        assert!(media_files[0].thumbnail.is_none());
+        assert!(media_files[1].preview.is_none());
+        assert_eq!(
+            media_files[1].thumbnail.map(TempFile::get_name),
+            Some("1.thumbnail.bin")
+        );
+        assert!(media_files[2].preview.is_none() && media_files[2].thumbnail.is_none());
+    }
+
+    #[test]
+    fn assets_without_media_file_are_rejected() {
+        assert!(matches!(
+            group_media_files(&files(&["0", "1.preview"])),
+            Err(UploadError::InvalidAsset(_))
+        ));
+    }
+
+    #[rocket::async_test]
+    async fn thumbnails_must_be_images() {
+        let files_map = files(&["0", "0.thumbnail"]);
+        let media_files = group_media_files(&files_map).unwrap();
+        let storage = StorageClient::from(LocalBackend::new(env::temp_dir()));
+        let file_input = parse_metadata(&format!(
+            "filename {},title {},price_msat {}",
+            base64::encode("track.mp3"),
+            base64::encode("Track"),
+            base64::encode("0")
+        ))
+        .unwrap();
+
+        assert!(matches!(
+            store_media_files(&storage, &media_files[0], file_input, Uuid::new_v4()).await,
+            Err(UploadError::InvalidAsset(_))
+        ));
+    }
+}
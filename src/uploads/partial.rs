@@ -0,0 +1,45 @@
+use std::{io, path::PathBuf};
+
+use rocket::tokio::fs;
+use uuid::Uuid;
+
+/// The bytes received so far for the uploads in progress.
+///
+/// Each upload is appended to its own file in the uploads directory,
+/// which must outlive the server so uploads can be resumed after a restart.
+#[derive(Clone)]
+pub struct PartialUploads {
+    root: PathBuf,
+}
+
+impl PartialUploads {
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    pub fn path(&self, uuid: &Uuid) -> PathBuf {
+        self.root.join(format!("{}.part", uuid))
+    }
+
+    /// Creates the empty file of a new upload
+    pub async fn create(&self, uuid: &Uuid) -> io::Result<()> {
+        fs::File::create(self.path(uuid)).await.map(|_| ())
+    }
+
+    /// The number of bytes received for an upload,
+    /// `0` if its file does not exist anymore
+    pub async fn length(&self, uuid: &Uuid) -> io::Result<u64> {
+        match fs::metadata(self.path(uuid)).await {
+            Ok(metadata) => Ok(metadata.len()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(0),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub async fn remove(&self, uuid: &Uuid) -> io::Result<()> {
+        match fs::remove_file(self.path(uuid)).await {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+}